@@ -1,7 +1,11 @@
+use std::fmt;
+
 use chrono::{DateTime, Utc};
+use compact_str::CompactString;
 use crux_core::capability::{CapabilityContext, Operation};
+use futures::future::{self, AbortHandle};
 use futures::{Stream, StreamExt as _};
-use jord::{Angle, LatLong, Length, Speed};
+use jord::{spherical::Sphere, Angle, LatLong, Length, Speed};
 use serde::{Deserialize, Serialize};
 
 /// The coordinates, altitude, speed and bearing of a device. (This type is used only by the shell
@@ -28,6 +32,36 @@ pub struct Position {
     pub heading: Option<f64>,
     /// The velocity of the device in meters per second. (Optional)
     pub volocity: Option<f64>,
+    /// How the position was determined. (Optional)
+    ///
+    /// Browsers don't tell this, but native shells may.
+    #[serde(default)]
+    pub source: Option<PositionSource>,
+    /// The number of satellites used to determine the position. (Optional)
+    #[serde(default)]
+    pub satellites: Option<u8>,
+}
+
+/// How a position was determined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PositionSource {
+    /// Satellite navigation.
+    Gps,
+    /// Wi-Fi or cell towers.
+    Network,
+    /// A combination of several sources.
+    Fused,
+}
+
+impl fmt::Display for PositionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gps => write!(f, "GPS"),
+            Self::Network => write!(f, "Network"),
+            Self::Fused => write!(f, "Fused"),
+        }
+    }
 }
 
 /// Options when retrieving a position.
@@ -53,6 +87,66 @@ pub struct GeoOptions {
     /// take the liberty to save resources by responding more quickly and/or using less power.
     /// Default: false.
     pub enable_high_accuracy: bool,
+    /// When watching the position, only positions at least this far from the last reported
+    /// position are reported. The first position is always reported.
+    ///
+    /// This is handled by the capability and not by the shell.
+    pub min_distance: Option<Length>,
+    /// When watching the position, positions with a worse (larger) accuracy than this are
+    /// dropped.
+    ///
+    /// This is handled by the capability and not by the shell.
+    pub max_accuracy: Option<Length>,
+    /// Whether to keep positions with unknown accuracy when `max_accuracy` is set.
+    pub keep_unknown_accuracy: bool,
+    /// How the shell should retry after an error when watching the position.
+    pub retry: RetryPolicy,
+}
+
+/// How the shell should retry watching the position after an error.
+///
+/// The first retry happens immediately, and retry number n (n > 0) happens after
+/// `base_delay * factor^n` milliseconds, but never after more than `max_delay` milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    /// The base delay in milliseconds.
+    pub base_delay: u64,
+    /// The factor which the delay grows with for each retry.
+    pub factor: f64,
+    /// The maximum delay in milliseconds.
+    pub max_delay: u64,
+    /// The maximum number of retries. `None` means retrying forever.
+    ///
+    /// When no more retries should be made, the shell should respond with
+    /// `GeoResponse::PositionUnavailableError` and stop watching.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay: 250,
+            factor: 2.0,
+            max_delay: 10000,
+            max_attempts: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay in milliseconds before retry number `n` (starting at 0), or `None` if no more
+    /// retries should be made.
+    pub fn delay(&self, n: u32) -> Option<u64> {
+        if self.max_attempts.is_some_and(|max| n >= max) {
+            None
+        } else if n == 0 {
+            Some(0)
+        } else {
+            let delay = self.base_delay as f64 * self.factor.powi(n.try_into().unwrap_or(i32::MAX));
+            Some(delay.min(self.max_delay as f64) as u64)
+        }
+    }
 }
 
 /// A position operation.
@@ -61,22 +155,55 @@ pub struct GeoOptions {
 pub enum GeoRequest {
     WatchPosition(GeoOptions),
     ClearWatch,
+    /// Get the current position once. This is independent of any active watch.
+    GetCurrentPosition(GeoOptions),
+    /// Stop waiting for the positions requested with `GetCurrentPosition`. The shell must drop
+    /// those requests without responding to them.
+    CancelCurrentPosition,
 }
 
 /// An error which may occur when retrieving the current position.
-#[derive(
-    Clone, Debug, PartialEq, Serialize, Deserialize, derive_more::Display, derive_more::Error,
-)]
+///
+/// The variants with a message are like the ones without, but with details from the shell, like
+/// the message of a `GeolocationPositionError` in a browser. They were added after the others so
+/// that errors encoded by shells which don't send messages can still be decoded.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, derive_more::Error)]
 #[serde(rename_all = "camelCase")]
 #[repr(u8)]
 pub enum GeoError {
-    #[display("Permission denied")]
     PermissionDenied = 1,
-    #[display("Position unavailable")]
     PositionUnavailable = 2,
     /// The time allowed to acquire the position was reached before the information was obtained.
-    #[display("Position retrieval timed out")]
     Timeout = 3,
+    PositionUnavailableWithMessage {
+        message: CompactString,
+    } = 4,
+    TimeoutWithMessage {
+        message: CompactString,
+    } = 5,
+}
+
+impl GeoError {
+    /// Whether this is a `Timeout` error, with or without a message.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Self::Timeout | Self::TimeoutWithMessage { .. })
+    }
+}
+
+impl fmt::Display for GeoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PermissionDenied => write!(f, "Permission denied"),
+            Self::PositionUnavailable => write!(f, "Position unavailable"),
+            Self::Timeout => write!(f, "Position retrieval timed out"),
+            Self::PositionUnavailableWithMessage { message } => {
+                write!(f, "Position unavailable: {message}")
+            }
+            Self::TimeoutWithMessage { message } => {
+                write!(f, "Position retrieval timed out: {message}")
+            }
+        }
+    }
 }
 
 pub type GeoResult<T, E = GeoError> = Result<T, E>;
@@ -93,6 +220,41 @@ pub enum GeoResponse {
     PermissionDeniedError,
     PositionUnavailableError,
     TimeoutError,
+    /// The position is unavailable. `message` tells why, as reported by the platform.
+    PositionUnavailableErrorWithMessage {
+        message: CompactString,
+    },
+    /// The position couldn't be retrieved in time. `message` tells more, as reported by the
+    /// platform.
+    TimeoutErrorWithMessage {
+        message: CompactString,
+    },
+}
+
+impl GeoResponse {
+    /// Create a position response like the shell would send. This is mainly useful to test apps
+    /// without a shell, see `response_to_geo_info`.
+    pub fn position(
+        coords: LatLong,
+        altitude: Option<Length>,
+        accuracy: Option<Length>,
+        timestamp: DateTime<Utc>,
+    ) -> Self {
+        Self::Position {
+            coords: Position {
+                latitude: coords.latitude().as_degrees(),
+                longitude: coords.longitude().as_degrees(),
+                altitude: altitude.map(|x| x.as_metres()),
+                accuracy: accuracy.map(|x| x.as_metres()),
+                altitude_accuracy: None,
+                heading: None,
+                volocity: None,
+                source: None,
+                satellites: None,
+            },
+            timestamp: timestamp.timestamp_millis(),
+        }
+    }
 }
 
 impl Operation for GeoRequest {
@@ -122,6 +284,17 @@ pub struct GeoInfo {
     pub bearing: Option<Angle>,
     /// The velocity of the device. (Optional)
     pub volocity: Option<Speed>,
+    /// How the position was determined. (Optional)
+    pub source: Option<PositionSource>,
+    /// The number of satellites used to determine the position. (Optional)
+    pub satellites: Option<u8>,
+}
+
+/// A handle to cancel a pending request for the current position, see
+/// `Geolocation::get_current_position`.
+#[derive(Debug, Clone)]
+pub struct CancelHandle {
+    abort: AbortHandle,
 }
 
 /// The Geolocation capability API
@@ -157,7 +330,9 @@ impl<Ev> crux_core::Capability<Ev> for Geolocation<Ev> {
     fn register_types(
         generator: &mut crux_core::typegen::TypeGen,
     ) -> crux_core::typegen::GeoResult {
+        generator.register_type::<PositionSource>()?;
         generator.register_type::<Position>()?;
+        generator.register_type::<RetryPolicy>()?;
         generator.register_type::<GeoOptions>()?;
         generator.register_type::<GeoError>()?;
         generator.register_type::<Self::Operation>()?;
@@ -201,9 +376,69 @@ where
         &self,
         options: GeoOptions,
     ) -> impl Stream<Item = GeoResult<GeoInfo>> {
-        self.context
+        let stream = self
+            .context
             .stream_from_shell(GeoRequest::WatchPosition(options))
             .map(response_to_geo_info)
+            .filter(move |x| future::ready(accurate_enough(x, &options)));
+        filter_min_distance(stream, options.min_distance)
+    }
+
+    /// Get the current position once.
+    ///
+    /// No watch is started and any active watch is left untouched. The request can be cancelled
+    /// with `cancel_current_position` and the returned handle.
+    pub fn get_current_position<F>(&self, options: GeoOptions, callback: F) -> CancelHandle
+    where
+        F: FnOnce(GeoResult<GeoInfo>) -> Ev + Send + Sync + 'static,
+    {
+        let (task, abort) = future::abortable({
+            let context = self.context.clone();
+            let this = self.clone();
+
+            async move {
+                let result = this.get_current_position_async(options).await;
+                context.update_app(callback(result));
+            }
+        });
+        self.context.spawn(async move {
+            // The task is only aborted by `cancel_current_position`.
+            let _ = task.await;
+        });
+        CancelHandle { abort }
+    }
+
+    /// Cancel a request made with `get_current_position`.
+    ///
+    /// The callback of the request is never called, even if the shell responds anyway. Instead
+    /// the waiting future is dropped. The shell is told to stop waiting for the position.
+    pub fn cancel_current_position(&self, handle: CancelHandle) {
+        handle.abort.abort();
+        self.context.spawn({
+            let this = self.clone();
+            async move { this.cancel_current_position_async().await }
+        });
+    }
+
+    /// Tell the shell to stop waiting for positions requested with `get_current_position`.
+    ///
+    /// This doesn't abort the futures of the requests, which are left unresolved.
+    /// This is an async call to use with [`crux_core::compose::Compose`].
+    pub async fn cancel_current_position_async(&self) {
+        self.context
+            .notify_shell(GeoRequest::CancelCurrentPosition)
+            .await
+    }
+
+    /// Get the current position once.
+    ///
+    /// This is an async call to use with [`crux_core::compose::Compose`].
+    pub async fn get_current_position_async(&self, options: GeoOptions) -> GeoResult<GeoInfo> {
+        let response = self
+            .context
+            .request_from_shell(GeoRequest::GetCurrentPosition(options))
+            .await;
+        response_to_geo_info(response)
     }
 
     /// Cancel any existing position watcher.
@@ -223,7 +458,61 @@ where
     }
 }
 
-fn response_to_geo_info(response: GeoResponse) -> GeoResult<GeoInfo> {
+/// Check if a position is accurate enough according to `options.max_accuracy` and
+/// `options.keep_unknown_accuracy`. Errors are always accepted.
+fn accurate_enough(geo: &GeoResult<GeoInfo>, options: &GeoOptions) -> bool {
+    match (geo, options.max_accuracy) {
+        (Ok(geo), Some(max_accuracy)) => geo
+            .accuracy
+            .map(|accuracy| accuracy <= max_accuracy)
+            .unwrap_or(options.keep_unknown_accuracy),
+        _ => true,
+    }
+}
+
+/// Filter out positions which are closer than `min_distance` to the last position that passed the
+/// filter. Errors are always passed through.
+fn filter_min_distance(
+    stream: impl Stream<Item = GeoResult<GeoInfo>>,
+    min_distance: Option<Length>,
+) -> impl Stream<Item = GeoResult<GeoInfo>> {
+    let mut last: Option<LatLong> = None;
+    stream.filter(move |x| {
+        let keep = match (x, last, min_distance) {
+            (Ok(geo), Some(last), Some(min_distance)) => {
+                Sphere::EARTH.distance(last.to_nvector(), geo.coords.to_nvector()) >= min_distance
+            }
+            _ => true,
+        };
+        if let (true, Ok(geo)) = (keep, x) {
+            last = Some(geo.coords);
+        }
+        future::ready(keep)
+    })
+}
+
+/// Convert a response from the shell to the `GeoInfo` or error which the app receives.
+///
+/// Together with `GeoResponse::position`, this lets tests feed positions to an app:
+///
+/// ```
+/// use chrono::DateTime;
+/// use crux_geolocation::{response_to_geo_info, GeoResponse};
+/// use jord::{LatLong, Length};
+///
+/// let timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+/// let response = GeoResponse::position(
+///     LatLong::from_degrees(59.3, 18.1),
+///     None,
+///     Some(Length::from_metres(5.0)),
+///     timestamp,
+/// );
+/// let geo = response_to_geo_info(response).unwrap();
+/// assert_eq!(geo.timestamp, timestamp);
+/// assert_eq!(geo.accuracy, Some(Length::from_metres(5.0)));
+/// // Then send `Event::GeolocationUpdate(Ok(geo))` or similar to the app.
+/// ```
+pub fn response_to_geo_info(response: GeoResponse) -> GeoResult<GeoInfo> {
     match response {
         GeoResponse::Position {
             timestamp,
@@ -236,6 +525,8 @@ fn response_to_geo_info(response: GeoResponse) -> GeoResult<GeoInfo> {
                     altitude_accuracy,
                     heading,
                     volocity,
+                    source,
+                    satellites,
                 },
         } => Ok(GeoInfo {
             timestamp: DateTime::from_timestamp_millis(timestamp)
@@ -246,9 +537,266 @@ fn response_to_geo_info(response: GeoResponse) -> GeoResult<GeoInfo> {
             altitude_accuracy: altitude_accuracy.map(Length::from_metres),
             bearing: heading.map(Angle::from_degrees),
             volocity: volocity.map(Speed::from_metres_per_second),
+            source,
+            satellites,
         }),
         GeoResponse::PermissionDeniedError => Err(GeoError::PermissionDenied),
         GeoResponse::PositionUnavailableError => Err(GeoError::PositionUnavailable),
         GeoResponse::TimeoutError => Err(GeoError::Timeout),
+        GeoResponse::PositionUnavailableErrorWithMessage { message } => {
+            Err(GeoError::PositionUnavailableWithMessage { message })
+        }
+        GeoResponse::TimeoutErrorWithMessage { message } => {
+            Err(GeoError::TimeoutWithMessage { message })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, stream};
+
+    use super::*;
+
+    /// Create a `GeoInfo` at some coordinates (in degrees).
+    fn geo(latitude: f64, longitude: f64) -> GeoInfo {
+        GeoInfo {
+            timestamp: DateTime::UNIX_EPOCH,
+            coords: LatLong::from_degrees(latitude, longitude),
+            altitude: None,
+            accuracy: None,
+            altitude_accuracy: None,
+            bearing: None,
+            volocity: None,
+            source: None,
+            satellites: None,
+        }
+    }
+
+    /// Create a position response with some accuracy in meters.
+    fn response_with_accuracy(accuracy: Option<f64>) -> GeoResponse {
+        GeoResponse::Position {
+            coords: Position {
+                latitude: 1.0,
+                longitude: 2.0,
+                altitude: None,
+                accuracy,
+                altitude_accuracy: None,
+                heading: None,
+                volocity: None,
+                source: None,
+                satellites: None,
+            },
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy::default();
+        let delays = (0..8).map(|n| policy.delay(n)).collect::<Vec<_>>();
+        assert_eq!(delays, [0, 500, 1000, 2000, 4000, 8000, 10000, 10000].map(Some));
+        let policy = RetryPolicy {
+            base_delay: 100,
+            factor: 3.0,
+            max_delay: 1000,
+            max_attempts: Some(4),
+        };
+        let delays = (0..6).map(|n| policy.delay(n)).collect::<Vec<_>>();
+        assert_eq!(delays, [Some(0), Some(300), Some(900), Some(1000), None, None]);
+    }
+
+    #[test]
+    fn test_response_to_geo_info() {
+        let timestamp = DateTime::from_timestamp_millis(1_700_000_000_123).unwrap();
+        let response = GeoResponse::position(
+            LatLong::from_degrees(1.0, 2.0),
+            Some(Length::from_metres(30.0)),
+            Some(Length::from_metres(5.0)),
+            timestamp,
+        );
+        let geo = response_to_geo_info(response).unwrap();
+        assert_eq!(geo.timestamp, timestamp);
+        assert!((geo.coords.latitude().as_degrees() - 1.0).abs() < 1e-9);
+        assert!((geo.coords.longitude().as_degrees() - 2.0).abs() < 1e-9);
+        assert_eq!(geo.altitude, Some(Length::from_metres(30.0)));
+        assert_eq!(geo.accuracy, Some(Length::from_metres(5.0)));
+        assert_eq!(geo.altitude_accuracy, None);
+        assert_eq!(geo.bearing, None);
+        assert_eq!(geo.volocity, None);
+        assert_eq!(geo.source, None);
+        assert_eq!(geo.satellites, None);
+
+        let GeoResponse::Position { coords, timestamp } = response_with_accuracy(None) else {
+            unreachable!()
+        };
+        let response = GeoResponse::Position {
+            coords: Position {
+                heading: Some(90.0),
+                volocity: Some(1.5),
+                altitude_accuracy: Some(3.0),
+                source: Some(PositionSource::Gps),
+                satellites: Some(9),
+                ..coords
+            },
+            timestamp,
+        };
+        let geo = response_to_geo_info(response).unwrap();
+        assert_eq!(geo.timestamp, DateTime::UNIX_EPOCH);
+        assert_eq!(geo.bearing, Some(Angle::from_degrees(90.0)));
+        assert_eq!(geo.volocity, Some(Speed::from_metres_per_second(1.5)));
+        assert_eq!(geo.altitude_accuracy, Some(Length::from_metres(3.0)));
+        assert_eq!(geo.source, Some(PositionSource::Gps));
+        assert_eq!(geo.satellites, Some(9));
+
+        assert_eq!(
+            response_to_geo_info(GeoResponse::PermissionDeniedError),
+            Err(GeoError::PermissionDenied)
+        );
+        assert_eq!(
+            response_to_geo_info(GeoResponse::PositionUnavailableError),
+            Err(GeoError::PositionUnavailable)
+        );
+        assert_eq!(
+            response_to_geo_info(GeoResponse::TimeoutError),
+            Err(GeoError::Timeout)
+        );
+    }
+
+    #[test]
+    fn test_position_serde() {
+        // A position from a shell which doesn't know about the source and the satellites.
+        let json = r#"{"latitude":1.0,"longitude":2.0,"altitude":null,"accuracy":5.0,
+            "altitudeAccuracy":null,"heading":null,"volocity":null}"#;
+        let position = serde_json::from_str::<Position>(json).unwrap();
+        assert_eq!(position.source, None);
+        assert_eq!(position.satellites, None);
+
+        let position = Position {
+            source: Some(PositionSource::Fused),
+            satellites: Some(12),
+            ..position
+        };
+        let json = serde_json::to_string(&position).unwrap();
+        assert!(
+            json.contains(r#""source":"fused","satellites":12"#),
+            "{json}"
+        );
+        assert_eq!(serde_json::from_str::<Position>(&json).unwrap(), position);
+    }
+
+    #[test]
+    fn test_error_message() {
+        let error = response_to_geo_info(GeoResponse::PositionUnavailableErrorWithMessage {
+            message: "Network location provider unavailable".into(),
+        })
+        .unwrap_err();
+        assert_eq!(
+            error,
+            GeoError::PositionUnavailableWithMessage {
+                message: "Network location provider unavailable".into()
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Position unavailable: Network location provider unavailable"
+        );
+        assert_eq!(
+            GeoError::Timeout.to_string(),
+            "Position retrieval timed out"
+        );
+        assert!(GeoError::Timeout.is_timeout());
+        assert!(GeoError::TimeoutWithMessage {
+            message: "Timeout expired".into()
+        }
+        .is_timeout());
+        assert!(!GeoError::PositionUnavailable.is_timeout());
+    }
+
+    #[test]
+    fn test_error_serde() {
+        // Errors as encoded by shells which don't send messages.
+        assert_eq!(
+            serde_json::from_str::<GeoResponse>(r#""PositionUnavailableError""#).unwrap(),
+            GeoResponse::PositionUnavailableError
+        );
+        assert_eq!(
+            serde_json::from_str::<GeoResponse>(r#""TimeoutError""#).unwrap(),
+            GeoResponse::TimeoutError
+        );
+        assert_eq!(
+            serde_json::from_str::<GeoError>(r#""positionUnavailable""#).unwrap(),
+            GeoError::PositionUnavailable
+        );
+        assert_eq!(
+            serde_json::from_str::<GeoError>(r#""timeout""#).unwrap(),
+            GeoError::Timeout
+        );
+
+        let response = GeoResponse::TimeoutErrorWithMessage {
+            message: "Timeout expired".into(),
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            json,
+            r#"{"TimeoutErrorWithMessage":{"message":"Timeout expired"}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<GeoResponse>(&json).unwrap(),
+            response
+        );
+    }
+
+    #[test]
+    fn test_accurate_enough() {
+        let mut options = GeoOptions {
+            max_accuracy: Some(Length::from_metres(10.0)),
+            ..GeoOptions::default()
+        };
+        let accurate = response_to_geo_info(response_with_accuracy(Some(5.0)));
+        let inaccurate = response_to_geo_info(response_with_accuracy(Some(50.0)));
+        let unknown = response_to_geo_info(response_with_accuracy(None));
+        let error = response_to_geo_info(GeoResponse::TimeoutError);
+        assert!(accurate_enough(&accurate, &options));
+        assert!(!accurate_enough(&inaccurate, &options));
+        assert!(!accurate_enough(&unknown, &options));
+        assert!(accurate_enough(&error, &options));
+        options.keep_unknown_accuracy = true;
+        assert!(accurate_enough(&unknown, &options));
+        options.max_accuracy = None;
+        assert!(accurate_enough(&inaccurate, &options));
+    }
+
+    #[test]
+    fn test_filter_min_distance() {
+        // 0.0001 degrees of latitude is about 11 meters.
+        let positions = vec![
+            Ok(geo(0.0, 0.0)),
+            Ok(geo(0.00001, 0.0)),
+            Ok(geo(0.00005, 0.0)),
+            Err(GeoError::Timeout),
+            Ok(geo(0.0001, 0.0)),
+            Ok(geo(0.00015, 0.0)),
+            Ok(geo(0.0002, 0.0)),
+        ];
+        let filtered = block_on(
+            filter_min_distance(
+                stream::iter(positions.clone()),
+                Some(Length::from_metres(10.0)),
+            )
+            .collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            filtered,
+            vec![
+                Ok(geo(0.0, 0.0)),
+                Err(GeoError::Timeout),
+                Ok(geo(0.0001, 0.0)),
+                Ok(geo(0.0002, 0.0)),
+            ]
+        );
+        let unfiltered =
+            block_on(filter_min_distance(stream::iter(positions.clone()), None).collect::<Vec<_>>());
+        assert_eq!(unfiltered, positions);
     }
 }
@@ -0,0 +1,120 @@
+use compact_str::CompactString;
+use crux_core::capability::{CapabilityContext, Operation};
+use serde::{Deserialize, Serialize};
+
+/// A request to let the user pick a file which is sent to the app.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileUploadRequest {
+    /// The file types to accept, as in the `accept` attribute of an HTML file input. For instance
+    /// `".gpx,application/gpx+xml"`. `None` means that any file is accepted.
+    pub accept: Option<CompactString>,
+}
+
+/// The file picked by the user.
+///
+/// If the user doesn't pick any file, the shell doesn't respond at all.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileUploadResponse {
+    pub file_name: Option<CompactString>,
+    pub content: Vec<u8>,
+}
+
+impl Operation for FileUploadRequest {
+    type Output = FileUploadResponse;
+}
+
+/// The FileUpload capability API.
+///
+/// This capability allows the app to ask the user for a file.
+pub struct FileUpload<Ev> {
+    context: CapabilityContext<FileUploadRequest, Ev>,
+}
+
+impl<Ev> Clone for FileUpload<Ev> {
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+        }
+    }
+}
+
+impl<Ev> crux_core::Capability<Ev> for FileUpload<Ev> {
+    type Operation = FileUploadRequest;
+    type MappedSelf<MappedEv> = FileUpload<MappedEv>;
+
+    fn map_event<F, NewEv>(&self, f: F) -> Self::MappedSelf<NewEv>
+    where
+        F: Fn(NewEv) -> Ev + Send + Sync + 'static,
+        Ev: 'static,
+        NewEv: 'static + Send,
+    {
+        FileUpload::new(self.context.map_event(f))
+    }
+
+    #[cfg(feature = "typegen")]
+    fn register_types(generator: &mut crux_core::typegen::TypeGen) -> crux_core::typegen::Result {
+        generator.register_type::<Self::Operation>()?;
+        generator.register_type::<<Self::Operation as Operation>::Output>()?;
+        Ok(())
+    }
+}
+
+impl<Ev> FileUpload<Ev>
+where
+    Ev: 'static,
+{
+    pub fn new(context: CapabilityContext<FileUploadRequest, Ev>) -> Self {
+        Self { context }
+    }
+
+    pub fn file_upload<F>(&self, accept: Option<impl Into<CompactString>>, callback: F)
+    where
+        F: FnOnce(FileUploadResponse) -> Ev + Send + Sync + 'static,
+    {
+        let req = FileUploadRequest {
+            accept: accept.map(Into::into),
+        };
+        self.context.spawn({
+            let context = self.context.clone();
+            let this = self.clone();
+            async move {
+                let response = this.file_upload_async(req).await;
+                context.update_app(callback(response));
+            }
+        });
+    }
+
+    pub async fn file_upload_async(&self, req: FileUploadRequest) -> FileUploadResponse {
+        self.context.request_from_shell(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let req = FileUploadRequest {
+            accept: Some(".gpx".into()),
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(json, r#"{"accept":".gpx"}"#);
+        assert_eq!(
+            serde_json::from_str::<FileUploadRequest>(&json).unwrap(),
+            req
+        );
+
+        let response = FileUploadResponse {
+            file_name: Some("walk.gpx".into()),
+            content: b"<gpx/>".to_vec(),
+        };
+        let bytes = bincode::serialize(&response).unwrap();
+        assert_eq!(
+            bincode::deserialize::<FileUploadResponse>(&bytes).unwrap(),
+            response
+        );
+    }
+}
@@ -1,2 +1,10 @@
 mod file_download;
+mod file_upload;
+mod notify;
+mod reverse_geocode;
+mod screen_wake;
 pub use file_download::*;
+pub use file_upload::*;
+pub use notify::*;
+pub use reverse_geocode::*;
+pub use screen_wake::*;
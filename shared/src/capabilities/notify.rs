@@ -0,0 +1,121 @@
+use compact_str::CompactString;
+use crux_core::capability::{CapabilityContext, Operation};
+use serde::{Deserialize, Serialize};
+
+/// The kind of a notification. The shell may for instance choose a sound or a vibration pattern
+/// from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NotifyKind {
+    Info,
+    /// A target has been reached.
+    Arrival,
+    Warning,
+}
+
+/// A request to get the attention of the user with a message, also when the app isn't visible.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotifyUserOperation {
+    pub kind: NotifyKind,
+    pub message: CompactString,
+}
+
+/// An empty response.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NotifyUserResponse {}
+
+impl Operation for NotifyUserOperation {
+    type Output = NotifyUserResponse;
+}
+
+/// The Notify capability API.
+///
+/// This capability allows the app to notify the user. How, for instance with a system
+/// notification, a sound or a vibration, is up to the shell.
+pub struct Notify<Ev> {
+    context: CapabilityContext<NotifyUserOperation, Ev>,
+}
+
+impl<Ev> Clone for Notify<Ev> {
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+        }
+    }
+}
+
+impl<Ev> crux_core::Capability<Ev> for Notify<Ev> {
+    type Operation = NotifyUserOperation;
+    type MappedSelf<MappedEv> = Notify<MappedEv>;
+
+    fn map_event<F, NewEv>(&self, f: F) -> Self::MappedSelf<NewEv>
+    where
+        F: Fn(NewEv) -> Ev + Send + Sync + 'static,
+        Ev: 'static,
+        NewEv: 'static + Send,
+    {
+        Notify::new(self.context.map_event(f))
+    }
+
+    #[cfg(feature = "typegen")]
+    fn register_types(generator: &mut crux_core::typegen::TypeGen) -> crux_core::typegen::Result {
+        generator.register_type::<NotifyKind>()?;
+        generator.register_type::<Self::Operation>()?;
+        generator.register_type::<<Self::Operation as Operation>::Output>()?;
+        Ok(())
+    }
+}
+
+impl<Ev> Notify<Ev>
+where
+    Ev: 'static,
+{
+    pub fn new(context: CapabilityContext<NotifyUserOperation, Ev>) -> Self {
+        Self { context }
+    }
+
+    pub fn notify_user(&self, kind: NotifyKind, message: impl Into<CompactString>) {
+        let req = NotifyUserOperation {
+            kind,
+            message: message.into(),
+        };
+        self.context.spawn({
+            let this = self.clone();
+            async move {
+                this.notify_user_async(req).await;
+            }
+        });
+    }
+
+    pub async fn notify_user_async(&self, req: NotifyUserOperation) {
+        self.context.notify_shell(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let req = NotifyUserOperation {
+            kind: NotifyKind::Arrival,
+            message: "You have arrived at Home.".into(),
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"arrival","message":"You have arrived at Home."}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<NotifyUserOperation>(&json).unwrap(),
+            req
+        );
+        let bytes = bincode::serialize(&req).unwrap();
+        assert_eq!(
+            bincode::deserialize::<NotifyUserOperation>(&bytes).unwrap(),
+            req
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use compact_str::CompactString;
+use crux_core::capability::{CapabilityContext, Operation};
+use jord::LatLong;
+use serde::{Deserialize, Serialize};
+
+/// A request to find a human readable name of the place at some coordinates.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReverseGeocodeOperation {
+    pub coords: LatLong,
+}
+
+/// The name of a place, or `None` if it couldn't be found.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReverseGeocodeResponse {
+    pub name: Option<CompactString>,
+}
+
+impl Operation for ReverseGeocodeOperation {
+    type Output = ReverseGeocodeResponse;
+}
+
+/// The ReverseGeocode capability API.
+///
+/// This capability allows the app to ask the shell for the name of a place. How the name is found,
+/// for instance with a web service, is up to the shell.
+pub struct ReverseGeocode<Ev> {
+    context: CapabilityContext<ReverseGeocodeOperation, Ev>,
+}
+
+impl<Ev> Clone for ReverseGeocode<Ev> {
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+        }
+    }
+}
+
+impl<Ev> crux_core::Capability<Ev> for ReverseGeocode<Ev> {
+    type Operation = ReverseGeocodeOperation;
+    type MappedSelf<MappedEv> = ReverseGeocode<MappedEv>;
+
+    fn map_event<F, NewEv>(&self, f: F) -> Self::MappedSelf<NewEv>
+    where
+        F: Fn(NewEv) -> Ev + Send + Sync + 'static,
+        Ev: 'static,
+        NewEv: 'static + Send,
+    {
+        ReverseGeocode::new(self.context.map_event(f))
+    }
+
+    #[cfg(feature = "typegen")]
+    fn register_types(generator: &mut crux_core::typegen::TypeGen) -> crux_core::typegen::Result {
+        generator.register_type::<Self::Operation>()?;
+        generator.register_type::<<Self::Operation as Operation>::Output>()?;
+        Ok(())
+    }
+}
+
+impl<Ev> ReverseGeocode<Ev>
+where
+    Ev: 'static,
+{
+    pub fn new(context: CapabilityContext<ReverseGeocodeOperation, Ev>) -> Self {
+        Self { context }
+    }
+
+    pub fn reverse_geocode<F>(&self, coords: LatLong, callback: F)
+    where
+        F: FnOnce(ReverseGeocodeResponse) -> Ev + Send + Sync + 'static,
+    {
+        self.context.spawn({
+            let context = self.context.clone();
+            let this = self.clone();
+            async move {
+                let response = this.reverse_geocode_async(coords).await;
+                context.update_app(callback(response));
+            }
+        });
+    }
+
+    pub async fn reverse_geocode_async(&self, coords: LatLong) -> ReverseGeocodeResponse {
+        self.context
+            .request_from_shell(ReverseGeocodeOperation { coords })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let req = ReverseGeocodeOperation {
+            coords: LatLong::from_degrees(59.3, 18.1),
+        };
+        let bytes = bincode::serialize(&req).unwrap();
+        assert_eq!(
+            bincode::deserialize::<ReverseGeocodeOperation>(&bytes).unwrap(),
+            req
+        );
+
+        let response = ReverseGeocodeResponse {
+            name: Some("Stockholm".into()),
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(json, r#"{"name":"Stockholm"}"#);
+        assert_eq!(
+            serde_json::from_str::<ReverseGeocodeResponse>(&json).unwrap(),
+            response
+        );
+    }
+}
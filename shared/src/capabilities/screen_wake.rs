@@ -0,0 +1,98 @@
+use crux_core::capability::{CapabilityContext, Operation};
+use serde::{Deserialize, Serialize};
+
+/// A request to keep the screen awake or to let it sleep again.
+///
+/// The shell should ignore requests which don't change anything, but the app only sends a request
+/// when the wanted state changes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenWakeOperation {
+    /// Whether the screen should be kept awake.
+    pub enable: bool,
+}
+
+/// An empty response.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ScreenWakeResponse {}
+
+impl Operation for ScreenWakeOperation {
+    type Output = ScreenWakeResponse;
+}
+
+/// The ScreenWake capability API.
+///
+/// This capability allows the app to keep the screen, and thereby the app, from sleeping, for
+/// instance while the position is recorded.
+pub struct ScreenWake<Ev> {
+    context: CapabilityContext<ScreenWakeOperation, Ev>,
+}
+
+impl<Ev> Clone for ScreenWake<Ev> {
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+        }
+    }
+}
+
+impl<Ev> crux_core::Capability<Ev> for ScreenWake<Ev> {
+    type Operation = ScreenWakeOperation;
+    type MappedSelf<MappedEv> = ScreenWake<MappedEv>;
+
+    fn map_event<F, NewEv>(&self, f: F) -> Self::MappedSelf<NewEv>
+    where
+        F: Fn(NewEv) -> Ev + Send + Sync + 'static,
+        Ev: 'static,
+        NewEv: 'static + Send,
+    {
+        ScreenWake::new(self.context.map_event(f))
+    }
+
+    #[cfg(feature = "typegen")]
+    fn register_types(generator: &mut crux_core::typegen::TypeGen) -> crux_core::typegen::Result {
+        generator.register_type::<Self::Operation>()?;
+        generator.register_type::<<Self::Operation as Operation>::Output>()?;
+        Ok(())
+    }
+}
+
+impl<Ev> ScreenWake<Ev>
+where
+    Ev: 'static,
+{
+    pub fn new(context: CapabilityContext<ScreenWakeOperation, Ev>) -> Self {
+        Self { context }
+    }
+
+    pub fn set_screen_wake(&self, enable: bool) {
+        self.context.spawn({
+            let this = self.clone();
+            async move {
+                this.set_screen_wake_async(enable).await;
+            }
+        });
+    }
+
+    pub async fn set_screen_wake_async(&self, enable: bool) {
+        self.context
+            .notify_shell(ScreenWakeOperation { enable })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let req = ScreenWakeOperation { enable: true };
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(json, r#"{"enable":true}"#);
+        assert_eq!(
+            serde_json::from_str::<ScreenWakeOperation>(&json).unwrap(),
+            req
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! The JSON format of all data, as downloaded with `Event::DownloadData` and imported with
+//! `Event::ImportData`.
+//!
+//! The data is wrapped in an object with the name of the format and its version, so that other
+//! tools can recognize it and exports of older versions can be migrated when they are imported.
+//! Exports from before the metadata was added are treated as version 0.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use compact_str::{format_compact, CompactString};
+use rstar::RTree;
+use serde::{Deserialize, Serialize};
+
+use super::geo_types::{RecordedWay, SavedPos};
+
+/// The value of the `format` field of exported data.
+pub const EXPORT_FORMAT: &str = "crux_geo_app";
+
+/// The current version of the format of exported data.
+///
+/// Version 1 added the `format`, `version` and `exported_at` fields.
+pub const EXPORT_VERSION: u16 = 1;
+
+/// Exported data of the current version.
+#[derive(Serialize)]
+struct ExportRef<'a> {
+    format: &'static str,
+    version: u16,
+    exported_at: Option<DateTime<Utc>>,
+    /// The R-tree and the saved positions by name, as they are persisted.
+    saved_positions: (&'a RTree<SavedPos>, &'a HashMap<CompactString, SavedPos>),
+    recorded_ways: &'a HashMap<CompactString, RecordedWay>,
+}
+
+/// The fields of exported data which tell how the rest of the data should be read.
+#[derive(Deserialize)]
+struct Metadata {
+    format: Option<CompactString>,
+    version: Option<u16>,
+}
+
+/// The payload of exported data, which is the same in all versions so far.
+#[derive(Deserialize)]
+struct Payload {
+    /// The R-tree is ignored since it can be built from the positions.
+    saved_positions: (serde_json::Value, HashMap<CompactString, SavedPos>),
+    recorded_ways: HashMap<CompactString, RecordedWay>,
+}
+
+/// Imported data.
+#[derive(Debug, Clone)]
+pub(crate) struct Export {
+    pub exported_at: Option<DateTime<Utc>>,
+    pub saved_positions: Vec<SavedPos>,
+    pub recorded_ways: Vec<(CompactString, RecordedWay)>,
+}
+
+/// Write all saved positions and recorded ways as JSON with the metadata of the current version.
+pub(crate) fn to_json(
+    exported_at: Option<DateTime<Utc>>,
+    saved_positions: &RTree<SavedPos>,
+    saved_positions_names: &HashMap<CompactString, SavedPos>,
+    recorded_ways: &HashMap<CompactString, RecordedWay>,
+) -> Vec<u8> {
+    serde_json::to_vec(&ExportRef {
+        format: EXPORT_FORMAT,
+        version: EXPORT_VERSION,
+        exported_at,
+        saved_positions: (saved_positions, saved_positions_names),
+        recorded_ways,
+    })
+    .unwrap()
+}
+
+/// Read data written by `to_json`, or by an older version of the app.
+///
+/// Data of another format or a newer version is rejected.
+pub(crate) fn parse_export(bytes: &[u8]) -> Result<Export, CompactString> {
+    let json: serde_json::Value =
+        serde_json::from_slice(bytes).map_err(|e| format_compact!("Invalid JSON: {e}"))?;
+    let metadata =
+        Metadata::deserialize(&json).map_err(|e| format_compact!("Invalid metadata: {e}"))?;
+    let version = match (metadata.format, metadata.version) {
+        (None, None) => 0,
+        (Some(format), _) if format != EXPORT_FORMAT => {
+            return Err(format_compact!("Unknown format: {format}"))
+        }
+        (Some(_), Some(version)) => version,
+        (Some(_), None) => return Err("The version is missing".into()),
+        (None, Some(_)) => return Err("The format is missing".into()),
+    };
+    if version > EXPORT_VERSION {
+        return Err(format_compact!(
+            "Version {version} is newer than the newest known version {EXPORT_VERSION}"
+        ));
+    }
+    let exported_at = match json.get("exported_at") {
+        Some(exported_at) if version >= 1 => serde_json::from_value(exported_at.clone())
+            .map_err(|e| format_compact!("Invalid export time: {e}"))?,
+        _ => None,
+    };
+    let payload = Payload::deserialize(&json).map_err(|e| format_compact!("Invalid data: {e}"))?;
+    let mut saved_positions = payload.saved_positions.1.into_values().collect::<Vec<_>>();
+    saved_positions.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut recorded_ways = payload.recorded_ways.into_iter().collect::<Vec<_>>();
+    recorded_ways.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(Export {
+        exported_at,
+        saved_positions,
+        recorded_ways,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use jord::LatLong;
+
+    use super::super::geo_types::Position;
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let pos = SavedPos {
+            name: "Home".into(),
+            pos: Position {
+                coords: LatLong::from_degrees(1.0, 2.0),
+                altitude: None,
+                accuracy: None,
+                altitude_accuracy: None,
+            },
+            timestamp: DateTime::from_timestamp(3, 0).unwrap(),
+            averaged: None,
+        };
+        let names = HashMap::from([(pos.name.clone(), pos.clone())]);
+        let mut rec = RecordedWay::new();
+        rec.add(&pos);
+        let ways = HashMap::from([("Walk".into(), rec.clone())]);
+        let exported_at = DateTime::from_timestamp(60, 0);
+        let bytes = to_json(
+            exported_at,
+            &RTree::bulk_load(vec![pos.clone()]),
+            &names,
+            &ways,
+        );
+
+        let json: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["format"], EXPORT_FORMAT);
+        assert_eq!(json["version"], EXPORT_VERSION);
+        assert_eq!(json["exported_at"], "1970-01-01T00:01:00Z");
+        let export = parse_export(&bytes).unwrap();
+        assert_eq!(export.exported_at, exported_at);
+        assert_eq!(export.saved_positions, [pos]);
+        assert_eq!(export.recorded_ways, [(CompactString::from("Walk"), rec)]);
+
+        // An export without metadata.
+        let mut legacy = json.clone();
+        for key in ["format", "version", "exported_at"] {
+            legacy.as_object_mut().unwrap().remove(key);
+        }
+        let export = parse_export(&serde_json::to_vec(&legacy).unwrap()).unwrap();
+        assert_eq!(export.exported_at, None);
+        assert_eq!(export.saved_positions.len(), 1);
+    }
+
+    #[test]
+    fn test_reject() {
+        let check = |json: serde_json::Value, expected: &str| {
+            let e = parse_export(&serde_json::to_vec(&json).unwrap()).unwrap_err();
+            assert!(e.starts_with(expected), "{e}");
+        };
+        let payload = serde_json::json!({ "saved_positions": [[], {}], "recorded_ways": {} });
+        let with = |format: &str, version: u16| {
+            let mut json = payload.clone();
+            json["format"] = format.into();
+            json["version"] = version.into();
+            json
+        };
+        check(with("gpx", 1), "Unknown format: gpx");
+        check(
+            with(EXPORT_FORMAT, EXPORT_VERSION + 1),
+            "Version 2 is newer",
+        );
+        check(
+            serde_json::json!({ "format": EXPORT_FORMAT }),
+            "The version is missing",
+        );
+        check(serde_json::json!({ "version": 1 }), "The format is missing");
+        check(serde_json::json!({}), "Invalid data");
+        assert!(parse_export(b"<gpx/>")
+            .unwrap_err()
+            .starts_with("Invalid JSON"));
+        assert!(parse_export(&serde_json::to_vec(&with(EXPORT_FORMAT, 1)).unwrap()).is_ok());
+    }
+}
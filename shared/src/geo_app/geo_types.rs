@@ -1,17 +1,18 @@
+use std::collections::{BTreeMap, HashMap};
 use std::ops::Div;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use compact_str::CompactString;
 use crux_geolocation::GeoInfo;
 use jord::{
-    spherical::{GreatCircle, MinorArc},
-    LatLong, Length, NVector, Vec3,
+    spherical::{GreatCircle, MinorArc, Sphere},
+    Angle, LatLong, Length, NVector, Speed, Vec3,
 };
-use rstar::{PointDistance, RTreeObject, AABB};
+use rstar::{PointDistance, RTree, RTreeObject, AABB};
 use serde::{Deserialize, Serialize};
 
 use super::geo_traits::*;
-use crate::numbers::eq_zero;
+use crate::numbers::{self, eq_zero};
 use crate::PLANET;
 
 /// A position.
@@ -92,12 +93,23 @@ impl RecordedPos for PosWithTimestamp {
     }
 }
 
+/// Information about how an averaged position was computed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Averaging {
+    /// The number of positions the average was computed from.
+    pub samples: u32,
+    /// The standard deviation of the distances from the positions to the average.
+    pub std_dev: Length,
+}
+
 /// A saved position.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SavedPos {
     pub name: CompactString,
     pub pos: Position,
     pub timestamp: DateTime<Utc>,
+    /// Set if the position is an average of several positions.
+    pub averaged: Option<Averaging>,
 }
 
 impl SavedPos {
@@ -106,8 +118,103 @@ impl SavedPos {
             name,
             pos: geo.into(),
             timestamp: geo.timestamp,
+            averaged: None,
         }
     }
+
+    /// Create a saved position from the average of some positions.
+    ///
+    /// The coordinates are the normalized mean of the n-vectors, weighted by the inverse of the
+    /// accuracies if all positions have known accuracies. The altitude and accuracy are the means
+    /// of the known values. The timestamp is taken from the last position. Returns `None` if
+    /// `positions` is empty.
+    pub fn new_averaged(name: CompactString, positions: &[impl RecordedPos]) -> Option<Self> {
+        let last = positions.last()?;
+        let inverse_accuracies = positions
+            .iter()
+            .map(|x| x.accuracy().map(|a| a.as_metres().recip()))
+            .collect::<Option<Vec<_>>>()
+            .filter(|weights| weights.iter().all(|w| w.is_finite()));
+        let weight = |i: usize| {
+            inverse_accuracies
+                .as_ref()
+                .map_or(1.0, |weights| weights[i])
+        };
+        let sum = positions
+            .iter()
+            .enumerate()
+            .fold(Vec3::new(0.0, 0.0, 0.0), |sum, (i, x)| {
+                let (v, w) = (x.nvector().as_vec3(), weight(i));
+                sum + Vec3::new(v.x() * w, v.y() * w, v.z() * w)
+            });
+        let mean = NVector::new(sum.unit());
+        let total_weight = (0..positions.len()).map(weight).sum::<f64>();
+        let variance = positions
+            .iter()
+            .enumerate()
+            .map(|(i, x)| weight(i) * PLANET.distance(mean, x.nvector()).as_metres().powi(2))
+            .sum::<f64>()
+            / total_weight;
+        let mean_of = |values: Vec<Length>| {
+            let values = values.iter().map(|x| x.as_metres()).collect::<Vec<_>>();
+            numbers::mean(&values).map(Length::from_metres)
+        };
+        Some(Self {
+            name,
+            pos: Position {
+                coords: LatLong::from_nvector(mean),
+                altitude: mean_of(positions.iter().filter_map(Altitude::altitude).collect()),
+                accuracy: mean_of(positions.iter().filter_map(Coords::accuracy).collect()),
+                altitude_accuracy: mean_of(
+                    positions
+                        .iter()
+                        .filter_map(Altitude::altitude_accuracy)
+                        .collect(),
+                ),
+            },
+            timestamp: last.timestamp(),
+            averaged: Some(Averaging {
+                samples: positions.len().try_into().unwrap_or(u32::MAX),
+                std_dev: Length::from_metres(variance.sqrt()),
+            }),
+        })
+    }
+}
+
+/// A saved position as it was persisted before `SavedPos::averaged` was added.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LegacySavedPos {
+    name: CompactString,
+    pos: Position,
+    timestamp: DateTime<Utc>,
+}
+
+impl RTreeObject for LegacySavedPos {
+    type Envelope = AABB<[f64; 3]>;
+    fn envelope(&self) -> Self::Envelope {
+        AABB::from_point(rtree_point(&self.pos))
+    }
+}
+
+/// Decode saved positions persisted in the format used before `SavedPos::averaged` was added.
+pub fn decode_legacy_saved_positions(
+    bytes: &[u8],
+) -> bincode::Result<(RTree<SavedPos>, HashMap<CompactString, SavedPos>)> {
+    let (_, names): (RTree<LegacySavedPos>, HashMap<CompactString, LegacySavedPos>) =
+        bincode::deserialize(bytes)?;
+    let names = names
+        .into_iter()
+        .map(|(key, x)| {
+            let pos = SavedPos {
+                name: x.name,
+                pos: x.pos,
+                timestamp: x.timestamp,
+                averaged: None,
+            };
+            (key, pos)
+        })
+        .collect::<HashMap<_, _>>();
+    Ok((RTree::bulk_load(names.values().cloned().collect()), names))
 }
 
 impl Coords for SavedPos {
@@ -134,12 +241,136 @@ impl RecordedPos for SavedPos {
     }
 }
 
+/// The centroid of some coordinates, found by averaging their n-vectors, so that it works across
+/// the antimeridian and the poles. `None` if there are no coordinates or if they cancel out, like
+/// two antipodal points.
+pub fn centroid(coords: impl IntoIterator<Item = LatLong>) -> Option<LatLong> {
+    let sum = coords.into_iter().fold(Vec3::new(0.0, 0.0, 0.0), |sum, x| {
+        sum + x.to_nvector().as_vec3()
+    });
+    (sum.norm() > 1e-9).then(|| LatLong::from_nvector(NVector::new(sum.unit())))
+}
+
+/// Whether two positions are (almost) antipodal, so that every great circle through one of them
+/// goes through the other and there is no single shortest line between them.
+pub fn are_antipodal(a: NVector, b: NVector) -> bool {
+    // Within about 10 m of the antipode.
+    a.as_vec3().dot_prod(b.as_vec3()) + 1.0 < 1e-12
+}
+
+/// Iterate over the saved positions whose surface distance to `center` is at most `radius`.
+///
+/// The r-tree measures the straight 3D distance (the chord) between n-vectors on the unit sphere,
+/// which is shorter than the distance along the surface. The radius is therefore converted to a
+/// chord for the r-tree query, and the results are filtered by the real surface distance so that
+/// rounding errors near the boundary don't matter.
+pub fn saved_within_radius(
+    saved_positions: &RTree<SavedPos>,
+    center: LatLong,
+    radius: Length,
+) -> impl Iterator<Item = &SavedPos> {
+    let angle = radius.as_metres() / PLANET.radius().as_metres();
+    // A bit larger than the exact chord, since false positives are filtered out anyway.
+    let chord = 2.0 * (angle / 2.0).min(std::f64::consts::FRAC_PI_2).sin() + 1e-9;
+    let center = center.to_nvector();
+    saved_positions
+        .locate_within_distance(rtree_point(&center), chord * chord)
+        .filter(move |x| PLANET.distance(center, x.nvector()).as_metres() <= radius.as_metres())
+}
+
+/// Group coordinates into clusters with their centroids and the number of coordinates in each.
+/// The clusters are sorted by size, largest first.
+///
+/// The n-vectors of the coordinates are quantized into a grid of cubes with sides of about
+/// `cell_size` along the surface. Unlike a grid of latitudes and longitudes, the cells are equally
+/// large everywhere and nothing special happens at the poles or at the antimeridian. The grid is
+/// offset by half a cell, since otherwise the great circles through the poles and the
+/// antimeridian, and the equator, would be borders between cells.
+pub fn clusters(
+    coords: impl IntoIterator<Item = LatLong>,
+    cell_size: Length,
+) -> Vec<(LatLong, usize)> {
+    let cell = cell_size.as_metres() / PLANET.radius().as_metres();
+    let mut cells = BTreeMap::<[i64; 3], Vec<LatLong>>::new();
+    for x in coords {
+        let key = rtree_point(&x).map(|component| (component / cell + 0.5).floor() as i64);
+        cells.entry(key).or_default().push(x);
+    }
+    let mut clusters = cells
+        .into_values()
+        .map(|coords| {
+            // The coordinates in a cell are close, so they have a centroid.
+            (
+                centroid(coords.iter().copied()).unwrap_or(coords[0]),
+                coords.len(),
+            )
+        })
+        .collect::<Vec<_>>();
+    clusters.sort_by(|(_, a), (_, b)| b.cmp(a));
+    clusters
+}
+
 /// Get a point passed to `RTree`.
 pub fn rtree_point<T: Coords>(pos: &T) -> [f64; 3] {
     let nvec = pos.nvector().as_vec3();
     [nvec.x(), nvec.y(), nvec.z()]
 }
 
+/// Check whether some coordinates are within a rectangle of latitudes and longitudes. If `min`
+/// has a larger longitude than `max`, the rectangle crosses the antimeridian.
+pub fn in_box(coords: LatLong, min: LatLong, max: LatLong) -> bool {
+    let (latitude, longitude) = (
+        coords.latitude().as_degrees(),
+        coords.longitude().as_degrees(),
+    );
+    let (min_lat, min_long) = (min.latitude().as_degrees(), min.longitude().as_degrees());
+    let (max_lat, max_long) = (max.latitude().as_degrees(), max.longitude().as_degrees());
+    let in_longitude = if min_long <= max_long {
+        min_long <= longitude && longitude <= max_long
+    } else {
+        min_long <= longitude || longitude <= max_long
+    };
+    in_longitude && min_lat <= latitude && latitude <= max_lat
+}
+
+/// Get an envelope for `RTree` which contains a rectangle of latitudes and longitudes. See
+/// [`in_box`].
+///
+/// The rectangle is curved in the 3d space of the n-vectors, so the envelope is a superset of it.
+/// Use `in_box` to filter out the points outside the rectangle. The envelope is made from the
+/// corners and the points on the edges where an n-vector coordinate may have an extreme value,
+/// that is at the equator and at longitudes which are multiples of 90 degrees.
+pub fn rtree_box(min: LatLong, max: LatLong) -> AABB<[f64; 3]> {
+    let (min_lat, max_lat) = (min.latitude().as_degrees(), max.latitude().as_degrees());
+    let min_long = min.longitude().as_degrees();
+    let mut max_long = max.longitude().as_degrees();
+    if max_long < min_long {
+        max_long += 360.0;
+    }
+    let mut latitudes = vec![min_lat, max_lat];
+    if min_lat < 0.0 && 0.0 < max_lat {
+        latitudes.push(0.0);
+    }
+    let mut longitudes = vec![min_long, max_long];
+    let first_quarter = (min_long / 90.0).ceil() as i32;
+    let last_quarter = (max_long / 90.0).floor() as i32;
+    longitudes.extend((first_quarter..=last_quarter).map(|k| k as f64 * 90.0));
+    let points = latitudes
+        .iter()
+        .flat_map(|&lat| {
+            longitudes
+                .iter()
+                .map(move |&long| rtree_point(&LatLong::from_degrees(lat, long)))
+        })
+        .collect::<Vec<_>>();
+    // Add a small margin for rounding errors.
+    let envelope = AABB::from_points(&points);
+    AABB::from_corners(
+        envelope.lower().map(|x| x - 1e-12),
+        envelope.upper().map(|x| x + 1e-12),
+    )
+}
+
 /// We implement RTreeObject for a position on the Earth's surface. (Ignoring altitude.)
 ///
 /// The distances will technically not be correct since the rtree will compute the direct distance
@@ -165,11 +396,21 @@ impl PointDistance for SavedPos {
     }
 }
 
+/// Altitude changes (in meters) smaller than this are considered noise when computing the
+/// elevation gain and loss of a way.
+pub const ELEVATION_NOISE_THRESHOLD: f64 = 2.0;
+
+/// Segments of a way with a lower speed (in meters per second) than this are considered stops
+/// when computing the moving time of a way.
+pub const MIN_MOVING_SPEED: f64 = 0.5;
+
 /// A line is actually a minor arc (or a geodesi) on the surface of the planet.
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Line(MinorArc);
 
 impl Line {
+    /// The line from `start` to `end`. It is undefined, with a NaN normal, if they are equal or
+    /// antipodal.
     pub fn new(start: LatLong, end: LatLong) -> Self {
         Line(MinorArc::new(start.to_nvector(), end.to_nvector()))
     }
@@ -216,25 +457,99 @@ impl RTreeObject for Line {
 impl PointDistance for Line {
     fn distance_2(&self, point: &[f64; 3]) -> f64 {
         let point = NVector::new(Vec3::new(point[0], point[1], point[2]));
-        f64::min(
-            PLANET
-                .cross_track_distance(point, GreatCircle::new(self.0.start(), self.0.end()))
-                .as_metres()
-                .div(PLANET.radius().as_metres())
-                .powi(2),
+        self.distance(point)
+            .as_metres()
+            .div(PLANET.radius().as_metres())
+            .powi(2)
+    }
+}
+
+impl Line {
+    /// The point on this line which is closest to `point`.
+    pub fn closest_point(&self, point: NVector) -> NVector {
+        let n = self.0.normal();
+        let p = point.as_vec3();
+        // Project the point onto the plane of the great circle.
+        let d = p.dot_prod(n);
+        let q = Vec3::new(p.x() - d * n.x(), p.y() - d * n.y(), p.z() - d * n.z());
+        if !eq_zero(q.norm()) {
+            let q = q.unit();
+            // The projection is on the line iff it is between the start and the end.
+            if self.0.start().as_vec3().cross_prod(q).dot_prod(n) >= 0.0
+                && q.cross_prod(self.0.end().as_vec3()).dot_prod(n) >= 0.0
+            {
+                return NVector::new(q);
+            }
+        }
+        if PLANET.distance(point, self.0.start()) <= PLANET.distance(point, self.0.end()) {
+            self.0.start()
+        } else {
+            self.0.end()
+        }
+    }
+
+    /// The length of this line along the surface.
+    pub fn length(&self) -> Length {
+        PLANET.distance(self.0.start(), self.0.end())
+    }
+
+    /// `n + 1` evenly spaced points along this line, from the start to the end. If the start and
+    /// end are antipodal there is no single line between them, and only they are returned.
+    pub fn sample(&self, n: usize) -> Vec<LatLong> {
+        let (start, end) = (self.0.start(), self.0.end());
+        if are_antipodal(start, end) {
+            return vec![LatLong::from_nvector(start), LatLong::from_nvector(end)];
+        }
+        let n = n.max(1);
+        (0..=n)
+            .filter_map(|i| {
+                let fraction = i as f64 / n as f64;
+                Sphere::interpolated_pos(start, end, fraction).map(LatLong::from_nvector)
+            })
+            .collect()
+    }
+
+    /// The distance from a point to this line. This is the cross-track distance, but never more
+    /// than the distance to the start or end.
+    pub fn distance(&self, point: NVector) -> Length {
+        let cross_track = PLANET
+            .cross_track_distance(point, GreatCircle::new(self.0.start(), self.0.end()))
+            .as_metres()
+            .abs();
+        Length::from_metres(f64::min(
+            cross_track,
             f64::min(
-                PLANET
-                    .distance(point, self.0.start())
-                    .as_metres()
-                    .div(PLANET.radius().as_metres())
-                    .powi(2),
-                PLANET
-                    .distance(point, self.0.end())
-                    .as_metres()
-                    .div(PLANET.radius().as_metres())
-                    .powi(2),
+                PLANET.distance(point, self.0.start()).as_metres(),
+                PLANET.distance(point, self.0.end()).as_metres(),
             ),
-        )
+        ))
+    }
+}
+
+/// A segment of a named way. Used to find the ways nearest to a position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaySegment {
+    pub line: Line,
+    /// The name of the way this segment belongs to.
+    pub way: CompactString,
+}
+
+impl RTreeObject for WaySegment {
+    type Envelope = AABB<[f64; 3]>;
+    fn envelope(&self) -> Self::Envelope {
+        self.line.envelope()
+    }
+}
+
+impl PointDistance for WaySegment {
+    /// The squared distance, as an angle in radians, to the closest point on the segment.
+    fn distance_2(&self, point: &[f64; 3]) -> f64 {
+        let point = NVector::new(Vec3::new(point[0], point[1], point[2]));
+        PLANET
+            .distance(point, self.line.closest_point(point))
+            .as_metres()
+            .div(PLANET.radius().as_metres())
+            .powi(2)
     }
 }
 
@@ -265,6 +580,34 @@ impl<T> Way<T> {
 }
 
 impl<T: Coords> Way<T> {
+    /// The south-west and north-east corners of the smallest box of latitudes and longitudes
+    /// which contains all nodes, or `None` if the way is empty.
+    ///
+    /// The longitudes are compared as numbers between -180 and 180 degrees, so for a way which
+    /// crosses the antimeridian the box spans the rest of the earth rather than the way.
+    pub fn bounding_box(&self) -> Option<(LatLong, LatLong)> {
+        let radians = |coords: LatLong| {
+            (
+                coords.latitude().as_radians(),
+                coords.longitude().as_radians(),
+            )
+        };
+        let first = radians(self.nodes.first()?.coords());
+        let (mut min, mut max) = (first, first);
+        for node in &self.nodes[1..] {
+            let (latitude, longitude) = radians(node.coords());
+            min = (min.0.min(latitude), min.1.min(longitude));
+            max = (max.0.max(latitude), max.1.max(longitude));
+        }
+        let lat_long = |(latitude, longitude): (f64, f64)| {
+            LatLong::new(
+                Angle::from_radians(latitude),
+                Angle::from_radians(longitude),
+            )
+        };
+        Some((lat_long(min), lat_long(max)))
+    }
+
     /// Add a node to the end of the way.
     pub fn append(&mut self, pos: T) {
         if let Some(last) = self.nodes.last() {
@@ -273,18 +616,208 @@ impl<T: Coords> Way<T> {
         self.nodes.push(pos);
     }
 
+    /// Remove the first `n` nodes from the way.
+    ///
+    /// The length is updated by subtracting the removed segments, so the rest of the way doesn't
+    /// have to be walked.
+    pub fn remove_front(&mut self, n: usize) {
+        if n >= self.nodes.len() {
+            self.nodes.clear();
+            self.length = Length::ZERO;
+            return;
+        }
+        self.subtract_front_lines(n);
+        self.nodes.drain(..n);
+    }
+
+    /// Remove the first node from the way, or return `None` if the way is empty.
+    ///
+    /// The length is updated like in `remove_front`, but the remaining nodes are still shifted,
+    /// so this takes time linear in the number of nodes. To remove many nodes, call
+    /// `remove_front` once rather than this repeatedly.
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        self.subtract_front_lines(1);
+        Some(self.nodes.remove(0))
+    }
+
+    /// Subtract the lengths of the lines between the first `n + 1` nodes from the length. If at
+    /// most one node will be left, the length is set to zero so that no rounding errors remain.
+    fn subtract_front_lines(&mut self, n: usize) {
+        if n + 1 >= self.nodes.len() {
+            self.length = Length::ZERO;
+            return;
+        }
+        for i in 0..n {
+            self.length =
+                self.length - PLANET.distance(self.nodes[i].nvector(), self.nodes[i + 1].nvector());
+        }
+    }
+
     /// Insert a node at the specified index.
     pub fn insert(&mut self, i: usize, pos: T) {
         self.nodes.insert(i, pos);
         self.recompute_length()
     }
 
+    /// Remove the node at a certain index.
+    pub fn remove(&mut self, i: usize) -> T {
+        let pos = self.nodes.remove(i);
+        self.recompute_length();
+        pos
+    }
+
     /// Change a node at a certain index.
     pub fn update(&mut self, i: usize, new_pos: T) {
         self.nodes[i] = new_pos;
         self.recompute_length();
     }
 
+    /// The lines between consecutive nodes. Nodes with the same coordinates as the previous node
+    /// are skipped, so a way where all nodes are at the same place has no lines.
+    pub fn lines(&self) -> impl Iterator<Item = Line> + '_ {
+        self.nodes
+            .windows(2)
+            .filter(|w| w[0].coords() != w[1].coords())
+            .map(|w| Line::new(w[0].coords(), w[1].coords()))
+    }
+
+    /// The accuracy which a fraction `p` of the nodes with known accuracy are at least as good as,
+    /// interpolated linearly between the nodes. `None` if no node has a known accuracy or if `p`
+    /// is not in the range [0, 1].
+    pub fn accuracy_percentile(&self, p: f64) -> Option<Length> {
+        numbers::percentile(&self.accuracies(), p).map(Length::from_metres)
+    }
+
+    /// The median accuracy of the nodes with known accuracy.
+    pub fn median_accuracy(&self) -> Option<Length> {
+        numbers::median(&self.accuracies()).map(Length::from_metres)
+    }
+
+    /// The known accuracies of the nodes in metres.
+    fn accuracies(&self) -> Vec<f64> {
+        self.nodes
+            .iter()
+            .filter_map(|x| x.accuracy().map(|x| x.as_metres()))
+            .collect()
+    }
+
+    /// Find the point on the way which is closest to `p`.
+    ///
+    /// Returns the index of the node where the closest segment starts, the closest point and the
+    /// distance to it, or `None` if the way is empty.
+    pub fn closest_point(&self, p: LatLong) -> Option<(usize, NVector, Length)> {
+        let p = p.to_nvector();
+        if let [node] = self.nodes.as_slice() {
+            return Some((0, node.nvector(), PLANET.distance(p, node.nvector())));
+        }
+        self.nodes
+            .windows(2)
+            .enumerate()
+            .map(|(i, w)| {
+                let closest = if w[0].coords() == w[1].coords() {
+                    w[0].nvector()
+                } else {
+                    Line::new(w[0].coords(), w[1].coords()).closest_point(p)
+                };
+                (i, closest, PLANET.distance(p, closest))
+            })
+            .min_by(|(_, _, a), (_, _, b)| a.as_metres().total_cmp(&b.as_metres()))
+    }
+
+    /// The distance along the way between the nodes at two indices, in either order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an index is out of bounds.
+    pub fn distance_from_to(&self, from_index: usize, to_index: usize) -> Length {
+        let (start, end) = if from_index <= to_index {
+            (from_index, to_index)
+        } else {
+            (to_index, from_index)
+        };
+        self.nodes[start..=end]
+            .windows(2)
+            .map(|w| PLANET.distance(w[0].nvector(), w[1].nvector()))
+            .fold(Length::ZERO, |sum, x| sum + x)
+    }
+
+    /// The distance along the way from the point on it which is closest to `p` to the last node,
+    /// or to the first node if `reverse` is set. `None` if the way is empty.
+    pub fn remaining_along(&self, p: LatLong, reverse: bool) -> Option<Length> {
+        let (i, closest, _) = self.closest_point(p)?;
+        let last = self.nodes.len() - 1;
+        let (next, end) = if reverse {
+            (i, 0)
+        } else {
+            ((i + 1).min(last), last)
+        };
+        Some(
+            PLANET.distance(closest, self.nodes[next].nvector()) + self.distance_from_to(next, end),
+        )
+    }
+
+    /// Simplify the way with the Ramer–Douglas–Peucker algorithm.
+    ///
+    /// Nodes closer than `tolerance` to the line between the surrounding kept nodes are removed.
+    /// The first and last nodes are always kept.
+    pub fn simplify(&mut self, tolerance: Length) {
+        let n = self.nodes.len();
+        if n < 3 {
+            return;
+        }
+        let mut keep = vec![false; n];
+        keep[0] = true;
+        keep[n - 1] = true;
+        let mut stack = vec![(0, n - 1)];
+        while let Some((first, last)) = stack.pop() {
+            let (start, end) = (&self.nodes[first], &self.nodes[last]);
+            // There is no line between equal nodes, for instance at the ends of a loop, so the
+            // distance to the node is used instead.
+            let line =
+                (start.coords() != end.coords()).then(|| Line::new(start.coords(), end.coords()));
+            let distance = |node: &T| match &line {
+                Some(line) => line.distance(node.nvector()),
+                None => PLANET.distance(start.nvector(), node.nvector()),
+            };
+            let farthest = (first + 1..last)
+                .map(|i| (i, distance(&self.nodes[i])))
+                .max_by(|(_, a), (_, b)| a.as_metres().total_cmp(&b.as_metres()));
+            if let Some((i, distance)) = farthest {
+                if distance > tolerance {
+                    keep[i] = true;
+                    stack.push((first, i));
+                    stack.push((i, last));
+                }
+            }
+        }
+        let mut keep = keep.into_iter();
+        self.nodes.retain(|_| keep.next().unwrap());
+        self.recompute_length();
+    }
+
+    /// Reduce the way to about `target` nodes by keeping nodes at evenly spaced indices.
+    ///
+    /// Unlike [`Self::simplify`], the number of nodes afterwards is predictable. The first and
+    /// last nodes are always kept, so at least two nodes remain. Nothing happens if the way
+    /// doesn't have more than `target` nodes.
+    pub fn downsample(&mut self, target: usize) {
+        let n = self.nodes.len();
+        let target = target.max(2);
+        if n <= target {
+            return;
+        }
+        let mut keep = vec![false; n];
+        for i in 0..target {
+            keep[(i * (n - 1) + (target - 1) / 2) / (target - 1)] = true;
+        }
+        let mut keep = keep.into_iter();
+        self.nodes.retain(|_| keep.next().unwrap());
+        self.recompute_length();
+    }
+
     /// Recompute the length for the way.
     fn recompute_length(&mut self) {
         self.length = Length::ZERO;
@@ -295,15 +828,255 @@ impl<T: Coords> Way<T> {
     }
 }
 
+impl<T: Altitude> Way<T> {
+    /// The total climb of the way, or `None` if no two consecutive nodes have altitudes.
+    ///
+    /// Changes smaller than [`ELEVATION_NOISE_THRESHOLD`] are ignored.
+    pub fn elevation_gain(&self) -> Option<Length> {
+        self.elevation_changes().map(|(gain, _)| gain)
+    }
+
+    /// The total descent of the way, or `None` if no two consecutive nodes have altitudes.
+    ///
+    /// Changes smaller than [`ELEVATION_NOISE_THRESHOLD`] are ignored.
+    pub fn elevation_loss(&self) -> Option<Length> {
+        self.elevation_changes().map(|(_, loss)| loss)
+    }
+
+    /// Sum the positive and negative altitude changes of the way.
+    ///
+    /// The altitude is compared to a reference altitude which is only moved when the difference
+    /// exceeds the noise threshold, so that a slow but steady climb is still counted. Nodes
+    /// without altitude break the way into parts which are summed individually.
+    fn elevation_changes(&self) -> Option<(Length, Length)> {
+        let mut gain = 0.0;
+        let mut loss = 0.0;
+        let mut any_pair = false;
+        let mut reference: Option<f64> = None;
+        let mut prev_has_altitude = false;
+        for altitude in self.nodes.iter().map(Altitude::altitude) {
+            let Some(altitude) = altitude.map(|x| x.as_metres()) else {
+                reference = None;
+                prev_has_altitude = false;
+                continue;
+            };
+            any_pair |= prev_has_altitude;
+            prev_has_altitude = true;
+            let Some(prev) = reference else {
+                reference = Some(altitude);
+                continue;
+            };
+            let delta = altitude - prev;
+            if delta.abs() >= ELEVATION_NOISE_THRESHOLD {
+                if delta > 0.0 {
+                    gain += delta;
+                } else {
+                    loss -= delta;
+                }
+                reference = Some(altitude);
+            }
+        }
+        any_pair.then(|| (Length::from_metres(gain), Length::from_metres(loss)))
+    }
+}
+
+impl<T: Coords + Altitude> Way<T> {
+    /// The length of the way including climbing and descending. For each line where both nodes
+    /// have altitudes, the altitude difference is added to the surface distance with Pythagoras.
+    /// Other lines count with their surface distance.
+    pub fn length_3d(&self) -> Length {
+        let metres = self
+            .nodes
+            .windows(2)
+            .map(|w| {
+                let distance = PLANET.distance(w[0].nvector(), w[1].nvector()).as_metres();
+                match (w[0].altitude(), w[1].altitude()) {
+                    (Some(a), Some(b)) => distance.hypot(b.as_metres() - a.as_metres()),
+                    _ => distance,
+                }
+            })
+            .sum();
+        Length::from_metres(metres)
+    }
+}
+
+impl<T: RecordedPos> Way<T> {
+    /// The average speed over the way, that is the length divided by the time between the first
+    /// and last node. `None` if the way has less than two nodes or takes no time.
+    pub fn average_speed(&self) -> Option<Speed> {
+        let (first, last) = (self.nodes.first()?, self.nodes.last()?);
+        speed(self.length, last.timestamp() - first.timestamp())
+    }
+
+    /// The time between the first and last node, or zero if the way has less than two nodes.
+    pub fn total_time(&self) -> TimeDelta {
+        match (self.nodes.first(), self.nodes.last()) {
+            (Some(first), Some(last)) => last.timestamp() - first.timestamp(),
+            _ => TimeDelta::zero(),
+        }
+    }
+
+    /// The total time of all segments where the speed is higher than `min_speed`.
+    pub fn moving_time(&self, min_speed: Speed) -> TimeDelta {
+        self.nodes
+            .windows(2)
+            .filter_map(|w| {
+                let time = w[1].timestamp() - w[0].timestamp();
+                speed(PLANET.distance(w[0].nvector(), w[1].nvector()), time)
+                    .filter(|speed| speed.as_metres_per_second() > min_speed.as_metres_per_second())
+                    .map(|_| time)
+            })
+            .sum()
+    }
+
+    /// The average speed when moving, that is the length of all segments where the speed is
+    /// higher than `min_speed` divided by the moving time.
+    pub fn moving_average_speed(&self, min_speed: Speed) -> Option<Speed> {
+        let distance = self
+            .nodes
+            .windows(2)
+            .filter_map(|w| {
+                let distance = PLANET.distance(w[0].nvector(), w[1].nvector());
+                speed(distance, w[1].timestamp() - w[0].timestamp())
+                    .filter(|speed| speed.as_metres_per_second() > min_speed.as_metres_per_second())
+                    .map(|_| distance.as_metres())
+            })
+            .sum();
+        speed(Length::from_metres(distance), self.moving_time(min_speed))
+    }
+
+    /// The speed of every segment of the way, so one less than the number of nodes. Segments
+    /// which take no time get a speed of zero.
+    pub fn segment_speeds(&self) -> Vec<Speed> {
+        self.nodes
+            .windows(2)
+            .map(|w| {
+                let metres_per_second = speed(
+                    PLANET.distance(w[0].nvector(), w[1].nvector()),
+                    w[1].timestamp() - w[0].timestamp(),
+                )
+                .map_or(0.0, |x| x.as_metres_per_second());
+                // `max` also replaces NaN with zero.
+                Speed::from_metres_per_second(metres_per_second.max(0.0))
+            })
+            .collect()
+    }
+
+    /// The maximum speed over any segment of the way. `None` if no segment takes any time.
+    pub fn max_speed(&self) -> Option<Speed> {
+        self.nodes
+            .windows(2)
+            .filter_map(|w| {
+                speed(
+                    PLANET.distance(w[0].nvector(), w[1].nvector()),
+                    w[1].timestamp() - w[0].timestamp(),
+                )
+            })
+            .max_by(|a, b| {
+                a.as_metres_per_second()
+                    .total_cmp(&b.as_metres_per_second())
+            })
+    }
+}
+
+impl Way<PosWithTimestamp> {
+    /// Fill gaps longer than `max_gap` between consecutive nodes with nodes at every `step` along
+    /// the great circle, so that a lost signal isn't drawn as one long line. The interpolated
+    /// nodes are marked by not having any accuracy or altitude. Gaps between antipodal nodes are
+    /// left as they are.
+    ///
+    /// Returns the number of inserted nodes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is not positive.
+    pub fn interpolate_gaps(&mut self, max_gap: TimeDelta, step: TimeDelta) -> usize {
+        assert!(step > TimeDelta::zero(), "Bad step: {step}");
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        for w in self.nodes.windows(2) {
+            let (start, end) = (&w[0], &w[1]);
+            nodes.push(start.clone());
+            let gap = end.timestamp - start.timestamp;
+            // There is no single great circle between antipodal nodes to put the new nodes on.
+            if gap <= max_gap || are_antipodal(start.nvector(), end.nvector()) {
+                continue;
+            }
+            let mut timestamp = start.timestamp + step;
+            while timestamp < end.timestamp {
+                let fraction = (timestamp - start.timestamp).num_milliseconds() as f64
+                    / gap.num_milliseconds() as f64;
+                let Some(nvector) =
+                    Sphere::interpolated_pos(start.nvector(), end.nvector(), fraction)
+                else {
+                    break;
+                };
+                nodes.push(PosWithTimestamp {
+                    pos: Position {
+                        coords: LatLong::from_nvector(nvector),
+                        altitude: None,
+                        accuracy: None,
+                        altitude_accuracy: None,
+                    },
+                    timestamp,
+                });
+                timestamp += step;
+            }
+        }
+        nodes.extend(self.nodes.last().cloned());
+        let n_inserted = nodes.len() - self.nodes.len();
+        if n_inserted > 0 {
+            self.nodes = nodes;
+            self.recompute_length();
+        }
+        n_inserted
+    }
+}
+
+/// The speed when travelling a distance during some time, or `None` if the time is not positive.
+fn speed(distance: Length, time: TimeDelta) -> Option<Speed> {
+    let secs = time.num_milliseconds() as f64 / 1000.0;
+    (secs > 0.0).then(|| Speed::from_metres_per_second(distance.as_metres() / secs))
+}
+
+/// A color and a note which the user has attached to a way.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WayMeta {
+    /// A color to draw the way in, like "red" or "#ff0000".
+    pub color: Option<CompactString>,
+    pub note: Option<CompactString>,
+}
+
 /// A recorded way.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub(crate) struct RecordedWay {
+pub struct RecordedWay {
     pub way: Way<PosWithTimestamp>,
+    /// The default is only used for JSON, since bincode can't skip fields. Bincode data without
+    /// this field is read as a `LegacyRecordedWay`.
+    #[serde(default)]
+    pub meta: WayMeta,
+}
+
+/// A recorded way as it was persisted before `RecordedWay::meta` was added.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LegacyRecordedWay {
+    way: Way<PosWithTimestamp>,
+}
+
+impl From<LegacyRecordedWay> for RecordedWay {
+    fn from(x: LegacyRecordedWay) -> Self {
+        Self {
+            way: x.way,
+            meta: WayMeta::default(),
+        }
+    }
 }
 
 impl RecordedWay {
     pub fn new() -> Self {
-        Self { way: Way::new() }
+        Self {
+            way: Way::new(),
+            meta: WayMeta::default(),
+        }
     }
 
     pub fn way(&self) -> &Way<impl RecordedPos> {
@@ -337,6 +1110,29 @@ impl RecordedWay {
         }
     }
 
+    /// Remove the node at a certain index, or return `None` if the index is out of range.
+    pub fn remove_node(&mut self, index: usize) -> Option<PosWithTimestamp> {
+        (index < self.way.nodes().len()).then(|| self.way.remove(index))
+    }
+
+    /// Recompute all cached statistics for the way from scratch.
+    ///
+    /// Returns the length before and after the repair.
+    pub fn repair(&mut self) -> (Length, Length) {
+        let before = self.way.length();
+        self.way.recompute_length();
+        (before, self.way.length())
+    }
+
+    /// Remove all positions before a certain timestamp. (Exclusive)
+    ///
+    /// Returns the number of removed positions.
+    pub fn remove_before(&mut self, timestamp: DateTime<Utc>) -> usize {
+        let n = self.way.nodes().len() - self.get_since(timestamp).len();
+        self.way.remove_front(n);
+        n
+    }
+
     /// Get all positions since a certain timestamp. (Inclusive)
     pub fn get_since(&self, timestamp: DateTime<Utc>) -> &[PosWithTimestamp] {
         let i = self
@@ -346,6 +1142,66 @@ impl RecordedWay {
             .unwrap_or_else(|i| i);
         &self.way.nodes()[i..]
     }
+
+    /// Get all positions between two timestamps. (Inclusive)
+    pub fn get_range(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> &[PosWithTimestamp] {
+        let nodes = self.get_since(start);
+        let n = match nodes.binary_search_by_key(&end, RecordedPos::timestamp) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+        &nodes[..n]
+    }
+
+    /// The index of the first node recorded at or after a certain timestamp, or the number of
+    /// nodes if there is none.
+    pub fn index_at(&self, timestamp: DateTime<Utc>) -> usize {
+        self.way.nodes().len() - self.get_since(timestamp).len()
+    }
+
+    /// Split the recording into one with the nodes before `index` and one with the rest. Both
+    /// keep the metadata. `None` if either would be empty.
+    pub fn split(&self, index: usize) -> Option<(Self, Self)> {
+        let nodes = self.way.nodes();
+        if index == 0 || index >= nodes.len() {
+            return None;
+        }
+        let part = |nodes: &[PosWithTimestamp]| {
+            let mut way = Way::new();
+            for node in nodes {
+                way.append(node.clone());
+            }
+            Self {
+                way,
+                meta: self.meta.clone(),
+            }
+        };
+        Some((part(&nodes[..index]), part(&nodes[index..])))
+    }
+
+    /// Join two recordings into one. The nodes of both are always sorted by timestamp, whichever
+    /// recording starts first. Of nodes with the same timestamp, only the one in `self` is kept,
+    /// and so is the metadata of `self`.
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut nodes = self
+            .way
+            .nodes()
+            .iter()
+            .chain(other.way.nodes())
+            .cloned()
+            .collect::<Vec<_>>();
+        // The sort is stable, so the nodes of `self` come first among nodes with equal timestamps.
+        nodes.sort_by_key(|x| x.timestamp);
+        nodes.dedup_by_key(|x| x.timestamp);
+        let mut way = Way::new();
+        for node in nodes {
+            way.append(node);
+        }
+        Self {
+            way,
+            meta: self.meta.clone(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -356,6 +1212,716 @@ mod tests {
     use super::*;
     use crate::numbers::{gte, lte};
 
+    /// Create a position at some coordinates (in degrees) and a timestamp (in seconds).
+    fn pos(latitude: f64, longitude: f64, secs: i64) -> PosWithTimestamp {
+        PosWithTimestamp {
+            pos: Position {
+                coords: LatLong::from_degrees(latitude, longitude),
+                altitude: None,
+                accuracy: None,
+                altitude_accuracy: None,
+            },
+            timestamp: DateTime::from_timestamp(secs, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_repair_recorded_way() {
+        let mut rec = RecordedWay::new();
+        rec.add(&pos(0.0, 0.0, 0));
+        rec.add(&pos(0.0, 1.0, 1));
+        rec.add(&pos(1.0, 1.0, 2));
+        let length = rec.way.length();
+        rec.way.length = Length::from_metres(1.0);
+        let (before, after) = rec.repair();
+        assert_eq!(before, Length::from_metres(1.0));
+        assert_eq!(after, length);
+        assert_eq!(rec.way.length(), length);
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let mut way = Way::new();
+        assert_eq!(way.bounding_box(), None);
+        way.append(pos(10.0, -20.0, 0));
+        assert_eq!(
+            way.bounding_box(),
+            Some((
+                LatLong::from_degrees(10.0, -20.0),
+                LatLong::from_degrees(10.0, -20.0)
+            ))
+        );
+        way.append(pos(-5.0, 30.0, 1));
+        way.append(pos(40.0, -25.0, 2));
+        way.append(pos(0.0, 0.0, 3));
+        let (min, max) = way.bounding_box().unwrap();
+        assert!((min.latitude().as_degrees() - -5.0).abs() < 1e-9);
+        assert!((min.longitude().as_degrees() - -25.0).abs() < 1e-9);
+        assert!((max.latitude().as_degrees() - 40.0).abs() < 1e-9);
+        assert!((max.longitude().as_degrees() - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_remove_node() {
+        let mut rec = RecordedWay::new();
+        rec.add(&pos(0.0, 0.0, 0));
+        rec.add(&pos(0.001, 0.0, 1));
+        rec.add(&pos(0.5, 0.5, 2));
+        rec.add(&pos(0.002, 0.0, 3));
+        let length = rec.way.length();
+        assert_eq!(rec.remove_node(4), None);
+        assert_eq!(rec.remove_node(2), Some(pos(0.5, 0.5, 2)));
+        assert_eq!(rec.way.nodes().len(), 3);
+        assert!(rec.way.length() < length);
+        let distance = PLANET.distance(pos(0.0, 0.0, 0).nvector(), pos(0.002, 0.0, 0).nvector());
+        assert!((rec.way.length() - distance).as_metres().abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_remove_front() {
+        let mut rec = RecordedWay::new();
+        let mut tail = Way::new();
+        for i in 0..10 {
+            let p = pos(i as f64 / 10.0, 0.0, i);
+            rec.add(&p);
+            if i >= 4 {
+                tail.append(p);
+            }
+        }
+        assert_eq!(rec.remove_before(DateTime::from_timestamp(4, 0).unwrap()), 4);
+        assert_eq!(rec.way.nodes(), tail.nodes());
+        assert!((rec.way.length() - tail.length()).as_metres().abs() < 1e-6);
+        rec.way.remove_front(100);
+        assert!(rec.way.nodes().is_empty());
+        assert_eq!(rec.way.length(), Length::ZERO);
+    }
+
+    #[test]
+    fn test_pop_front() {
+        let node = |i: i64| pos((i % 7) as f64 / 100.0, (i % 13) as f64 / 100.0, i);
+        let mut way = Way::new();
+        assert_eq!(way.pop_front(), None);
+        for i in 0..1000 {
+            way.append(node(i));
+            if way.nodes().len() > 10 {
+                assert_eq!(way.pop_front(), Some(node(i - 10)));
+            }
+            let mut expected = Way::new();
+            for node in way.nodes() {
+                expected.append(node.clone());
+            }
+            assert!((way.length() - expected.length()).as_metres().abs() < 1e-3);
+        }
+        assert_eq!(way.nodes().len(), 10);
+        assert_eq!(way.nodes()[0], node(990));
+        while way.nodes().len() > 1 {
+            way.pop_front();
+        }
+        assert_eq!(way.length(), Length::ZERO);
+        assert_eq!(way.pop_front(), Some(node(999)));
+        assert_eq!(way.length(), Length::ZERO);
+    }
+
+    #[test]
+    fn test_simplify_straight_line() {
+        let mut way = Way::new();
+        for i in 0..20 {
+            way.append(pos(i as f64 / 1000.0, 0.0, i));
+        }
+        let length = way.length();
+        way.simplify(Length::from_metres(1.0));
+        assert_eq!(way.nodes(), &[pos(0.0, 0.0, 0), pos(0.019, 0.0, 19)]);
+        assert!((way.length() - length).as_metres().abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_simplify_zig_zag() {
+        let mut way = Way::new();
+        for i in 0..10 {
+            way.append(pos(i as f64 / 1000.0, (i % 2) as f64 / 1000.0, i));
+        }
+        let nodes = way.nodes().to_vec();
+        let length = way.length();
+        way.simplify(Length::from_metres(10.0));
+        assert_eq!(way.nodes(), nodes);
+        assert_eq!(way.length(), length);
+    }
+
+    #[test]
+    fn test_simplify_loop() {
+        let mut way = Way::new();
+        way.append(pos(0.0, 0.0, 0));
+        way.append(pos(0.0, 0.01, 1));
+        way.append(pos(0.01, 0.01, 2));
+        way.append(pos(0.0, 0.0, 3));
+        let nodes = way.nodes().to_vec();
+        way.simplify(Length::from_metres(10.0));
+        assert_eq!(way.nodes(), nodes);
+    }
+
+    #[test]
+    fn test_downsample() {
+        let mut way = Way::new();
+        for i in 0..100 {
+            way.append(pos(i as f64 / 1000.0, (i % 3) as f64 / 1000.0, i));
+        }
+        let first = way.nodes()[0].clone();
+        let last = way.nodes()[99].clone();
+        way.downsample(20);
+        assert_eq!(way.nodes().len(), 20);
+        assert_eq!(way.nodes()[0], first);
+        assert_eq!(way.nodes()[19], last);
+        let mut expected = Way::new();
+        for node in way.nodes() {
+            expected.append(node.clone());
+        }
+        assert_eq!(way.length(), expected.length());
+
+        // Nothing happens when the way is already small enough.
+        let nodes = way.nodes().to_vec();
+        way.downsample(30);
+        assert_eq!(way.nodes(), nodes);
+        way.downsample(0);
+        assert_eq!(way.nodes(), &[first, last]);
+    }
+
+    #[test]
+    fn test_closest_point() {
+        let mut way = Way::new();
+        assert_eq!(way.closest_point(LatLong::from_degrees(0.0, 0.0)), None);
+        way.append(pos(0.0, -1.0, 0));
+        way.append(pos(0.0, 0.0, 1));
+        way.append(pos(0.0, 1.0, 2));
+        way.append(pos(1.0, 2.0, 3));
+        let (i, closest, distance) = way.closest_point(LatLong::from_degrees(0.01, 0.5)).unwrap();
+        assert_eq!(i, 1);
+        let closest = LatLong::from_nvector(closest);
+        assert!(closest.latitude().as_degrees().abs() < 1e-9);
+        assert!((closest.longitude().as_degrees() - 0.5).abs() < 1e-9);
+        let expected = PLANET.distance(
+            LatLong::from_degrees(0.01, 0.5).to_nvector(),
+            LatLong::from_degrees(0.0, 0.5).to_nvector(),
+        );
+        assert!((distance - expected).as_metres().abs() < 1e-3);
+
+        // A point beyond the end of the way is closest to the last node.
+        let (i, closest, _) = way.closest_point(LatLong::from_degrees(2.0, 3.0)).unwrap();
+        assert_eq!(i, 2);
+        assert_eq!(closest, LatLong::from_degrees(1.0, 2.0).to_nvector());
+    }
+
+    #[test]
+    fn test_way_segment_distance() {
+        let segment = WaySegment {
+            line: Line::new(
+                LatLong::from_degrees(0.0, 0.0),
+                LatLong::from_degrees(0.0, 1.0),
+            ),
+            way: "Road".into(),
+        };
+        let distance_2 = |lat: f64, long: f64| {
+            let p = LatLong::from_degrees(lat, long).to_nvector().as_vec3();
+            segment.distance_2(&[p.x(), p.y(), p.z()])
+        };
+        // A point on the great circle but beyond the end of the segment is as far away as the end.
+        assert!((distance_2(0.0, 2.0) - 1f64.to_radians().powi(2)).abs() < 1e-12);
+        assert!((distance_2(0.0, -0.5) - 0.5f64.to_radians().powi(2)).abs() < 1e-12);
+        // A point beside the segment is as far away as its projection onto it.
+        assert!((distance_2(0.5, 0.5) - 0.5f64.to_radians().powi(2)).abs() < 1e-12);
+        assert!(distance_2(0.0, 0.5) < 1e-12);
+    }
+
+    #[test]
+    fn test_distance_along() {
+        let mut way = Way::new();
+        assert_eq!(
+            way.remaining_along(LatLong::from_degrees(0.0, 0.0), false),
+            None
+        );
+        for (i, long) in [0.0, 0.01, 0.02, 0.04].into_iter().enumerate() {
+            way.append(pos(0.0, long, i as i64));
+        }
+        let along = |from: f64, to: f64| {
+            PLANET.distance(
+                LatLong::from_degrees(0.0, from).to_nvector(),
+                LatLong::from_degrees(0.0, to).to_nvector(),
+            )
+        };
+        assert_eq!(way.distance_from_to(2, 2), Length::ZERO);
+        assert!(
+            (way.distance_from_to(1, 3) - along(0.01, 0.04))
+                .as_metres()
+                .abs()
+                < 1e-6
+        );
+        assert!(
+            (way.distance_from_to(3, 1) - along(0.01, 0.04))
+                .as_metres()
+                .abs()
+                < 1e-6
+        );
+        assert!(
+            (way.distance_from_to(0, 3) - way.length())
+                .as_metres()
+                .abs()
+                < 1e-6
+        );
+
+        // Beside the second segment.
+        let p = LatLong::from_degrees(0.001, 0.015);
+        let forward = way.remaining_along(p, false).unwrap();
+        assert!((forward - along(0.015, 0.04)).as_metres().abs() < 1e-3);
+        let backward = way.remaining_along(p, true).unwrap();
+        assert!((backward - along(0.0, 0.015)).as_metres().abs() < 1e-3);
+        // Beyond the end.
+        let p = LatLong::from_degrees(0.0, 0.05);
+        assert!(way.remaining_along(p, false).unwrap().as_metres().abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_accuracy_percentile() {
+        let mut way = Way::new();
+        assert_eq!(way.accuracy_percentile(0.95), None);
+        way.append(pos(0.0, 0.0, 0));
+        assert_eq!(way.median_accuracy(), None);
+        // The accuracies 1, 2, ..., 11 m in some order.
+        for (i, accuracy) in [5, 1, 9, 3, 11, 7, 2, 10, 4, 8, 6].into_iter().enumerate() {
+            let mut p = pos(0.0, 0.0, i as i64 + 1);
+            p.pos.accuracy = Some(Length::from_metres(accuracy as f64));
+            way.append(p);
+        }
+        let percentile = |p| way.accuracy_percentile(p).unwrap().as_metres();
+        assert_eq!(percentile(0.0), 1.0);
+        assert_eq!(percentile(1.0), 11.0);
+        assert_eq!(way.median_accuracy(), Some(Length::from_metres(6.0)));
+        assert!((percentile(0.95) - 10.5).abs() < 1e-9);
+        assert!((percentile(0.25) - 3.5).abs() < 1e-9);
+        assert_eq!(way.accuracy_percentile(1.5), None);
+        assert_eq!(way.accuracy_percentile(-0.5), None);
+    }
+
+    #[test]
+    fn test_elevation_gain_and_loss() {
+        let mut way = Way::new();
+        let altitudes = [
+            Some(100.0),
+            Some(101.0),
+            Some(102.5),
+            Some(110.0),
+            Some(109.5),
+            None,
+            Some(120.0),
+            Some(105.0),
+            Some(104.0),
+            Some(100.0),
+        ];
+        for (i, altitude) in altitudes.into_iter().enumerate() {
+            let mut p = pos(i as f64 / 1000.0, 0.0, i as i64);
+            p.pos.altitude = altitude.map(Length::from_metres);
+            way.append(p);
+        }
+        // 100 -> 102.5 -> 110 is a gain of 10 m. The jitter of 0.5 m is ignored. After the gap, 120
+        // -> 105 -> 100 is a loss of 20 m.
+        assert!((way.elevation_gain().unwrap().as_metres() - 10.0).abs() < 1e-9);
+        assert!((way.elevation_loss().unwrap().as_metres() - 20.0).abs() < 1e-9);
+
+        let mut flat = Way::new();
+        flat.append(pos(0.0, 0.0, 0));
+        flat.append(pos(0.0, 0.001, 1));
+        assert_eq!(flat.elevation_gain(), None);
+        assert_eq!(flat.elevation_loss(), None);
+    }
+
+    #[test]
+    fn test_length_3d() {
+        let mut way = Way::new();
+        // About 111 m north, climbing 40 m, and then a line without altitude.
+        let mut a = pos(0.0, 0.0, 0);
+        a.pos.altitude = Some(Length::from_metres(100.0));
+        let mut b = pos(0.001, 0.0, 1);
+        b.pos.altitude = Some(Length::from_metres(140.0));
+        way.append(a);
+        way.append(b);
+        let surface = way.length().as_metres();
+        let expected = surface.hypot(40.0);
+        assert!((way.length_3d().as_metres() - expected).abs() < 1e-6);
+        assert!(way.length_3d().as_metres() > surface + 5.0);
+
+        way.append(pos(0.002, 0.0, 2));
+        let last_line = way.length().as_metres() - surface;
+        assert!((way.length_3d().as_metres() - (expected + last_line)).abs() < 1e-6);
+
+        let mut flat = Way::new();
+        flat.append(pos(0.0, 0.0, 0));
+        flat.append(pos(0.0, 0.001, 1));
+        assert!((flat.length_3d().as_metres() - flat.length().as_metres()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_speed() {
+        let mut way = Way::new();
+        way.append(pos(0.0, 0.0, 0));
+        assert_eq!(way.average_speed(), None);
+        assert_eq!(way.max_speed(), None);
+        way.append(pos(0.001, 0.0, 10));
+        way.append(pos(0.002, 0.0, 15));
+        let segment = PLANET
+            .distance(pos(0.0, 0.0, 0).nvector(), pos(0.001, 0.0, 0).nvector())
+            .as_metres();
+        let average = way.average_speed().unwrap().as_metres_per_second();
+        let max = way.max_speed().unwrap().as_metres_per_second();
+        assert!((average - 2.0 * segment / 15.0).abs() < 1e-6);
+        assert!((max - segment / 5.0).abs() < 1e-6);
+
+        let mut instant = Way::new();
+        instant.append(pos(0.0, 0.0, 0));
+        instant.append(pos(0.001, 0.0, 0));
+        assert_eq!(instant.average_speed(), None);
+        assert_eq!(instant.max_speed(), None);
+    }
+
+    #[test]
+    fn test_clusters() {
+        let cell_size = Length::from_metres(1000.0);
+        assert!(clusters([] as [LatLong; 0], cell_size).is_empty());
+        // Three points within about 100 m, and one point far away.
+        let close = [
+            LatLong::from_degrees(59.0, 18.0),
+            LatLong::from_degrees(59.0005, 18.0),
+            LatLong::from_degrees(59.0, 18.001),
+        ];
+        let lone = LatLong::from_degrees(60.0, 18.0);
+        let found = clusters(close.into_iter().chain([lone]), cell_size);
+        // A tight cluster could straddle the border between two cells, but not this one.
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].1, 3);
+        let distance = |a: LatLong, b: LatLong| PLANET.distance(a.to_nvector(), b.to_nvector());
+        assert!(distance(found[0].0, centroid(close).unwrap()).as_metres() < 1e-3);
+        assert_eq!(found[1].1, 1);
+        assert!(distance(found[1].0, lone).as_metres() < 1e-3);
+
+        // Points on each side of the antimeridian and around the north pole are clustered.
+        let antimeridian = [
+            LatLong::from_degrees(0.0, 179.9999),
+            LatLong::from_degrees(0.0, -179.9999),
+        ];
+        assert_eq!(clusters(antimeridian, cell_size).len(), 1);
+        let pole = [
+            LatLong::from_degrees(89.9999, 0.0),
+            LatLong::from_degrees(89.9999, 90.0),
+            LatLong::from_degrees(89.9999, -135.0),
+        ];
+        let found = clusters(pole, cell_size);
+        assert_eq!(found.len(), 1);
+        assert!((found[0].0.latitude().as_degrees() - 90.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_centroid() {
+        assert_eq!(centroid([] as [LatLong; 0]), None);
+        let c = centroid([LatLong::from_degrees(10.0, 20.0)]).unwrap();
+        assert!((c.latitude().as_degrees() - 10.0).abs() < 1e-9);
+        assert!((c.longitude().as_degrees() - 20.0).abs() < 1e-9);
+
+        // Naive averaging of degrees would give a longitude of 0.
+        let c = centroid([
+            LatLong::from_degrees(10.0, 179.0),
+            LatLong::from_degrees(-10.0, -179.0),
+        ])
+        .unwrap();
+        assert!(c.latitude().as_degrees().abs() < 1e-9);
+        assert!((c.longitude().as_degrees().abs() - 180.0).abs() < 1e-9);
+
+        let c = centroid([
+            LatLong::from_degrees(89.0, 0.0),
+            LatLong::from_degrees(89.0, 180.0),
+        ])
+        .unwrap();
+        assert!((c.latitude().as_degrees() - 90.0).abs() < 1e-9);
+
+        let antipodal = [
+            LatLong::from_degrees(0.0, 0.0),
+            LatLong::from_degrees(0.0, 180.0),
+        ];
+        assert_eq!(centroid(antipodal), None);
+    }
+
+    #[test]
+    fn test_are_antipodal() {
+        let a = LatLong::from_degrees(10.0, 20.0).to_nvector();
+        assert!(are_antipodal(
+            a,
+            LatLong::from_degrees(-10.0, -160.0).to_nvector()
+        ));
+        assert!(!are_antipodal(
+            a,
+            LatLong::from_degrees(-10.0, -159.9).to_nvector()
+        ));
+        assert!(!are_antipodal(a, a));
+    }
+
+    #[test]
+    fn test_line_sample() {
+        let start = LatLong::from_degrees(59.0, 18.0);
+        let end = LatLong::from_degrees(40.0, -74.0);
+        let line = Line::new(start, end);
+        let points = line.sample(10);
+        assert_eq!(points.len(), 11);
+        let distance = |a: LatLong, b: LatLong| PLANET.distance(a.to_nvector(), b.to_nvector());
+        assert!(distance(points[0], start).as_metres() < 1e-3);
+        assert!(distance(points[10], end).as_metres() < 1e-3);
+        // The points are evenly spaced along the line.
+        let step = line.length().as_metres() / 10.0;
+        for w in points.windows(2) {
+            assert!((distance(w[0], w[1]).as_metres() - step).abs() < 1e-3);
+        }
+        assert_eq!(line.sample(0).len(), 2);
+        let antipodal = Line::new(
+            LatLong::from_degrees(0.0, 0.0),
+            LatLong::from_degrees(0.0, 180.0),
+        );
+        assert_eq!(antipodal.sample(10).len(), 2);
+    }
+
+    #[test]
+    fn test_interpolate_gaps() {
+        let mut way = Way::new();
+        assert_eq!(
+            way.interpolate_gaps(TimeDelta::seconds(30), TimeDelta::seconds(10)),
+            0
+        );
+        way.append(pos(0.0, 0.0, 0));
+        way.append(pos(0.0, 0.001, 20));
+        // A gap of 100 s.
+        way.append(pos(0.0, 0.011, 120));
+        let length = way.length();
+        assert_eq!(
+            way.interpolate_gaps(TimeDelta::seconds(30), TimeDelta::seconds(10)),
+            9
+        );
+        assert_eq!(way.nodes().len(), 12);
+        for (i, node) in way.nodes()[2..11].iter().enumerate() {
+            let secs = 30 + 10 * i as i64;
+            assert_eq!(node.timestamp, DateTime::from_timestamp(secs, 0).unwrap());
+            let expected = 0.001 + 0.01 * (secs - 20) as f64 / 100.0;
+            assert!((node.coords().longitude().as_degrees() - expected).abs() < 1e-9);
+            assert_eq!(node.accuracy(), None);
+        }
+        assert!(way
+            .nodes()
+            .windows(2)
+            .all(|w| w[0].timestamp < w[1].timestamp));
+        // The nodes are on the line, so the length is the same.
+        assert!((way.length().as_metres() - length.as_metres()).abs() < 1e-6);
+
+        // A gap between antipodal nodes is not filled.
+        let mut way = Way::new();
+        way.append(pos(0.0, 0.0, 0));
+        way.append(pos(0.0, 180.0, 100));
+        assert_eq!(
+            way.interpolate_gaps(TimeDelta::seconds(30), TimeDelta::seconds(10)),
+            0
+        );
+    }
+
+    #[test]
+    fn test_segment_speeds() {
+        let mut way = Way::new();
+        assert!(way.segment_speeds().is_empty());
+        way.append(pos(0.0, 0.0, 0));
+        assert!(way.segment_speeds().is_empty());
+        // About 111 m in 60 s, a stop, about 111 m in 30 s and then a segment without time.
+        way.append(pos(0.001, 0.0, 60));
+        way.append(pos(0.001, 0.0, 120));
+        way.append(pos(0.002, 0.0, 150));
+        way.append(pos(0.003, 0.0, 150));
+        let segment = PLANET
+            .distance(
+                LatLong::from_degrees(0.0, 0.0).to_nvector(),
+                LatLong::from_degrees(0.001, 0.0).to_nvector(),
+            )
+            .as_metres();
+        let speeds = way
+            .segment_speeds()
+            .into_iter()
+            .map(|x| x.as_metres_per_second())
+            .collect::<Vec<_>>();
+        assert_eq!(speeds.len(), 4);
+        assert!((speeds[0] - segment / 60.0).abs() < 1e-6);
+        assert_eq!(speeds[1], 0.0);
+        assert!((speeds[2] - segment / 30.0).abs() < 1e-6);
+        assert_eq!(speeds[3], 0.0);
+    }
+
+    #[test]
+    fn test_moving_time() {
+        let mut way = Way::new();
+        assert_eq!(way.total_time(), TimeDelta::zero());
+        way.append(pos(0.0, 0.0, 0));
+        assert_eq!(way.total_time(), TimeDelta::zero());
+        assert_eq!(
+            way.moving_time(Speed::from_metres_per_second(0.5)),
+            TimeDelta::zero()
+        );
+        // About 111 m in 60 s, a stop for 120 s, and about 111 m in 30 s.
+        way.append(pos(0.001, 0.0, 60));
+        way.append(pos(0.001, 0.0, 120));
+        way.append(pos(0.001, 0.0, 180));
+        way.append(pos(0.002, 0.0, 210));
+        let min_speed = Speed::from_metres_per_second(0.5);
+        assert_eq!(way.total_time(), TimeDelta::seconds(210));
+        assert_eq!(way.moving_time(min_speed), TimeDelta::seconds(90));
+        let moving_average = way.moving_average_speed(min_speed).unwrap();
+        let expected = way.length().as_metres() / 90.0;
+        assert!((moving_average.as_metres_per_second() - expected).abs() < 1e-6);
+        assert_eq!(
+            way.moving_time(Speed::from_metres_per_second(100.0)),
+            TimeDelta::zero()
+        );
+        assert_eq!(
+            way.moving_average_speed(Speed::from_metres_per_second(100.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_averaged_saved_pos() {
+        let positions = [pos(0.0, 0.0, 0), pos(0.0, 0.001, 1), pos(0.0, 0.002, 2)];
+        let saved = SavedPos::new_averaged("a".into(), &positions).unwrap();
+        let averaging = saved.averaged.unwrap();
+        assert_eq!(averaging.samples, 3);
+        let middle = PLANET.distance(positions[1].nvector(), saved.nvector());
+        assert!(middle.as_metres() < 1e-3);
+        let side = PLANET.distance(positions[0].nvector(), positions[1].nvector());
+        let expected = (2.0 / 3.0f64).sqrt() * side.as_metres();
+        assert!((averaging.std_dev.as_metres() - expected).abs() < 1e-3);
+        assert_eq!(saved.timestamp, positions[2].timestamp);
+        assert!(SavedPos::new_averaged("b".into(), &[] as &[PosWithTimestamp]).is_none());
+    }
+
+    #[test]
+    fn test_averaged_saved_pos_weighted() {
+        let with_accuracy = |mut x: PosWithTimestamp, accuracy: f64| {
+            x.pos.accuracy = Some(Length::from_metres(accuracy));
+            x
+        };
+        let positions = [
+            with_accuracy(pos(0.0, 0.0, 0), 5.0),
+            with_accuracy(pos(0.0, 0.002, 1), 5.0),
+        ];
+        let saved = SavedPos::new_averaged("a".into(), &positions).unwrap();
+        let midpoint = LatLong::from_degrees(0.0, 0.001).to_nvector();
+        assert!(PLANET.distance(midpoint, saved.nvector()).as_metres() < 1e-3);
+
+        // The more accurate position weighs three times as much.
+        let positions = [
+            with_accuracy(pos(0.0, 0.0, 0), 5.0),
+            with_accuracy(pos(0.0, 0.004, 1), 15.0),
+        ];
+        let saved = SavedPos::new_averaged("a".into(), &positions).unwrap();
+        let expected = LatLong::from_degrees(0.0, 0.001).to_nvector();
+        assert!(PLANET.distance(expected, saved.nvector()).as_metres() < 1e-2);
+        assert_eq!(saved.pos.accuracy, Some(Length::from_metres(10.0)));
+    }
+
+    #[test]
+    fn test_merge() {
+        let way = |nodes: &[(f64, i64)]| {
+            let mut rec = RecordedWay::new();
+            for &(long, secs) in nodes {
+                rec.add(&pos(0.0, long, secs));
+            }
+            rec
+        };
+        let first = way(&[(0.0, 0), (0.01, 1), (0.02, 2)]);
+        let second = way(&[(0.03, 3), (0.05, 4)]);
+        let merged = first.merge(&second);
+        assert_eq!(merged.way.nodes().len(), 5);
+        let gap = PLANET.distance(
+            LatLong::from_degrees(0.0, 0.02).to_nvector(),
+            LatLong::from_degrees(0.0, 0.03).to_nvector(),
+        );
+        assert!(
+            (merged.way.length() - (first.way.length() + gap + second.way.length()))
+                .as_metres()
+                .abs()
+                < 1e-6
+        );
+        // The order of the arguments doesn't matter for the nodes.
+        assert_eq!(second.merge(&first).way, merged.way);
+
+        // Overlapping ways are interleaved, and the node of the first way is kept if the
+        // timestamps are equal.
+        let overlapping = way(&[(0.5, 1), (0.015, 5)]);
+        let merged = first.merge(&overlapping);
+        let longitudes = merged
+            .way
+            .nodes()
+            .iter()
+            .map(|x| x.coords().longitude().as_degrees())
+            .collect::<Vec<_>>();
+        assert_eq!(longitudes.len(), 4);
+        for (a, b) in longitudes.iter().zip([0.0, 0.01, 0.02, 0.015]) {
+            assert!((a - b).abs() < 1e-9);
+        }
+        let mut repaired = merged.clone();
+        repaired.repair();
+        assert!(
+            (merged.way.length() - repaired.way.length())
+                .as_metres()
+                .abs()
+                < 1e-6
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        let mut rec = RecordedWay::new();
+        for i in 0..5 {
+            rec.add(&pos(0.0, i as f64 / 100.0, i * 10));
+        }
+        assert_eq!(rec.split(0), None);
+        assert_eq!(rec.split(5), None);
+        let (a, b) = rec.split(2).unwrap();
+        assert_eq!(a.way.nodes(), &rec.way.nodes()[..2]);
+        assert_eq!(b.way.nodes(), &rec.way.nodes()[2..]);
+        let gap = PLANET.distance(
+            LatLong::from_degrees(0.0, 0.01).to_nvector(),
+            LatLong::from_degrees(0.0, 0.02).to_nvector(),
+        );
+        assert!(
+            (a.way.length() + gap + b.way.length() - rec.way.length())
+                .as_metres()
+                .abs()
+                < 1e-6
+        );
+
+        let at = |secs| rec.index_at(DateTime::from_timestamp(secs, 0).unwrap());
+        assert_eq!(at(-5), 0);
+        assert_eq!(at(20), 2);
+        assert_eq!(at(25), 3);
+        assert_eq!(at(50), 5);
+    }
+
+    #[test]
+    fn test_decode_legacy_saved_positions() {
+        let legacy = LegacySavedPos {
+            name: "a".into(),
+            pos: pos(1.0, 2.0, 3).pos,
+            timestamp: DateTime::from_timestamp(3, 0).unwrap(),
+        };
+        let rtree = RTree::bulk_load(vec![legacy.clone()]);
+        let names = HashMap::from([(legacy.name.clone(), legacy.clone())]);
+        let bytes = bincode::serialize(&(&rtree, &names)).unwrap();
+        let (rtree, names) = decode_legacy_saved_positions(&bytes).unwrap();
+        assert_eq!(rtree.size(), 1);
+        let saved = &names["a"];
+        assert_eq!(saved.pos, legacy.pos);
+        assert_eq!(saved.timestamp, legacy.timestamp);
+        assert_eq!(saved.averaged, None);
+        assert_eq!(rtree.iter().next(), Some(saved));
+    }
+
     #[test]
     fn test_line_extrema() {
         let angles = [
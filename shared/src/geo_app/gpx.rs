@@ -0,0 +1,293 @@
+//! Reading and writing of [GPX 1.1](https://www.topografix.com/GPX/1/1/) files.
+
+use std::fmt::Write as _;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use compact_str::{format_compact, CompactString};
+use jord::{LatLong, Length};
+use roxmltree::{Document, Node};
+
+use super::geo_traits::*;
+use super::geo_types::{PosWithTimestamp, Position, RecordedWay, Way};
+
+/// Escape a string so that it can be used as text or an attribute value in XML.
+pub(super) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write a way as a GPX document with a single track and track segment.
+///
+/// Altitudes are written as `<ele>` elements when they are known.
+pub fn way_to_gpx(name: &str, way: &Way<PosWithTimestamp>) -> String {
+    let mut gpx = String::new();
+    gpx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    gpx.push_str(
+        "<gpx version=\"1.1\" creator=\"Geosuper\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+    );
+    gpx.push_str("  <trk>\n");
+    writeln!(gpx, "    <name>{}</name>", escape(name)).unwrap();
+    gpx.push_str("    <trkseg>\n");
+    for node in way.nodes() {
+        writeln!(
+            gpx,
+            "      <trkpt lat=\"{}\" lon=\"{}\">",
+            node.coords().latitude().as_degrees(),
+            node.coords().longitude().as_degrees()
+        )
+        .unwrap();
+        if let Some(altitude) = node.altitude() {
+            writeln!(gpx, "        <ele>{}</ele>", altitude.as_metres()).unwrap();
+        }
+        writeln!(
+            gpx,
+            "        <time>{}</time>",
+            node.timestamp()
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+        )
+        .unwrap();
+        gpx.push_str("      </trkpt>\n");
+    }
+    gpx.push_str("    </trkseg>\n");
+    gpx.push_str("  </trk>\n");
+    gpx.push_str("</gpx>\n");
+    gpx
+}
+
+/// A waypoint in a GPX file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waypoint {
+    pub name: Option<CompactString>,
+    pub pos: Position,
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// The contents of a GPX file.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Gpx {
+    /// All `<wpt>` elements.
+    pub waypoints: Vec<Waypoint>,
+    /// All `<trk>` elements with their names. All segments in a track are joined, and track
+    /// points without a timestamp are skipped.
+    pub tracks: Vec<(Option<CompactString>, RecordedWay)>,
+    /// The number of `<trk>` elements which were skipped since none of their points had a
+    /// timestamp.
+    pub skipped_tracks: usize,
+}
+
+/// Get the trimmed text of the first child element with a certain name.
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|x| x.has_tag_name(name))
+        .and_then(|x| x.text())
+        .map(str::trim)
+}
+
+/// Parse a `<wpt>` or `<trkpt>` element.
+fn parse_point(node: Node) -> Result<Waypoint, CompactString> {
+    let tag = node.tag_name().name();
+    let coord = |attr: &str| {
+        node.attribute(attr)
+            .and_then(|x| x.trim().parse::<f64>().ok())
+            .ok_or_else(|| format_compact!("Missing or invalid {attr} in <{tag}>"))
+    };
+    let coords = LatLong::from_degrees(coord("lat")?, coord("lon")?);
+    let altitude = child_text(node, "ele")
+        .map(|x| x.parse::<f64>().map(Length::from_metres))
+        .transpose()
+        .map_err(|e| format_compact!("Invalid <ele> in <{tag}>: {e}"))?;
+    let timestamp = child_text(node, "time")
+        .map(|x| DateTime::parse_from_rfc3339(x).map(|t| t.with_timezone(&Utc)))
+        .transpose()
+        .map_err(|e| format_compact!("Invalid <time> in <{tag}>: {e}"))?;
+    Ok(Waypoint {
+        name: child_text(node, "name").map(Into::into),
+        pos: Position {
+            coords,
+            altitude,
+            accuracy: None,
+            altitude_accuracy: None,
+        },
+        timestamp,
+    })
+}
+
+/// Parse a GPX file.
+pub(crate) fn parse_gpx(bytes: &[u8]) -> Result<Gpx, CompactString> {
+    let text = std::str::from_utf8(bytes).map_err(|e| format_compact!("Invalid UTF-8: {e}"))?;
+    let doc = Document::parse(text).map_err(|e| format_compact!("Invalid XML: {e}"))?;
+    let root = doc.root_element();
+    if !root.has_tag_name("gpx") {
+        return Err("The root element is not <gpx>".into());
+    }
+    let mut gpx = Gpx::default();
+    for node in root.children() {
+        if node.has_tag_name("wpt") {
+            gpx.waypoints.push(parse_point(node)?);
+        } else if node.has_tag_name("trk") {
+            let mut rec = RecordedWay::new();
+            for trkpt in node.descendants().filter(|x| x.has_tag_name("trkpt")) {
+                let point = parse_point(trkpt)?;
+                if let Some(timestamp) = point.timestamp {
+                    rec.add(&PosWithTimestamp {
+                        pos: point.pos,
+                        timestamp,
+                    });
+                }
+            }
+            if rec.way.nodes().is_empty() {
+                gpx.skipped_tracks += 1;
+                continue;
+            }
+            gpx.tracks
+                .push((child_text(node, "name").map(Into::into), rec));
+        }
+    }
+    Ok(gpx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_way_to_gpx() {
+        let mut way = Way::new();
+        way.append(PosWithTimestamp {
+            pos: Position {
+                coords: LatLong::from_degrees(1.5, -2.25),
+                altitude: Some(Length::from_metres(12.5)),
+                accuracy: None,
+                altitude_accuracy: None,
+            },
+            timestamp: DateTime::from_timestamp(0, 0).unwrap(),
+        });
+        way.append(PosWithTimestamp {
+            pos: Position {
+                coords: LatLong::from_degrees(1.75, -2.0),
+                altitude: None,
+                accuracy: None,
+                altitude_accuracy: None,
+            },
+            timestamp: DateTime::from_timestamp(60, 0).unwrap(),
+        });
+        let gpx = way_to_gpx("A <walk> & more", &way);
+        assert!(gpx.starts_with("<?xml"));
+        assert!(gpx.contains("<name>A &lt;walk&gt; &amp; more</name>"));
+        for node in way.nodes() {
+            assert!(gpx.contains(&format!(
+                "<trkpt lat=\"{}\" lon=\"{}\">",
+                node.coords().latitude().as_degrees(),
+                node.coords().longitude().as_degrees()
+            )));
+        }
+        assert_eq!(gpx.matches("<trkpt ").count(), 2);
+        assert_eq!(gpx.matches("</trkpt>").count(), 2);
+        assert_eq!(gpx.matches("<ele>").count(), 1);
+        assert!(gpx.contains("<ele>12.5</ele>"));
+        assert!(gpx.contains("<time>1970-01-01T00:00:00.000Z</time>"));
+        assert!(gpx.contains("<time>1970-01-01T00:01:00.000Z</time>"));
+        assert!(gpx.trim_end().ends_with("</gpx>"));
+    }
+
+    #[test]
+    fn test_parse_gpx() {
+        let gpx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <wpt lat="59.5" lon="18.25">
+    <ele>10</ele>
+    <time>2024-05-01T12:00:00Z</time>
+    <name>Cabin</name>
+  </wpt>
+  <wpt lat="60" lon="18"/>
+  <trk>
+    <name>Walk</name>
+    <trkseg>
+      <trkpt lat="59.5" lon="18.25"><time>2024-05-01T12:00:00Z</time></trkpt>
+      <trkpt lat="59.6" lon="18.25"><time>2024-05-01T12:01:00Z</time></trkpt>
+    </trkseg>
+    <trkseg>
+      <trkpt lat="59.7" lon="18.25"><ele>5</ele><time>2024-05-01T12:02:00Z</time></trkpt>
+      <trkpt lat="59.8" lon="18.25"/>
+    </trkseg>
+  </trk>
+  <trk>
+    <name>Untimed</name>
+    <trkseg><trkpt lat="59.5" lon="18.25"/></trkseg>
+  </trk>
+</gpx>"#;
+        let gpx = parse_gpx(gpx.as_bytes()).unwrap();
+        assert_eq!(gpx.waypoints.len(), 2);
+        assert_eq!(gpx.waypoints[0].name.as_deref(), Some("Cabin"));
+        assert_eq!(
+            gpx.waypoints[0].pos.altitude,
+            Some(Length::from_metres(10.0))
+        );
+        assert_eq!(
+            gpx.waypoints[0].timestamp,
+            Some(DateTime::from_timestamp(1714564800, 0).unwrap())
+        );
+        assert_eq!(gpx.waypoints[1].name, None);
+        assert_eq!(gpx.waypoints[1].timestamp, None);
+        // The track without timestamps is skipped.
+        assert_eq!(gpx.tracks.len(), 1);
+        assert_eq!(gpx.skipped_tracks, 1);
+        let (name, rec) = &gpx.tracks[0];
+        assert_eq!(name.as_deref(), Some("Walk"));
+        // The last track point has no timestamp and is skipped.
+        assert_eq!(rec.way.nodes().len(), 3);
+        assert_eq!(
+            rec.way.nodes()[2].altitude(),
+            Some(Length::from_metres(5.0))
+        );
+    }
+
+    #[test]
+    fn test_gpx_round_trip() {
+        let mut way = Way::new();
+        for i in 0..5 {
+            way.append(PosWithTimestamp {
+                pos: Position {
+                    coords: LatLong::from_degrees(i as f64 / 7.0, 1.0 / 3.0),
+                    altitude: Some(Length::from_metres(i as f64 * 1.5)),
+                    accuracy: None,
+                    altitude_accuracy: None,
+                },
+                timestamp: DateTime::from_timestamp(i * 10, 0).unwrap(),
+            });
+        }
+        let gpx = parse_gpx(way_to_gpx("Round trip", &way).as_bytes()).unwrap();
+        let (name, rec) = &gpx.tracks[0];
+        assert_eq!(name.as_deref(), Some("Round trip"));
+        assert_eq!(rec.way.nodes().len(), way.nodes().len());
+        for (a, b) in rec.way.nodes().iter().zip(way.nodes()) {
+            assert_eq!(a.timestamp, b.timestamp);
+            assert_eq!(a.altitude(), b.altitude());
+            assert!(
+                (a.coords().latitude().as_degrees() - b.coords().latitude().as_degrees()).abs()
+                    < 1e-9
+            );
+            assert!(
+                (a.coords().longitude().as_degrees() - b.coords().longitude().as_degrees()).abs()
+                    < 1e-9
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_malformed_gpx() {
+        assert!(parse_gpx(b"<gpx><wpt lat=\"1\" lon=\"2\"></gpx>").is_err());
+        assert!(parse_gpx(b"<kml></kml>").is_err());
+        assert!(parse_gpx(b"<gpx><wpt lat=\"north\" lon=\"2\"/></gpx>").is_err());
+    }
+}
@@ -0,0 +1,608 @@
+//! Translations of messages to the user.
+//!
+//! Every message which can be translated is a variant of `Text`, and `Text::translate` is the
+//! catalog with the message in every language. Details which come from elsewhere, like the
+//! message of a `GeoError` or a storage error, are not translated.
+
+use chrono::{DateTime, Utc};
+use compact_str::{format_compact, CompactString};
+use crux_geolocation::GeoError;
+use serde::{Deserialize, Serialize};
+
+use super::{AppError, StorageAction, MIN_REPLAY_SPEED_FACTOR};
+
+/// The language of messages to the user.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    English,
+    Swedish,
+}
+
+/// A message which can be translated.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Text<'a> {
+    Error(&'a AppError),
+    NoGpsInformation,
+    GpsError(&'a GeoError),
+    /// The age of the last position in seconds and whether it is stale.
+    LastFix {
+        secs: i64,
+        stale: bool,
+    },
+    /// A formatted accuracy.
+    Accuracy(&'a str),
+    /// A formatted altitude accuracy.
+    AltitudeAccuracy(&'a str),
+    /// The number of positions recorded within a window of some seconds before now.
+    PositionsInWindow {
+        n: usize,
+        secs: i64,
+    },
+    PowerSaverOn,
+    /// The current position is at the target or a saved position with this name.
+    Arrived(&'a str),
+    /// The numbers of imported saved positions and recorded ways, and when the data was exported
+    /// if it is known.
+    Imported {
+        positions: usize,
+        ways: usize,
+        exported_at: Option<DateTime<Utc>>,
+    },
+    /// The number of tracks in a GPX file which were skipped since they had no timestamps.
+    SkippedUntimedTracks(usize),
+    /// A recorded way has been simplified from one number of nodes to another.
+    Simplified {
+        name: &'a str,
+        before: usize,
+        after: usize,
+    },
+    /// A recorded way has been downsampled from one number of nodes to another.
+    Downsampled {
+        name: &'a str,
+        before: usize,
+        after: usize,
+    },
+    /// The number of duplicate saved positions which have been removed.
+    DuplicatesRemoved(usize),
+    /// The number of recorded ways imported with `Event::ImportWays`.
+    ImportedWays(usize),
+    /// The number of keys and bytes the app uses in persistant storage, out of about `budget` bytes.
+    StorageUsage {
+        keys: usize,
+        bytes: usize,
+        budget: usize,
+    },
+    /// Saved positions, joined by commas, have been removed since at most `max` may be saved.
+    Evicted {
+        names: &'a str,
+        max: usize,
+    },
+    /// Nodes of a recorded way have been saved as positions, and some may have been skipped since
+    /// their names were already in use.
+    SavedFromWay {
+        name: &'a str,
+        saved: usize,
+        skipped: usize,
+    },
+    /// The number of positions of the recording since app start which have been recovered.
+    RecordingRecovered(usize),
+    /// What failed in an `AppError::Storage`, completing "Failed to".
+    StorageAction(&'a StorageAction),
+}
+
+impl Text<'_> {
+    /// The message in a language.
+    pub fn translate(&self, lang: Lang) -> CompactString {
+        match lang {
+            Lang::English => self.english(),
+            Lang::Swedish => self.swedish(),
+        }
+    }
+
+    fn english(&self) -> CompactString {
+        match *self {
+            Self::Error(e) => match e {
+                AppError::PosNameExists(name) => {
+                    format_compact!("Error: There is already a position named {name}")
+                }
+                AppError::WayNameExists(name) => {
+                    format_compact!("Error: The name {name} is already in use.")
+                }
+                AppError::PosNotFound(name) => {
+                    format_compact!("Error: Position {name} does not exist.")
+                }
+                AppError::WayNotFound(name) => format_compact!("Error: Way {name} does not exist."),
+                AppError::NoCurrentPosition => "Error: The current position is not known.".into(),
+                AppError::NoRecordedPositions => "Error: No positions recorded.".into(),
+                AppError::InvertedTimeRange => {
+                    "Error: The start of the time range is after the end.".into()
+                }
+                AppError::EmptyTimeRange => {
+                    "Error: No positions were recorded in the time range.".into()
+                }
+                AppError::InvalidSplitIndex { name, max } => format_compact!(
+                    "Error: {name} can only be split at an index between 1 and {max}."
+                ),
+                AppError::InvalidNodeIndex { name, nodes } => {
+                    format_compact!("Error: {name} has only {}.", count(*nodes, "node", "nodes"))
+                }
+                AppError::NoSyncEndpoint => "Error: No sync endpoint has been set.".into(),
+                AppError::UploadRejected { name, status } => {
+                    format_compact!("Error: The server rejected {name} with status {status}.")
+                }
+                AppError::UploadFailed { name, error } => {
+                    format_compact!("Error: Failed to reach the server to upload {name}: {error}")
+                }
+                AppError::ReplayWhileGeolocating => {
+                    "Error: Geolocation must be stopped to replay a way.".into()
+                }
+                AppError::InvalidSpeedFactor(factor) => format_compact!(
+                    "Error: The speed factor must be at least {MIN_REPLAY_SPEED_FACTOR}, not \
+                     {factor}."
+                ),
+                AppError::RateWindowTooShort => "Error: The window must be at least 1 s.".into(),
+                AppError::ZeroAutosaveInterval => {
+                    "Error: The interval must be longer than zero.".into()
+                }
+                AppError::UnknownKey(key) => {
+                    format_compact!("Error: {key} is not a key used by this app.")
+                }
+                AppError::NoBackup(key) => format_compact!("Error: There is no backup of {key}."),
+                AppError::InvalidDataFile(error) => {
+                    format_compact!("Error: Failed to read the data file: {error}")
+                }
+                AppError::InvalidGpxFile(error) => {
+                    format_compact!("Error: Failed to read the GPX file: {error}")
+                }
+                AppError::NotAGeofence(name) => format_compact!("Error: {name} is not a geofence."),
+                AppError::EmptyWay(name) => {
+                    format_compact!("Error: {name} doesn't have any nodes.")
+                }
+                AppError::InvalidSmoothingFactor(factor) => format_compact!(
+                    "Error: The smoothing factor must be between 0 and 1, not {factor}."
+                ),
+                AppError::ZeroNodeInterval => {
+                    "Error: Every nth node can't be saved with n = 0.".into()
+                }
+                AppError::PermissionDenied => "Error: Permission to access the position was \
+                    denied. Allow it and start the GPS again."
+                    .into(),
+                AppError::Serialization { what, error } => {
+                    format_compact!("Browser Error: Error while decoding {what}: {error}")
+                }
+                AppError::Storage { action, error } => format_compact!(
+                    "Internal Error: Failed to {}: {error}",
+                    Self::StorageAction(action).english()
+                ),
+            },
+            Self::NoGpsInformation => "No GPS information".into(),
+            Self::GpsError(e) => format_compact!("GPS Error: {e}"),
+            Self::LastFix { secs, stale } => format_compact!(
+                "Last fix: {secs} s ago{}, ",
+                if stale { " (stale)" } else { "" }
+            ),
+            Self::Accuracy(accuracy) => format_compact!("Accuracy: {accuracy}, "),
+            Self::AltitudeAccuracy(accuracy) => format_compact!("Altitude accuracy: {accuracy}, "),
+            Self::PositionsInWindow { n, secs: 60 } => {
+                format_compact!("{} in the last minute.", count(n, "position", "positions"))
+            }
+            Self::PositionsInWindow { n, secs } if secs % 60 == 0 => {
+                format_compact!(
+                    "{} in the last {} minutes.",
+                    count(n, "position", "positions"),
+                    secs / 60
+                )
+            }
+            Self::PositionsInWindow { n, secs } => {
+                format_compact!(
+                    "{} in the last {secs} s.",
+                    count(n, "position", "positions")
+                )
+            }
+            Self::PowerSaverOn => " Power saver on.".into(),
+            Self::Arrived(name) => format_compact!("You have arrived at {name}."),
+            Self::Imported {
+                positions,
+                ways,
+                exported_at,
+            } => {
+                let mut text = format_compact!(
+                    "Imported {} and {}",
+                    count(positions, "position", "positions"),
+                    count(ways, "way", "ways")
+                );
+                if let Some(exported_at) = exported_at {
+                    text += &format_compact!(" exported {}", exported_at.format("%Y-%m-%d"));
+                }
+                text + "."
+            }
+            Self::SkippedUntimedTracks(n) => format_compact!(
+                " {} without timestamps {} skipped.",
+                count(n, "track", "tracks"),
+                if n == 1 { "was" } else { "were" }
+            ),
+            Self::Simplified {
+                name,
+                before,
+                after,
+            } => format_compact!(
+                "{name} has been simplified from {before} to {}.",
+                count(after, "node", "nodes")
+            ),
+            Self::Downsampled {
+                name,
+                before,
+                after,
+            } => format_compact!(
+                "{name} has been downsampled from {before} to {}.",
+                count(after, "node", "nodes")
+            ),
+            Self::DuplicatesRemoved(n) => format_compact!(
+                "{} {} been removed.",
+                count(n, "duplicate position", "duplicate positions"),
+                if n == 1 { "has" } else { "have" }
+            ),
+            Self::ImportedWays(n) => format_compact!("Imported {}.", count(n, "way", "ways")),
+            Self::StorageUsage {
+                keys,
+                bytes,
+                budget,
+            } => format_compact!(
+                "{} {} {:.1} KB of persistant storage, which is {:.1}% of about {} MB.",
+                count(keys, "key", "keys"),
+                if keys == 1 { "uses" } else { "use" },
+                bytes as f64 / 1024.0,
+                bytes as f64 / budget as f64 * 100.0,
+                budget / (1024 * 1024),
+            ),
+            Self::Evicted { names, max } => format_compact!(
+                "{names} removed since at most {} may be saved.",
+                count(max, "position", "positions")
+            ),
+            Self::SavedFromWay {
+                name,
+                saved,
+                skipped,
+            } => {
+                let mut text = format_compact!(
+                    "Saved {} from {name}.",
+                    count(saved, "position", "positions")
+                );
+                if skipped == 1 {
+                    text += " 1 was skipped since its name is already in use.";
+                } else if skipped > 1 {
+                    text += &format_compact!(
+                        " {skipped} were skipped since their names are already in use."
+                    );
+                }
+                text
+            }
+            Self::RecordingRecovered(n) => format_compact!(
+                "{} of the recording since app start {} been recovered.",
+                count(n, "position", "positions"),
+                if n == 1 { "has" } else { "have" }
+            ),
+            Self::StorageAction(action) => match action {
+                StorageAction::SaveSavedPositions => "save the saved positions".into(),
+                StorageAction::SaveWay => "save a way".into(),
+                StorageAction::DeleteWay => "delete a way".into(),
+                StorageAction::SaveWayIndex => "save the index of the saved ways".into(),
+                StorageAction::DeleteOldSavedWays => "delete the old saved ways".into(),
+                StorageAction::SaveUnitSystem => "save the unit system".into(),
+                StorageAction::SaveLanguage => "save the language".into(),
+                StorageAction::SaveAccuracy => "save the accuracy".into(),
+                StorageAction::SaveSyncEndpoint => "save the sync endpoint".into(),
+                StorageAction::SaveLiveAutosave => "save the autosave interval".into(),
+                StorageAction::SaveLiveRecording => "save the recording since app start".into(),
+                StorageAction::DeleteLiveRecording => {
+                    "delete the saved recording since app start".into()
+                }
+                StorageAction::RecoverLiveRecording => {
+                    "recover the recording since app start".into()
+                }
+                StorageAction::ListKeys => "list the keys in storage".into(),
+                StorageAction::Retrieve(key) => format_compact!("retrieve {key}"),
+                StorageAction::RetrieveBackup(key) => {
+                    format_compact!("retrieve the backup of {key}")
+                }
+                StorageAction::BackUpUndecodable => "back up data which couldn't be decoded".into(),
+                StorageAction::DeleteBackup => "delete a backup".into(),
+            },
+        }
+    }
+
+    fn swedish(&self) -> CompactString {
+        match *self {
+            Self::Error(e) => match e {
+                AppError::PosNameExists(name) => {
+                    format_compact!("Fel: Det finns redan en position som heter {name}")
+                }
+                AppError::WayNameExists(name) => {
+                    format_compact!("Fel: Namnet {name} används redan.")
+                }
+                AppError::PosNotFound(name) => {
+                    format_compact!("Fel: Positionen {name} finns inte.")
+                }
+                AppError::WayNotFound(name) => format_compact!("Fel: Spåret {name} finns inte."),
+                AppError::NoCurrentPosition => "Fel: Den nuvarande positionen är okänd.".into(),
+                AppError::NoRecordedPositions => "Fel: Inga positioner har spelats in.".into(),
+                AppError::InvertedTimeRange => {
+                    "Fel: Tidsintervallets början är efter dess slut.".into()
+                }
+                AppError::EmptyTimeRange => {
+                    "Fel: Inga positioner spelades in under tidsintervallet.".into()
+                }
+                AppError::InvalidSplitIndex { name, max } => {
+                    format_compact!("Fel: {name} kan bara delas vid ett index mellan 1 och {max}.")
+                }
+                AppError::InvalidNodeIndex { name, nodes } => {
+                    format_compact!("Fel: {name} har bara {}.", count(*nodes, "nod", "noder"))
+                }
+                AppError::NoSyncEndpoint => "Fel: Ingen synkroniseringsadress har angetts.".into(),
+                AppError::UploadRejected { name, status } => {
+                    format_compact!("Fel: Servern avvisade {name} med status {status}.")
+                }
+                AppError::UploadFailed { name, error } => {
+                    format_compact!("Fel: Kunde inte nå servern för att ladda upp {name}: {error}")
+                }
+                AppError::ReplayWhileGeolocating => {
+                    "Fel: Positioneringen måste stoppas för att spela upp ett spår.".into()
+                }
+                AppError::InvalidSpeedFactor(factor) => format_compact!(
+                    "Fel: Hastighetsfaktorn måste vara minst {MIN_REPLAY_SPEED_FACTOR}, inte \
+                     {factor}."
+                ),
+                AppError::RateWindowTooShort => "Fel: Fönstret måste vara minst 1 s.".into(),
+                AppError::ZeroAutosaveInterval => "Fel: Intervallet måste vara längre än noll.".into(),
+                AppError::UnknownKey(key) => {
+                    format_compact!("Fel: {key} är inte en nyckel som används av appen.")
+                }
+                AppError::NoBackup(key) => {
+                    format_compact!("Fel: Det finns ingen säkerhetskopia av {key}.")
+                }
+                AppError::InvalidDataFile(error) => {
+                    format_compact!("Fel: Kunde inte läsa datafilen: {error}")
+                }
+                AppError::InvalidGpxFile(error) => {
+                    format_compact!("Fel: Kunde inte läsa GPX-filen: {error}")
+                }
+                AppError::NotAGeofence(name) => format_compact!("Fel: {name} är inte ett geostaket."),
+                AppError::EmptyWay(name) => format_compact!("Fel: {name} har inga noder."),
+                AppError::InvalidSmoothingFactor(factor) => format_compact!(
+                    "Fel: Utjämningsfaktorn måste vara mellan 0 och 1, inte {factor}."
+                ),
+                AppError::ZeroNodeInterval => "Fel: Var n:te nod kan inte sparas med n = 0.".into(),
+                AppError::PermissionDenied => "Fel: Åtkomst till positionen nekades. Tillåt den \
+                    och starta GPS:en igen."
+                    .into(),
+                AppError::Serialization { what, error } => {
+                    format_compact!("Webbläsarfel: Fel vid avkodning av {what}: {error}")
+                }
+                AppError::Storage { action, error } => format_compact!(
+                    "Internt fel: Misslyckades att {}: {error}",
+                    Self::StorageAction(action).swedish()
+                ),
+            },
+            Self::NoGpsInformation => "Ingen GPS-information".into(),
+            Self::GpsError(e) => format_compact!("GPS-fel: {e}"),
+            Self::LastFix { secs, stale } => format_compact!(
+                "Senaste position: för {secs} s sedan{}, ",
+                if stale { " (inaktuell)" } else { "" }
+            ),
+            Self::Accuracy(accuracy) => format_compact!("Noggrannhet: {accuracy}, "),
+            Self::AltitudeAccuracy(accuracy) => format_compact!("Höjdnoggrannhet: {accuracy}, "),
+            Self::PositionsInWindow { n, secs: 60 } => {
+                format_compact!("{} den senaste minuten.", count(n, "position", "positioner"))
+            }
+            Self::PositionsInWindow { n, secs } if secs % 60 == 0 => {
+                format_compact!(
+                    "{} de senaste {} minuterna.",
+                    count(n, "position", "positioner"),
+                    secs / 60
+                )
+            }
+            Self::PositionsInWindow { n, secs } => {
+                format_compact!("{} de senaste {secs} s.", count(n, "position", "positioner"))
+            }
+            Self::PowerSaverOn => " Energisparläge på.".into(),
+            Self::Arrived(name) => format_compact!("Du har kommit fram till {name}."),
+            Self::Imported {
+                positions,
+                ways,
+                exported_at,
+            } => {
+                let mut text = format_compact!(
+                    "Importerade {} och {}",
+                    count(positions, "position", "positioner"),
+                    count(ways, "spår", "spår")
+                );
+                if let Some(exported_at) = exported_at {
+                    text += &format_compact!(" exporterade {}", exported_at.format("%Y-%m-%d"));
+                }
+                text + "."
+            }
+            Self::SkippedUntimedTracks(n) => format_compact!(
+                " {} utan tidsstämplar hoppades över.",
+                count(n, "spår", "spår")
+            ),
+            Self::Simplified {
+                name,
+                before,
+                after,
+            } => format_compact!(
+                "{name} har förenklats från {before} till {}.",
+                count(after, "nod", "noder")
+            ),
+            Self::Downsampled {
+                name,
+                before,
+                after,
+            } => format_compact!(
+                "{name} har reducerats från {before} till {}.",
+                count(after, "nod", "noder")
+            ),
+            Self::DuplicatesRemoved(n) => format_compact!(
+                "{} har tagits bort.",
+                count(n, "dubblettposition", "dubblettpositioner")
+            ),
+            Self::ImportedWays(n) => format_compact!("Importerade {}.", count(n, "spår", "spår")),
+            Self::StorageUsage {
+                keys,
+                bytes,
+                budget,
+            } => format_compact!(
+                "{} använder {:.1} KB av den beständiga lagringen, vilket är {:.1} % av ungefär {} MB.",
+                count(keys, "nyckel", "nycklar"),
+                bytes as f64 / 1024.0,
+                bytes as f64 / budget as f64 * 100.0,
+                budget / (1024 * 1024),
+            ),
+            Self::Evicted { names, max } => format_compact!(
+                "{names} har tagits bort eftersom högst {} får sparas.",
+                count(max, "position", "positioner")
+            ),
+            Self::SavedFromWay {
+                name,
+                saved,
+                skipped,
+            } => {
+                let mut text = format_compact!(
+                    "Sparade {} från {name}.",
+                    count(saved, "position", "positioner")
+                );
+                if skipped == 1 {
+                    text += " 1 hoppades över eftersom namnet redan används.";
+                } else if skipped > 1 {
+                    text += &format_compact!(
+                        " {skipped} hoppades över eftersom namnen redan används."
+                    );
+                }
+                text
+            }
+            Self::RecordingRecovered(n) => format_compact!(
+                "{} av inspelningen sedan appstart har återställts.",
+                count(n, "position", "positioner")
+            ),
+            Self::StorageAction(action) => match action {
+                StorageAction::SaveSavedPositions => "spara de sparade positionerna".into(),
+                StorageAction::SaveWay => "spara ett spår".into(),
+                StorageAction::DeleteWay => "ta bort ett spår".into(),
+                StorageAction::SaveWayIndex => "spara indexet över de sparade spåren".into(),
+                StorageAction::DeleteOldSavedWays => "ta bort de gamla sparade spåren".into(),
+                StorageAction::SaveUnitSystem => "spara enhetssystemet".into(),
+                StorageAction::SaveLanguage => "spara språket".into(),
+                StorageAction::SaveAccuracy => "spara noggrannheten".into(),
+                StorageAction::SaveSyncEndpoint => "spara synkroniseringsadressen".into(),
+                StorageAction::SaveLiveAutosave => {
+                    "spara intervallet för automatisk sparning".into()
+                }
+                StorageAction::SaveLiveRecording => "spara inspelningen sedan appstart".into(),
+                StorageAction::DeleteLiveRecording => {
+                    "ta bort den sparade inspelningen sedan appstart".into()
+                }
+                StorageAction::RecoverLiveRecording => {
+                    "återställa inspelningen sedan appstart".into()
+                }
+                StorageAction::ListKeys => "lista nycklarna i lagringen".into(),
+                StorageAction::Retrieve(key) => format_compact!("hämta {key}"),
+                StorageAction::RetrieveBackup(key) => {
+                    format_compact!("hämta säkerhetskopian av {key}")
+                }
+                StorageAction::BackUpUndecodable => {
+                    "säkerhetskopiera data som inte kunde avkodas".into()
+                }
+                StorageAction::DeleteBackup => "ta bort en säkerhetskopia".into(),
+            },
+        }
+    }
+}
+
+/// A number followed by the singular or plural form of a noun.
+fn count(n: usize, singular: &str, plural: &str) -> CompactString {
+    format_compact!("{n} {}", if n == 1 { singular } else { plural })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        let e = AppError::PosNotFound("Home".into());
+        assert_eq!(
+            Text::Error(&e).translate(Lang::English),
+            "Error: Position Home does not exist."
+        );
+        assert_eq!(
+            Text::Error(&e).translate(Lang::Swedish),
+            "Fel: Positionen Home finns inte."
+        );
+        // `Display` is in English.
+        assert_eq!(e.to_string(), Text::Error(&e).translate(Lang::English));
+
+        let text = Text::LastFix {
+            secs: 3,
+            stale: true,
+        };
+        assert_eq!(text.translate(Lang::English), "Last fix: 3 s ago (stale), ");
+        assert_eq!(
+            text.translate(Lang::Swedish),
+            "Senaste position: för 3 s sedan (inaktuell), "
+        );
+        assert_eq!(
+            Text::Arrived("Hem").translate(Lang::Swedish),
+            "Du har kommit fram till Hem."
+        );
+
+        let e = AppError::InvalidNodeIndex {
+            name: "Walk".into(),
+            nodes: 1,
+        };
+        assert_eq!(e.to_string(), "Error: Walk has only 1 node.");
+
+        assert_eq!(
+            Text::DuplicatesRemoved(1).translate(Lang::English),
+            "1 duplicate position has been removed."
+        );
+        assert_eq!(
+            Text::PositionsInWindow { n: 1, secs: 60 }.translate(Lang::English),
+            "1 position in the last minute."
+        );
+        assert_eq!(
+            Text::RecordingRecovered(1).translate(Lang::English),
+            "1 position of the recording since app start has been recovered."
+        );
+        let text = Text::StorageUsage {
+            keys: 1,
+            bytes: 1024,
+            budget: 1024 * 1024,
+        };
+        assert_eq!(
+            text.translate(Lang::English),
+            "1 key uses 1.0 KB of persistant storage, which is 0.1% of about 1 MB."
+        );
+
+        let e = AppError::Storage {
+            action: StorageAction::Retrieve("language".into()),
+            error: "quota".into(),
+        };
+        assert_eq!(
+            Text::Error(&e).translate(Lang::English),
+            "Internal Error: Failed to retrieve language: quota"
+        );
+        assert_eq!(
+            Text::Error(&e).translate(Lang::Swedish),
+            "Internt fel: Misslyckades att hämta language: quota"
+        );
+
+        let e = AppError::UploadRejected {
+            name: "Walk".into(),
+            status: 500,
+        };
+        assert_eq!(
+            Text::Error(&e).translate(Lang::Swedish),
+            "Fel: Servern avvisade Walk med status 500."
+        );
+    }
+}
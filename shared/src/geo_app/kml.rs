@@ -0,0 +1,136 @@
+//! Writing of [KML](https://developers.google.com/kml/documentation/kmlreference) files, for
+//! instance for Google Earth.
+
+use std::fmt::Write as _;
+
+use super::geo_traits::*;
+use super::geo_types::SavedPos;
+use super::gpx::escape;
+
+/// Format coordinates as in a KML `<coordinates>` element: longitude, latitude and, if known,
+/// altitude, separated by commas.
+fn kml_coords(pos: &(impl Coords + Altitude)) -> String {
+    let mut coords = format!(
+        "{},{}",
+        pos.coords().longitude().as_degrees(),
+        pos.coords().latitude().as_degrees()
+    );
+    if let Some(altitude) = pos.altitude() {
+        write!(coords, ",{}", altitude.as_metres()).unwrap();
+    }
+    coords
+}
+
+/// Write saved positions as point placemarks and ways as line string placemarks in a KML
+/// document.
+pub fn to_kml<'a, T: Coords + Altitude + 'a>(
+    saved_positions: impl IntoIterator<Item = &'a SavedPos>,
+    ways: impl IntoIterator<Item = (&'a str, &'a [T])>,
+) -> String {
+    let mut kml = String::new();
+    kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n");
+    kml.push_str("  <Document>\n");
+    for pos in saved_positions {
+        kml.push_str("    <Placemark>\n");
+        writeln!(kml, "      <name>{}</name>", escape(&pos.name)).unwrap();
+        writeln!(
+            kml,
+            "      <Point><coordinates>{}</coordinates></Point>",
+            kml_coords(pos)
+        )
+        .unwrap();
+        kml.push_str("    </Placemark>\n");
+    }
+    for (name, nodes) in ways {
+        kml.push_str("    <Placemark>\n");
+        writeln!(kml, "      <name>{}</name>", escape(name)).unwrap();
+        kml.push_str("      <LineString>\n");
+        kml.push_str("        <coordinates>\n");
+        for node in nodes {
+            writeln!(kml, "          {}", kml_coords(node)).unwrap();
+        }
+        kml.push_str("        </coordinates>\n");
+        kml.push_str("      </LineString>\n");
+        kml.push_str("    </Placemark>\n");
+    }
+    kml.push_str("  </Document>\n");
+    kml.push_str("</kml>\n");
+    kml
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use jord::{LatLong, Length};
+    use roxmltree::Document;
+
+    use super::super::geo_types::{PosWithTimestamp, Position};
+    use super::*;
+
+    fn pos(latitude: f64, longitude: f64, altitude: Option<f64>) -> Position {
+        Position {
+            coords: LatLong::from_degrees(latitude, longitude),
+            altitude: altitude.map(Length::from_metres),
+            accuracy: None,
+            altitude_accuracy: None,
+        }
+    }
+
+    #[test]
+    fn test_to_kml() {
+        let saved = SavedPos {
+            name: "Cabin & lake".into(),
+            pos: pos(59.5, 18.25, Some(10.0)),
+            timestamp: DateTime::UNIX_EPOCH,
+            averaged: None,
+        };
+        let nodes = [pos(1.0, 2.0, None), pos(3.0, 4.0, Some(5.0))].map(|pos| PosWithTimestamp {
+            pos,
+            timestamp: DateTime::UNIX_EPOCH,
+        });
+        let kml = to_kml([&saved], [("Walk", &nodes[..])]);
+
+        let doc = Document::parse(&kml).unwrap();
+        let placemarks = doc
+            .descendants()
+            .filter(|x| x.has_tag_name("Placemark"))
+            .collect::<Vec<_>>();
+        assert_eq!(placemarks.len(), 2);
+        let text = |node: roxmltree::Node, tag: &str| {
+            node.descendants()
+                .find(|x| x.has_tag_name(tag))
+                .and_then(|x| x.text())
+                .unwrap()
+                .trim()
+                .to_string()
+        };
+        let coords = |node: roxmltree::Node| {
+            text(node, "coordinates")
+                .split_whitespace()
+                .map(|tuple| {
+                    tuple
+                        .split(',')
+                        .map(|x| x.parse::<f64>().unwrap())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let assert_coords = |node, expected: &[&[f64]]| {
+            let actual = coords(node);
+            assert_eq!(actual.len(), expected.len());
+            for (a, b) in actual.iter().zip(expected) {
+                assert_eq!(a.len(), b.len());
+                assert!(a.iter().zip(*b).all(|(x, y)| (x - y).abs() < 1e-9));
+            }
+        };
+        assert_eq!(text(placemarks[0], "name"), "Cabin & lake");
+        // The longitude comes before the latitude.
+        assert_coords(placemarks[0], &[&[18.25, 59.5, 10.0]]);
+        assert_eq!(text(placemarks[1], "name"), "Walk");
+        assert!(placemarks[1]
+            .descendants()
+            .any(|x| x.has_tag_name("LineString")));
+        assert_coords(placemarks[1], &[&[2.0, 1.0], &[4.0, 3.0, 5.0]]);
+    }
+}
@@ -1,22 +1,48 @@
+mod export;
 mod geo_traits;
 mod geo_types;
+mod gpx;
+mod i18n;
+mod kml;
+mod persist;
 pub mod view_types;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
 use std::sync::LazyLock;
 
-use chrono::prelude::*;
+use chrono::{prelude::*, TimeDelta};
 use compact_str::{format_compact, CompactString, ToCompactString};
 use crux_core::{render::Render, App};
-use crux_geolocation::{GeoInfo, GeoOptions, GeoResult, Geolocation};
+use crux_geolocation::{
+    CancelHandle, GeoError, GeoInfo, GeoOptions, GeoResult, Geolocation, RetryPolicy,
+};
+use crux_http::Http;
 use crux_kv::{error::KeyValueError, KeyValue};
 use crux_time::{Time, TimeResponse};
-use geo_types::{rtree_point, RecordedWay, SavedPos};
-use jord::spherical::Sphere;
+use geo_types::{
+    are_antipodal, centroid, clusters, in_box, rtree_box, rtree_point, saved_within_radius,
+    PosWithTimestamp, RecordedWay, SavedPos, WayMeta, WaySegment, MIN_MOVING_SPEED,
+};
+use i18n::Text;
+use jord::{spherical::Sphere, Angle, LatLong, Length, Speed};
+use persist::{
+    decode, encode, migrate_recorded_way, migrate_recorded_ways, migrate_saved_positions,
+    migrate_unchanged,
+};
 use rstar::RTree;
 use serde::{Deserialize, Serialize};
-use view_types::ViewModel;
+use view_types::{
+    format_coords, format_distance_and_bearing, format_long_length, Precision, ViewModel,
+    ViewRecordedWay, ViewSavedPos, ALL_POSITIONS_NAME, ARRIVED_DISTANCE, MAX_PRECITION,
+};
 
-use crate::FileDownload;
+use crate::{
+    FileDownload, FileUpload, Notify, NotifyKind, ReverseGeocode, ReverseGeocodeResponse,
+    ScreenWake,
+};
+
+pub use i18n::Lang;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum Event {
@@ -26,8 +52,57 @@ pub enum Event {
     /// Got a position update.
     #[serde(skip)]
     GeolocationUpdate(GeoResult<GeoInfo>),
-    /// Stop geolocation services.
+    /// Set the odometer to zero.
+    ResetOdometer,
+    /// Set the factor, between 0 and 1, of the exponential moving average of the speed and the
+    /// heading. A higher factor means less smoothing.
+    SetSmoothingFactor(f64),
+    /// Set the window before the current time in which the recorded positions are counted in
+    /// `ViewModel::gps_status`. It must be at least one second.
+    SetRateWindow(std::time::Duration),
+    /// Stop geolocation services. What happens to the recording since app start is decided by
+    /// `Model::stop_behavior`.
     StopGeolocation,
+    /// Set what `Event::StopGeolocation` does with the recording since app start.
+    SetStopBehavior(StopBehavior),
+    /// The app has been put in the background. Geolocation is paused to save battery, without
+    /// affecting the recording since app start.
+    AppSuspended,
+    /// The app is in the foreground again. Geolocation is resumed if it was paused by
+    /// `Event::AppSuspended`.
+    AppResumed,
+    /// Set the options used when watching the position. Restarts the watch if it is active.
+    SetGeoOptions(GeoOptions),
+    /// Turn high accuracy on or off, which uses more or less battery. Restarts the watch if it
+    /// is active. The choice is persisted.
+    SetHighAccuracy(bool),
+    /// Feed the positions of a recorded way to the app as if they came from the GPS, for testing
+    /// and demos. The positions are sent `speed_factor` times faster than they were recorded. It
+    /// must be at least 0.01. Geolocation must be stopped, and starting it stops the replay.
+    ReplayWay {
+        name: CompactString,
+        speed_factor: f64,
+    },
+    /// Send the next position of the replay with some id.
+    #[serde(skip)]
+    ReplayNext(u64),
+    /// Get the current position once, without watching it. It is handled like any position
+    /// update when it arrives.
+    GetCurrentPosition,
+    /// Stop waiting for the position requested with `Event::GetCurrentPosition`, for instance
+    /// because the user closed the dialog which needed it.
+    CancelCurrentPosition,
+    /// The response to `Event::GetCurrentPosition`.
+    #[serde(skip)]
+    CurrentPosition(GeoResult<GeoInfo>),
+    /// Stop the replay started with `Event::ReplayWay`.
+    StopReplay,
+
+    // Power Saving
+    /// The battery status as reported by the shell. `level` is between 0.0 and 1.0.
+    BatteryStatus { level: f64, charging: bool },
+    /// Keep high accuracy even when the battery is low.
+    SetKeepHighAccuracy(bool),
 
     // Persistant Data
     /// Load Persistant Data.
@@ -38,24 +113,239 @@ pub enum Event {
         res: Result<Option<Vec<u8>>, KeyValueError>,
         key: CompactString,
     },
-    /// Download the data
+    /// The saved positions have been written to persistant storage. `expected` is what the app
+    /// wrote before, and `res` contains what was actually overwritten.
+    #[serde(skip)]
+    SavedPositionsWritten {
+        expected: Option<Vec<u8>>,
+        res: Result<Option<Vec<u8>>, KeyValueError>,
+    },
+    /// Write the saved positions to persistant storage if they have changed since they were last
+    /// written. Sent `SAVED_POSITIONS_DEBOUNCE` after the first unwritten change.
+    #[serde(skip)]
+    FlushSavedPositions,
+    /// Try to load data which couldn't be decoded from its backup. The argument is the key of the
+    /// data, without the `.corrupt` suffix.
+    RecoverFromBackup(CompactString),
+    /// The backup requested by `RecoverFromBackup`.
+    #[serde(skip)]
+    SetBackupData {
+        res: Result<Option<Vec<u8>>, KeyValueError>,
+        key: CompactString,
+    },
+    /// Download all saved positions and recorded ways as JSON, which can be imported with
+    /// `ImportData`.
     DownloadData,
+    /// Import saved positions and recorded ways from JSON downloaded with `DownloadData`.
+    ImportData(Vec<u8>),
+    /// Ask the user for a file downloaded with `DownloadData` and import it with `ImportData`.
+    UploadData,
+    /// Download all saved positions and recorded ways as a KML file.
+    ExportKml,
+    /// Import waypoints as saved positions and tracks as recorded ways from a GPX file.
+    ImportGpx(Vec<u8>),
+    /// Ask the user for a GPX file and import it with `ImportGpx`.
+    UploadGpx,
+    /// Report how much persistant storage the app uses in `Model::msg`.
+    ReportStorageUsage,
+    /// The keys in persistant storage, as a response to `ReportStorageUsage`.
+    #[serde(skip)]
+    StorageKeys(Result<(Vec<String>, u64), KeyValueError>),
+    /// The size of a value in persistant storage, as a response to `ReportStorageUsage`.
+    #[serde(skip)]
+    StorageKeySize {
+        key: CompactString,
+        res: Result<Option<Vec<u8>>, KeyValueError>,
+    },
+    /// Add many recorded ways at once. They are rendered and saved once rather than once per way.
+    /// Names which are already in use get a suffix like " (imported)".
+    #[serde(skip)]
+    ImportWays(Vec<(CompactString, RecordedWay)>),
 
     // Saved Positions
-    /// Save the current position with a name.
+    /// Save the current position with a name. If the name is empty, the name suggested by
+    /// `SuggestPosName` is used.
     SaveCurrPos(CompactString),
+    /// Look up the name of the place at the current position to suggest as the name when saving
+    /// it.
+    SuggestPosName,
+    /// The name found by `SuggestPosName`.
+    #[serde(skip)]
+    SetSuggestedName(ReverseGeocodeResponse),
+    /// Save the average of the positions recorded within `window` before the current time with a
+    /// name. More accurate positions weigh more. If there are no such positions, the current
+    /// position is saved.
+    SaveAveragedPos {
+        name: CompactString,
+        window: std::time::Duration,
+    },
+    /// Save every nth node of a way as a saved position named like "<way> #k", starting with the
+    /// first node. Nodes whose names are already in use are skipped.
+    SaveWaypointsFromWay {
+        way_name: CompactString,
+        every_n: usize,
+    },
     /// Delete a saved position by its name.
     DelSavedPos(CompactString),
+    /// Rename a saved position. The position and timestamp are kept.
+    RenameSavedPos {
+        old: CompactString,
+        new: CompactString,
+    },
+    /// Move a saved position to new coordinates and altitude. The name and timestamp are kept.
+    EditSavedPos {
+        name: CompactString,
+        coords: LatLong,
+        altitude: Option<Length>,
+    },
+    /// Remove saved positions which are within a radius from an older saved position.
+    DedupSavedPositions(Length),
+    /// Set the maximum number of saved positions. When a position is saved past the limit, the
+    /// least recently viewed positions are removed. `None` means no limit.
+    SetMaxSavedPositions(Option<usize>),
+    /// Make a saved position a geofence, so that a message is shown when the current position
+    /// enters or leaves the radius around it.
+    AddGeofence { name: CompactString, radius: Length },
+    /// Stop showing messages for a geofence.
+    RemoveGeofence(CompactString),
+    /// Show the distance and the estimated time of arrival to a saved position. If no target is
+    /// selected, the nearest saved position is used.
+    SelectTarget(CompactString),
+    /// Preview the great-circle route from the current position to a saved position in
+    /// `ViewModel::route_preview`, for instance to draw it on a map. An empty name stops the
+    /// preview.
+    PreviewRouteTo(CompactString),
     /// View the n nearest saved positions. To hide all, set this to 0.
     ViewNSavedPositions(usize),
+    /// Tell the name, distance and bearing of the nearest saved position in a message.
+    NearestSavedPosition,
+    /// Tell the distance and the initial bearing from one saved position to another in a
+    /// message.
+    MeasureBetween { a: CompactString, b: CompactString },
+    /// Tell the centroid of all saved positions in a message, for instance to center a map.
+    ShowSavedCentroid,
+    /// View all saved positions within a rectangle of latitudes and longitudes, for instance a map
+    /// viewport. If `min` has a larger longitude than `max`, the rectangle crosses the
+    /// antimeridian.
+    ViewSavedPositionsInBox { min: LatLong, max: LatLong },
+    /// View all saved positions within a distance along the surface from a point, nearest
+    /// first.
+    ViewSavedWithinRadius { center: LatLong, radius: Length },
+    /// View the saved positions grouped into clusters in cells of about `cell_size`, for instance
+    /// to show the number of positions in an area on a zoomed-out map. A size of zero stops
+    /// clustering.
+    ClusterSavedPositions { cell_size: Length },
 
     // Recorded Ways
     /// Save the way since the app started.
     SaveAllPositions(CompactString),
+    /// Save the part of the way since the app started which was recorded between `start` and
+    /// `end` (inclusive).
+    SaveWayRange {
+        name: CompactString,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+    /// Discard all positions recorded since the app started. The recording restarts with the
+    /// next position.
+    ClearCurrentRecording,
+    /// Start a new track, replacing the way since app start with an empty way. Unlike
+    /// `ClearCurrentRecording`, the empty way is shown right away.
+    StartNewTrack,
     /// Delete a recorded way.
     DelRecordedWay(CompactString),
+    /// Join two recorded ways into a new way named `into`. The nodes of both ways are always
+    /// sorted by timestamp, so the order of `first` and `second` only decides which node is kept
+    /// of nodes with the same timestamp, see `RecordedWay::merge`. If `delete_sources` is set,
+    /// `first` and `second` are deleted.
+    MergeWays {
+        first: CompactString,
+        second: CompactString,
+        into: CompactString,
+        delete_sources: bool,
+    },
+    /// Download a recorded way as a GPX file.
+    ExportWayGpx(CompactString),
+    /// Download a recorded way as JSON, together with its length, duration and number of nodes.
+    ExportWayJson(CompactString),
+    /// Split a recorded way into the ways "<name> (1)" with the nodes before `at_index` and
+    /// "<name> (2)" with the rest, and delete it. Both parts must get at least one node.
+    SplitWay {
+        name: CompactString,
+        at_index: usize,
+    },
+    /// Like `SplitWay`, but the second part starts with the first node recorded at or after `at`.
+    SplitWayAt {
+        name: CompactString,
+        at: DateTime<Utc>,
+    },
+    /// Set the color and the note of a saved way. `None` removes them.
+    SetWayMeta {
+        name: CompactString,
+        color: Option<CompactString>,
+        note: Option<CompactString>,
+    },
+    /// Upload a recorded way as a GPX file to the endpoint set with `SetSyncEndpoint`.
+    UploadWay(CompactString),
+    /// The result of `UploadWay`.
+    #[serde(skip)]
+    WayUploaded {
+        name: CompactString,
+        res: Result<(), SyncError>,
+    },
+    /// Set the URL which ways are uploaded to with `UploadWay`. An empty URL disables uploads.
+    /// The URL is saved persistently.
+    SetSyncEndpoint(CompactString),
     /// View n recorded ways.
     ViewNRecordedWays(usize),
+    /// Set the order of the recorded ways in `ViewModel::recorded_ways`. `None` shows the nearest
+    /// ways first.
+    SetWaySort(Option<WaySort>),
+    /// View the nodes and the speed of every segment of a way in `ViewModel::way_detail`, for
+    /// instance to draw it on a map. The name may also be a named recording or the way since app
+    /// start. `None` stops viewing it.
+    ViewWayDetail(Option<CompactString>),
+    /// Start a new named recording which runs alongside the recording since app start.
+    StartNamedRecording(CompactString),
+    /// Stop a named recording and save it as a recorded way with the same name.
+    StopNamedRecording(CompactString),
+    /// Recompute the cached statistics of a recorded way and save it again.
+    RepairRecordedWay(CompactString),
+    /// Delete the node at a certain index from a recorded way.
+    DeleteWayNode { name: CompactString, index: usize },
+    /// Tell the distance from the current position to a recorded way.
+    DistanceToWay(CompactString),
+    /// Show the distance along a recorded way from the point on it which is closest to the
+    /// current position to its end, or to its start if `reverse` is set because the way is
+    /// followed backwards.
+    RemainingAlongWay { name: CompactString, reverse: bool },
+    /// Simplify a recorded way by removing nodes which are closer than `tolerance` to the line
+    /// between their neighbours, and save it again.
+    SimplifyWay {
+        name: CompactString,
+        tolerance: Length,
+    },
+    /// Reduce a recorded way to about `target` evenly spaced nodes, for instance to render it
+    /// within a fixed budget, and save it again.
+    DownsampleWay { name: CompactString, target: usize },
+    /// Limit the size of the recording since app start. `None` means no limit.
+    SetLiveRecordingLimit(Option<LiveRecordingLimit>),
+    /// Save the recording since app start to persistant storage at this interval, so that it can
+    /// be recovered with `RecoverLiveRecording` if the app crashes. It must be longer than zero.
+    /// `None` stops saving it and deletes the saved recording. The interval is itself saved.
+    SetLiveAutosave(Option<std::time::Duration>),
+    /// Add the recording saved by `SetLiveAutosave` to the recording since app start. This is
+    /// done by `LoadPersistantData`.
+    RecoverLiveRecording,
+    /// The recording requested by `RecoverLiveRecording`.
+    #[serde(skip)]
+    LiveRecordingRecovered(Result<Option<Vec<u8>>, KeyValueError>),
+    /// Forget the current position when no position has been received for this number of
+    /// seconds, so that distances aren't shown relative to an old position. 0 means never.
+    SetMaxFixAge(u32),
+    /// Tell the user that the GPS has trouble when the position has timed out more than this
+    /// number of times in a row.
+    SetMaxConsecutiveTimeouts(u32),
 
     // Time
     /// Tell that `Model::curr_time` should be updated.
@@ -65,33 +355,509 @@ pub enum Event {
     SetCurrTime(crux_time::Instant),
 
     // Miscellaneous
+    /// Set the units to show distances and speeds in. The choice is saved persistently.
+    SetUnitSystem(UnitSystem),
+    /// Set the language of messages. The choice is saved persistently.
+    SetLanguage(Lang),
+    /// Set the unit to show speeds in. `None` uses the unit of the `UnitSystem`.
+    SetSpeedUnit(Option<SpeedUnit>),
+    /// Set whether bearings to saved positions are shown relative to north or to the heading.
+    SetBearingMode(BearingMode),
+    /// Set the number of decimals of the coordinates and of other numbers, like altitudes and
+    /// speeds, of the current position and saved positions. At most `MAX_PRECITION` decimals
+    /// are shown.
+    SetPrecision { coord: usize, other: usize },
     /// A message which should be displayed to the user.
     #[serde(skip)]
     Msg(CompactString),
+    /// An error which should be displayed to the user in their language.
+    #[serde(skip)]
+    Error(AppError),
     #[serde(skip)]
     None,
 }
 
+/// A limit of how much to keep in the recording since app start. Older positions are dropped when
+/// the limit is exceeded.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LiveRecordingLimit {
+    /// Keep at most this number of nodes.
+    Nodes(usize),
+    /// Keep only the nodes from this number of seconds before the latest node.
+    Seconds(u32),
+}
+
+/// What to do with the recording since app start when geolocation is stopped.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StopBehavior {
+    /// Keep the recording so that it continues when geolocation is started again.
+    #[default]
+    Pause,
+    /// End the recording so that a new one begins with the next position. If `auto_save` is set,
+    /// the recording is saved as a way named after its start time.
+    Finalize { auto_save: bool },
+}
+
+/// The units to show distances and speeds in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnitSystem {
+    /// Meters and meters per second.
+    #[default]
+    Metric,
+    /// Feet, miles and miles per hour.
+    Imperial,
+}
+
+/// The unit to show speeds in, independently of the `UnitSystem` of lengths.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpeedUnit {
+    MetersPerSecond,
+    KmPerHour,
+    Mph,
+    Knots,
+}
+
+impl From<UnitSystem> for SpeedUnit {
+    /// The speed unit of a unit system.
+    fn from(units: UnitSystem) -> Self {
+        match units {
+            UnitSystem::Metric => Self::MetersPerSecond,
+            UnitSystem::Imperial => Self::Mph,
+        }
+    }
+}
+
+/// An order of recorded ways.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaySort {
+    ByName,
+    /// The oldest way first.
+    ByStartTimeAsc,
+    /// The newest way first.
+    ByStartTimeDesc,
+    /// The longest way first.
+    ByLengthDesc,
+}
+
+/// How to show the bearings to saved positions.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BearingMode {
+    /// Clockwise from north.
+    #[default]
+    AbsoluteNorth,
+    /// Clockwise from the current heading, so that straight ahead is 0°. Bearings are absolute
+    /// when the heading is unknown, for instance when standing still.
+    RelativeToHeading,
+}
+
+/// The default for `Event::SetMaxFixAge`, which is to never forget the current position.
+const DEFAULT_MAX_FIX_AGE: u32 = 0;
+
+/// The default for `Event::SetMaxConsecutiveTimeouts`.
+const DEFAULT_MAX_CONSECUTIVE_TIMEOUTS: u32 = 3;
+
 /// The planet we want to navigate on.
 pub const PLANET: Sphere = Sphere::EARTH;
 
 static UPDATE_CURR_TIME_INTERVAL: LazyLock<crux_time::Duration> =
     LazyLock::new(|| crux_time::Duration::from_secs(1).unwrap());
+/// Changes to the saved positions are written to persistant storage this long after the first
+/// change, so that a burst of changes, like an import, results in a single write.
+static SAVED_POSITIONS_DEBOUNCE: LazyLock<crux_time::Duration> =
+    LazyLock::new(|| crux_time::Duration::from_millis(500).unwrap());
 const GEOLOCATION_OPTIONS: GeoOptions = GeoOptions {
     maximum_age: 0,
     timeout: Some(27000),
     enable_high_accuracy: true,
+    min_distance: None,
+    max_accuracy: None,
+    keep_unknown_accuracy: true,
+    retry: RetryPolicy {
+        base_delay: 250,
+        factor: 2.0,
+        max_delay: 10000,
+        max_attempts: None,
+    },
 };
+/// The odometer is only increased when the position has moved at least this many metres, or the
+/// accuracy of the position if it is worse, so that jitter while standing still isn't counted.
+const ODOMETER_MIN_STEP: f64 = 5.0;
+/// The default window in which recorded positions are counted, see `Event::SetRateWindow`.
+const DEFAULT_RATE_WINDOW_SECS: i64 = 60;
+/// The default factor for smoothing the speed and the heading.
+const DEFAULT_SMOOTHING_FACTOR: f64 = 0.3;
+/// The smallest speed factor of `Event::ReplayWay`, so that a replay doesn't take forever.
+const MIN_REPLAY_SPEED_FACTOR: f64 = 0.01;
+/// Below this battery level, high accuracy is disabled unless `Model::keep_high_accuracy` is set.
+const LOW_BATTERY_LEVEL: f64 = 0.2;
 
 /// Key when saving saved positions in persistant storage.
 const SAVED_POSITIONS_KEY: &str = "saved_positions";
-/// Key when saving ways.
+/// Key where all ways were saved before each way got its own key. Only used for migration.
 const RECORDED_WAYS_KEY: &str = "recorded_ways";
+/// Key when saving the names of all saved ways.
+const RECORDED_WAYS_INDEX_KEY: &str = "recorded_ways_index";
+/// Prefix for the key of each saved way. The name of the way follows the prefix.
+const RECORDED_WAY_KEY_PREFIX: &str = "recorded_ways/";
+/// Key when saving the unit system.
+const UNIT_SYSTEM_KEY: &str = "unit_system";
+/// Key when saving the language.
+const LANGUAGE_KEY: &str = "language";
+/// Key when saving whether high accuracy is enabled.
+const HIGH_ACCURACY_KEY: &str = "high_accuracy";
+/// Key when saving the URL which ways are uploaded to.
+const SYNC_ENDPOINT_KEY: &str = "sync_endpoint";
+/// Key where the recording since app start is saved by `Event::SetLiveAutosave`.
+const LIVE_RECORDING_KEY: &str = "live_recording";
+/// Key when saving the interval set with `Event::SetLiveAutosave`.
+const LIVE_AUTOSAVE_KEY: &str = "live_autosave";
+/// Suffix for the key of a backup of data which couldn't be decoded.
+const CORRUPT_BACKUP_SUFFIX: &str = ".corrupt";
+/// The number of nodes which may be added to the way since app start before its view is rebuilt
+/// rather than updated with `ViewRecordedWay::update_last`.
+const VIEW_REBUILD_INTERVAL: usize = 60;
+/// Browsers allow about this many bytes in local storage.
+const STORAGE_BUDGET: usize = 5 * 1024 * 1024;
+
+/// An ongoing storage usage report, see `Event::ReportStorageUsage`.
+#[derive(Debug, Default)]
+struct StorageUsage {
+    /// Keys whose sizes are not yet known.
+    pending: BTreeSet<CompactString>,
+    /// The number of keys whose sizes are known.
+    keys: usize,
+    /// The total number of bytes of the keys and values whose sizes are known, as they are
+    /// stored by the web shell, see `StorageUsage::add`.
+    bytes: usize,
+}
+
+impl StorageUsage {
+    fn new(keys: impl IntoIterator<Item = impl Into<CompactString>>) -> Self {
+        Self {
+            pending: keys.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Add the size of the value at a key. Unknown keys are ignored.
+    ///
+    /// The web shell stores values base64 encoded in local storage, where browsers count every
+    /// character of the keys and values as two bytes of UTF-16, so a value takes about 2.7 times
+    /// its own size.
+    fn add(&mut self, key: &str, value_size: usize) {
+        if self.pending.remove(key) {
+            self.keys += 1;
+            self.bytes += 2 * (key.encode_utf16().count() + value_size.div_ceil(3) * 4);
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// A message for the user about the total usage.
+    fn msg(&self, lang: Lang) -> CompactString {
+        Text::StorageUsage {
+            keys: self.keys,
+            bytes: self.bytes,
+            budget: STORAGE_BUDGET,
+        }
+        .translate(lang)
+    }
+}
+
+/// Whether a key in persistant storage is used by this app, including backups.
+fn is_app_key(key: &str) -> bool {
+    is_data_key(key)
+        || key == LIVE_RECORDING_KEY
+        || key
+            .strip_suffix(CORRUPT_BACKUP_SUFFIX)
+            .is_some_and(is_data_key)
+}
+
+/// Whether a key in persistant storage is used by this app for data which can be decoded by
+/// `GeoApp::decode_data`.
+fn is_data_key(key: &str) -> bool {
+    [
+        SAVED_POSITIONS_KEY,
+        RECORDED_WAYS_KEY,
+        RECORDED_WAYS_INDEX_KEY,
+        UNIT_SYSTEM_KEY,
+        LANGUAGE_KEY,
+        HIGH_ACCURACY_KEY,
+        SYNC_ENDPOINT_KEY,
+        LIVE_AUTOSAVE_KEY,
+    ]
+    .contains(&key)
+        || key.starts_with(RECORDED_WAY_KEY_PREFIX)
+}
+
+/// A view of `Model::all_positions` which is updated cheaply as nodes are added.
+struct AllPositionsView {
+    view: ViewRecordedWay,
+    /// The timestamp of the first node when the view was rebuilt.
+    start: Option<DateTime<Utc>>,
+    /// The number of nodes when the view was rebuilt.
+    nodes: usize,
+    /// The units when the view was rebuilt.
+    units: UnitSystem,
+    speed_unit: SpeedUnit,
+}
+
+/// Why uploading a way with `Event::UploadWay` failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncError {
+    /// The server responded with a status code which isn't 2xx.
+    Status(u16),
+    /// The server couldn't be reached, or some other error before a response was received.
+    Network(CompactString),
+}
+
+impl SyncError {
+    /// Interpret the response to an upload.
+    fn from_response(res: crux_http::Result<crux_http::Response<Vec<u8>>>) -> Result<(), Self> {
+        match res {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) => Err(Self::Status(response.status().into())),
+            Err(crux_http::HttpError::Http { code, .. }) => Err(Self::Status(code.into())),
+            Err(e) => Err(Self::Network(e.to_compact_string())),
+        }
+    }
+}
+
+/// An error which is shown to the user in `Model::msg`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    /// There is already a saved position with this name.
+    PosNameExists(CompactString),
+    /// There is already a recorded way with this name.
+    WayNameExists(CompactString),
+    /// There is no saved position with this name.
+    PosNotFound(CompactString),
+    /// There is no recorded way with this name.
+    WayNotFound(CompactString),
+    /// The current position is not known.
+    NoCurrentPosition,
+    /// No positions have been recorded since the app was started.
+    NoRecordedPositions,
+    /// The start of a time range is after its end.
+    InvertedTimeRange,
+    /// No positions were recorded within a time range.
+    EmptyTimeRange,
+    /// A way can only be split at an index between 1 and `max`.
+    InvalidSplitIndex { name: CompactString, max: usize },
+    /// A way doesn't have a node at an index since it only has `nodes` nodes.
+    InvalidNodeIndex { name: CompactString, nodes: usize },
+    /// No sync endpoint has been set with `Event::SetSyncEndpoint`.
+    NoSyncEndpoint,
+    /// The sync server responded to the upload of a way with a status which isn't 2xx.
+    UploadRejected { name: CompactString, status: u16 },
+    /// The sync server couldn't be reached to upload a way.
+    UploadFailed {
+        name: CompactString,
+        error: CompactString,
+    },
+    /// A way can't be replayed while geolocation is active.
+    ReplayWhileGeolocating,
+    /// The speed factor of a replay is smaller than `MIN_REPLAY_SPEED_FACTOR` or not finite.
+    InvalidSpeedFactor(f64),
+    /// The window of `Event::SetRateWindow` is shorter than a second.
+    RateWindowTooShort,
+    /// The interval of `Event::SetLiveAutosave` is zero.
+    ZeroAutosaveInterval,
+    /// A key which isn't used by this app was given to `Event::RecoverFromBackup`.
+    UnknownKey(CompactString),
+    /// There is no backup of the data under a key.
+    NoBackup(CompactString),
+    /// A file given to `Event::ImportData` couldn't be parsed.
+    InvalidDataFile(CompactString),
+    /// A file given to `Event::ImportGpx` couldn't be parsed.
+    InvalidGpxFile(CompactString),
+    /// There is no geofence around the saved position with this name.
+    NotAGeofence(CompactString),
+    /// A way doesn't have any nodes.
+    EmptyWay(CompactString),
+    /// The factor of `Event::SetSmoothingFactor` isn't within (0, 1].
+    InvalidSmoothingFactor(f64),
+    /// Every nth node of a way can't be saved with n = 0.
+    ZeroNodeInterval,
+    /// The permission to access the position was denied.
+    PermissionDenied,
+    /// Data from persistant storage couldn't be decoded.
+    Serialization {
+        what: CompactString,
+        error: CompactString,
+    },
+    /// An operation on persistant storage failed.
+    Storage {
+        action: StorageAction,
+        error: CompactString,
+    },
+}
+
+/// An operation on persistant storage, see `AppError::Storage`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageAction {
+    SaveSavedPositions,
+    SaveWay,
+    DeleteWay,
+    SaveWayIndex,
+    DeleteOldSavedWays,
+    SaveUnitSystem,
+    SaveLanguage,
+    SaveAccuracy,
+    SaveSyncEndpoint,
+    SaveLiveAutosave,
+    SaveLiveRecording,
+    DeleteLiveRecording,
+    RecoverLiveRecording,
+    /// List the keys in storage, see `Event::ReportStorageUsage`.
+    ListKeys,
+    /// Read the data under a key.
+    Retrieve(CompactString),
+    /// Read the backup of the data under a key, see `Event::RecoverFromBackup`.
+    RetrieveBackup(CompactString),
+    /// Save a backup of data which couldn't be decoded.
+    BackUpUndecodable,
+    DeleteBackup,
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Text::Error(self).translate(Lang::English))
+    }
+}
+
+/// A radius around a saved position, see `Event::AddGeofence`.
+#[derive(Debug, Clone, Copy)]
+struct Geofence {
+    radius: Length,
+    /// Whether the current position was inside the radius at the last position update. `None`
+    /// before the first position update after the fence was added or the position was moved.
+    inside: Option<bool>,
+}
+
+/// A recorded way which is fed back to the app, see `Event::ReplayWay`.
+#[derive(Debug, Clone)]
+struct Replay {
+    name: CompactString,
+    nodes: Vec<PosWithTimestamp>,
+    /// The index of the next node to send.
+    next: usize,
+    speed_factor: f64,
+    /// The timestamp of the first replayed position.
+    start: DateTime<Utc>,
+}
+
+impl Replay {
+    /// The timestamp of a replayed position which was recorded at `timestamp`. Timestamps too
+    /// far in the future to be represented are clamped.
+    fn replayed_time(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let elapsed = (timestamp - self.nodes[0].timestamp).num_milliseconds() as f64;
+        TimeDelta::try_milliseconds((elapsed / self.speed_factor).round() as i64)
+            .and_then(|x| self.start.checked_add_signed(x))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+/// The state of saving the recording since app start, see `Event::SetLiveAutosave`.
+#[derive(Debug, Default, Clone, Copy)]
+struct LiveAutosave {
+    /// How often the recording is saved. `None` if it isn't.
+    interval: Option<TimeDelta>,
+    /// When the recording was last saved.
+    saved_at: Option<DateTime<Utc>>,
+    /// The timestamp of the last position which has been saved, either under
+    /// `LIVE_RECORDING_KEY` or as a recorded way, so that an unchanged recording isn't saved
+    /// again.
+    saved_until: Option<DateTime<Utc>>,
+    /// Whether there may be a recording under `LIVE_RECORDING_KEY`.
+    stored: bool,
+}
+
+/// Exponential moving averages of the speed and the heading.
+#[derive(Debug, Default, Clone, Copy)]
+struct Smoothed {
+    /// The speed in metres per second.
+    speed: Option<f64>,
+    /// The sine and cosine of the heading. The heading is averaged on the unit circle so that
+    /// headings like 359° and 1° average to 0° and not 180°.
+    heading: Option<(f64, f64)>,
+}
+
+impl Smoothed {
+    /// Add a new speed and heading, weighted by `factor`. Missing values are ignored.
+    fn add(&mut self, factor: f64, speed: Option<Speed>, heading: Option<Angle>) {
+        let ema = |prev: Option<f64>, x: f64| prev.map_or(x, |prev| prev + factor * (x - prev));
+        if let Some(speed) = speed {
+            self.speed = Some(ema(self.speed, speed.as_metres_per_second()));
+        }
+        if let Some(heading) = heading {
+            let (sin, cos) = heading.as_radians().sin_cos();
+            self.heading = Some((
+                ema(self.heading.map(|x| x.0), sin),
+                ema(self.heading.map(|x| x.1), cos),
+            ));
+        }
+    }
+
+    fn speed(&self) -> Option<Speed> {
+        self.speed.map(Speed::from_metres_per_second)
+    }
+
+    /// The smoothed heading, or `None` if the headings cancel each other out.
+    fn heading(&self) -> Option<Angle> {
+        let (sin, cos) = self.heading?;
+        (sin.hypot(cos) > 1e-9).then(|| Angle::from_radians(sin.atan2(cos)))
+    }
+}
+
+/// The direction of travel at a position, or `None` if it is unknown or not a finite angle, or if
+/// the speed is known to be too low to be considered moving.
+fn travel_bearing(pos: &GeoInfo) -> Option<Angle> {
+    let bearing = pos.bearing.filter(|x| x.as_degrees().is_finite())?;
+    let moving = pos
+        .volocity
+        .is_none_or(|x| x.as_metres_per_second() >= MIN_MOVING_SPEED);
+    moving.then_some(bearing)
+}
 
 #[derive(Default)]
 pub struct Model {
     /// The most recently received position.
     curr_pos: Option<GeoResult<GeoInfo>>,
+    /// Whether the position is being watched.
+    geolocation_active: bool,
+    /// Whether the position was watched when the app was suspended, so that it should be watched
+    /// again when the app is resumed.
+    was_watching: bool,
+    /// Whether the shell has been asked to keep the screen awake.
+    screen_wake: bool,
+    /// The way being replayed with `Event::ReplayWay`.
+    replay: Option<Replay>,
+    /// The id of the latest replay, so that the timers of a stopped replay are ignored.
+    replay_id: u64,
+    /// A pending request from `Event::GetCurrentPosition`.
+    current_position_request: Option<CancelHandle>,
+    /// What to do with `all_positions` when geolocation is stopped.
+    stop_behavior: StopBehavior,
+    /// Options for watching the position. `None` means `GEOLOCATION_OPTIONS`.
+    geo_options: Option<GeoOptions>,
+    /// Whether the battery is low and not charging.
+    battery_low: bool,
+    /// The total distance travelled since the app was started or the odometer was reset.
+    odometer: Length,
+    /// The position where the odometer was last increased.
+    odometer_pos: Option<LatLong>,
+    /// The factor for smoothing the speed and the heading. `None` means
+    /// `DEFAULT_SMOOTHING_FACTOR`.
+    smoothing_factor: Option<f64>,
+    /// Set with `Event::SetRateWindow`. `None` means `DEFAULT_RATE_WINDOW_SECS`.
+    rate_window: Option<TimeDelta>,
+    /// The smoothed speed and heading.
+    smoothed: Smoothed,
+    /// Keep high accuracy even when the battery is low.
+    keep_high_accuracy: bool,
 
     // Saved Positions
     /// An r-tree with all saved positions.
@@ -103,16 +869,104 @@ pub struct Model {
     view_n_saved_positions: usize,
     /// Saved positions to view. Must exist in `self.saved_positions`.
     view_saved_positions: Vec<SavedPos>,
+    /// The rectangle set with `Event::ViewSavedPositionsInBox`.
+    view_box: Option<(LatLong, LatLong)>,
+    /// Saved positions within `self.view_box`.
+    view_saved_positions_in_box: Vec<SavedPos>,
+    /// The center and radius set with `Event::ViewSavedWithinRadius`.
+    view_radius: Option<(LatLong, Length)>,
+    /// Saved positions within `self.view_radius`, nearest first.
+    view_saved_positions_within_radius: Vec<SavedPos>,
+    /// The cell size set with `Event::ClusterSavedPositions`.
+    cluster_cell_size: Option<Length>,
+    /// The centroids of clusters of saved positions and the number of positions in them, largest
+    /// first.
+    saved_position_clusters: Vec<(LatLong, usize)>,
+    /// A name for the current position found by `Event::SuggestPosName`.
+    suggested_name: Option<CompactString>,
+    /// Geofences by the names of their saved positions.
+    geofences: BTreeMap<CompactString, Geofence>,
+    /// The name of the saved position selected with `Event::SelectTarget`.
+    target: Option<CompactString>,
+    /// The target which the current position is within `ARRIVED_DISTANCE` of, so that the user
+    /// is notified only once on arrival.
+    arrived_at: Option<CompactString>,
+    /// The name of the saved position set with `Event::PreviewRouteTo`.
+    route_preview: Option<CompactString>,
+    /// The maximum number of saved positions, set with `Event::SetMaxSavedPositions`.
+    max_saved_positions: Option<usize>,
+    /// The value of `saved_positions_view_count` when each saved position was last viewed or
+    /// saved, by their names.
+    saved_positions_last_viewed: HashMap<CompactString, u64>,
+    /// The number of times saved positions have been viewed or saved.
+    saved_positions_view_count: u64,
 
     // Recorded Ways
-    /// All positions since the app was started.
+    /// All positions since the app was started. This is the default recording.
     all_positions: Option<RecordedWay>,
+    /// Named recordings which are currently active. They receive the same positions as
+    /// `all_positions` but can be started and stopped independently.
+    ///
+    /// `all_positions` is the default recording, named `ALL_POSITIONS_NAME`, but it isn't in this
+    /// map since only it is limited, autosaved, recovered after a crash and viewed incrementally.
+    named_recordings: BTreeMap<CompactString, RecordedWay>,
+    /// An optional limit for `all_positions`. Named recordings are not affected.
+    live_recording_limit: Option<LiveRecordingLimit>,
+    /// Saving of `all_positions` for recovery after a crash.
+    live_autosave: LiveAutosave,
+    /// Set with `Event::SetMaxFixAge`. `None` means `DEFAULT_MAX_FIX_AGE`.
+    max_fix_age: Option<u32>,
+    /// The number of `GeoError::Timeout` received since the last position or other error.
+    consecutive_timeouts: u32,
+    /// Set with `Event::SetMaxConsecutiveTimeouts`. `None` means
+    /// `DEFAULT_MAX_CONSECUTIVE_TIMEOUTS`.
+    max_consecutive_timeouts: Option<u32>,
+    /// Whether positions have been dropped from `all_positions` because of the limit.
+    live_recording_trimmed: bool,
+    /// A cached view of `all_positions`, which is updated at the end of every update.
+    all_positions_view: Option<AllPositionsView>,
     /// Saved ways and their names.
     recorded_ways: HashMap<CompactString, RecordedWay>,
+    /// Names of the ways saved in persistant storage. This may include ways which are not yet
+    /// loaded into `recorded_ways`.
+    recorded_ways_index: BTreeSet<CompactString>,
     /// The number of recorded ways the UI at most want to show.
     view_n_recorded_ways: usize,
+    /// Set with `Event::SetWaySort`.
+    way_sort: Option<WaySort>,
     /// Names of recorded ways to view.
     view_recorded_ways: Vec<CompactString>,
+    /// The segments of all recorded ways, to find the ways nearest to the current position. It is
+    /// built when needed and cleared when the recorded ways change.
+    recorded_way_segments: Option<RTree<WaySegment>>,
+    /// The name of the way set with `Event::ViewWayDetail`.
+    view_way_detail: Option<CompactString>,
+
+    /// The units to show distances and speeds in.
+    unit_system: UnitSystem,
+    /// The language of messages, set with `Event::SetLanguage`.
+    language: Lang,
+    /// Set with `Event::SetSpeedUnit`. `None` means the unit of `unit_system`.
+    speed_unit: Option<SpeedUnit>,
+    /// Set with `Event::SetBearingMode`.
+    bearing_mode: BearingMode,
+    /// The number of decimals to show, set with `Event::SetPrecision`.
+    precision: Precision,
+    /// The URL which ways are uploaded to with `Event::UploadWay`.
+    sync_endpoint: Option<CompactString>,
+    /// An ongoing storage usage report.
+    storage_usage: Option<StorageUsage>,
+    /// Keys in persistant storage with data which couldn't be decoded. The data has been copied
+    /// to a backup, but it is not overwritten during this session unless it is recovered.
+    corrupt_keys: BTreeSet<CompactString>,
+    /// The saved positions as they were last loaded from or written to persistant storage, to
+    /// detect if another instance of the app has changed them.
+    saved_positions_in_storage: Option<Vec<u8>>,
+    /// Whether the saved positions have changed since they were last written to persistant
+    /// storage.
+    saved_positions_dirty: bool,
+    /// Whether an `Event::FlushSavedPositions` is scheduled.
+    saved_positions_flush_pending: bool,
 
     /// A message that should be viewed to the user.
     msg: CompactString,
@@ -120,6 +974,76 @@ pub struct Model {
     /// The current time minus at most `UPDATE_CURR_TIME_AFTER`. Only availlable after the first
     /// call to `Event::StartGeolocation`.
     curr_time: Option<DateTime<Utc>>,
+
+    /// The fingerprint of the view model when a render was last requested.
+    rendered_fingerprint: Option<u64>,
+    /// The view model built at the end of the last update, so that `GeoApp::view` doesn't need
+    /// to build it again.
+    view: Option<ViewModel>,
+}
+
+impl Model {
+    /// Show an error to the user in the chosen language.
+    fn show_error(&mut self, e: &AppError) {
+        self.msg = Text::Error(e).translate(self.language);
+    }
+
+    /// The unit to show speeds in.
+    fn speed_unit(&self) -> SpeedUnit {
+        self.speed_unit.unwrap_or(self.unit_system.into())
+    }
+
+    /// The heading which bearings to saved positions are relative to, or `None` if they are
+    /// relative to north. See `BearingMode` and `travel_bearing`.
+    fn heading_reference(&self) -> Option<Angle> {
+        match self.bearing_mode {
+            BearingMode::AbsoluteNorth => None,
+            BearingMode::RelativeToHeading => {
+                travel_bearing(self.curr_pos.as_ref()?.as_ref().ok()?)
+            }
+        }
+    }
+
+    /// Whether high accuracy is disabled to save battery.
+    fn power_saver(&self) -> bool {
+        self.battery_low && !self.keep_high_accuracy
+    }
+
+    /// Find a saved way, a named recording or the way since app start by its name.
+    fn find_way(&self, name: &str) -> Option<&RecordedWay> {
+        if name == ALL_POSITIONS_NAME {
+            self.all_positions.as_ref()
+        } else {
+            self.named_recordings
+                .get(name)
+                .or_else(|| self.recorded_ways.get(name))
+        }
+    }
+
+    /// The window in which recorded positions are counted.
+    fn rate_window(&self) -> TimeDelta {
+        self.rate_window
+            .unwrap_or(TimeDelta::seconds(DEFAULT_RATE_WINDOW_SECS))
+    }
+
+    /// The selected target, or the nearest saved position if no target is selected.
+    fn target_pos(&self, curr_coords: LatLong) -> Option<&SavedPos> {
+        match &self.target {
+            Some(name) => self.saved_positions_names.get(name),
+            None => self
+                .saved_positions
+                .nearest_neighbor(&rtree_point(&curr_coords)),
+        }
+    }
+
+    /// The options to use when watching the position.
+    fn effective_geo_options(&self) -> GeoOptions {
+        let mut opts = self.geo_options.unwrap_or(GEOLOCATION_OPTIONS);
+        if self.power_saver() {
+            opts.enable_high_accuracy = false;
+        }
+        opts
+    }
 }
 
 #[cfg_attr(feature = "typegen", derive(crux_core::macros::Export))]
@@ -130,6 +1054,11 @@ pub struct Capabilities {
     time: Time<Event>,
     geolocation: Geolocation<Event>,
     file_download: FileDownload<Event>,
+    file_upload: FileUpload<Event>,
+    reverse_geocode: ReverseGeocode<Event>,
+    http: Http<Event>,
+    notify: Notify<Event>,
+    screen_wake: ScreenWake<Event>,
 }
 
 #[derive(Default)]
@@ -146,110 +1075,929 @@ impl App for GeoApp {
         match event {
             // Geolocation
             Event::StartGeolocation => {
+                model.replay = None;
+                model.geolocation_active = true;
                 caps.geolocation
-                    .watch_position(GEOLOCATION_OPTIONS, Event::GeolocationUpdate);
+                    .watch_position(model.effective_geo_options(), Event::GeolocationUpdate);
                 self.update(Event::UpdateCurrTime, model, caps);
             }
-            Event::StopGeolocation => caps.geolocation.clear_watch(),
+            Event::StopGeolocation => {
+                model.geolocation_active = false;
+                caps.geolocation.clear_watch();
+                if let StopBehavior::Finalize { auto_save } = model.stop_behavior {
+                    self.finalize_recording(model, caps, auto_save);
+                }
+            }
+            Event::SetStopBehavior(stop_behavior) => model.stop_behavior = stop_behavior,
+            Event::AppSuspended => {
+                // The app may be killed while it is suspended.
+                self.flush_saved_positions(model, caps);
+                if model.geolocation_active {
+                    model.geolocation_active = false;
+                    model.was_watching = true;
+                    caps.geolocation.clear_watch();
+                }
+            }
+            Event::AppResumed => {
+                if std::mem::take(&mut model.was_watching) && !model.geolocation_active {
+                    model.geolocation_active = true;
+                    caps.geolocation
+                        .watch_position(model.effective_geo_options(), Event::GeolocationUpdate);
+                }
+            }
+            Event::SetGeoOptions(opts) => {
+                model.geo_options = Some(opts);
+                self.restart_geolocation(model, caps);
+            }
+            Event::SetHighAccuracy(enable) => {
+                self.set_high_accuracy(model, caps, enable);
+                self.save_high_accuracy(model, caps);
+            }
+            Event::ReplayWay { name, speed_factor } => {
+                if let Err(e) = self.replay_way(model, caps, name, speed_factor) {
+                    model.show_error(&e);
+                }
+            }
+            Event::ReplayNext(id) => self.replay_next(model, caps, id),
+            Event::GetCurrentPosition => {
+                if model.current_position_request.is_none() {
+                    model.current_position_request = Some(caps.geolocation.get_current_position(
+                        model.effective_geo_options(),
+                        Event::CurrentPosition,
+                    ));
+                }
+            }
+            Event::CancelCurrentPosition => {
+                if let Some(handle) = model.current_position_request.take() {
+                    caps.geolocation.cancel_current_position(handle);
+                }
+            }
+            Event::CurrentPosition(geo_result) => {
+                model.current_position_request = None;
+                self.geolocation_update(model, caps, geo_result);
+            }
+            Event::StopReplay => {
+                if let Some(replay) = model.replay.take() {
+                    model.msg = format_compact!("The replay of {} has been stopped.", replay.name);
+                }
+            }
             Event::GeolocationUpdate(geo_result) => {
-                model.curr_pos = Some(geo_result.clone());
-                if let Ok(geo_info) = geo_result {
-                    if let Some(rec) = &mut model.all_positions {
-                        rec.add(&geo_info);
-                    } else {
-                        let mut rec = RecordedWay::new();
-                        rec.add(&geo_info);
-                        model.all_positions = Some(rec);
-                    }
+                self.geolocation_update(model, caps, geo_result);
+            }
+            Event::ResetOdometer => {
+                model.odometer = Length::ZERO;
+                model.odometer_pos = None;
+            }
+            Event::SetSmoothingFactor(factor) => {
+                if factor > 0.0 && factor <= 1.0 {
+                    model.smoothing_factor = Some(factor);
+                } else {
+                    model.show_error(&AppError::InvalidSmoothingFactor(factor));
+                }
+            }
+
+            Event::SetRateWindow(window) => {
+                if window < std::time::Duration::from_secs(1) {
+                    model.show_error(&AppError::RateWindowTooShort);
+                } else {
+                    model.rate_window =
+                        Some(TimeDelta::from_std(window).unwrap_or(TimeDelta::max_value()));
+                }
+            }
+
+            // Power Saving
+            Event::BatteryStatus { level, charging } => {
+                let power_saver = model.power_saver();
+                model.battery_low = level < LOW_BATTERY_LEVEL && !charging;
+                if model.power_saver() != power_saver {
+                    self.restart_geolocation(model, caps);
+                }
+            }
+            Event::SetKeepHighAccuracy(keep) => {
+                let power_saver = model.power_saver();
+                model.keep_high_accuracy = keep;
+                if model.power_saver() != power_saver {
+                    self.restart_geolocation(model, caps);
                 }
             }
 
             // Persistant Data
             Event::LoadPersistantData => {
                 self.load_persistant_data(caps, SAVED_POSITIONS_KEY);
+                self.load_persistant_data(caps, RECORDED_WAYS_INDEX_KEY);
                 self.load_persistant_data(caps, RECORDED_WAYS_KEY);
+                self.load_persistant_data(caps, UNIT_SYSTEM_KEY);
+                self.load_persistant_data(caps, LANGUAGE_KEY);
+                self.load_persistant_data(caps, HIGH_ACCURACY_KEY);
+                self.load_persistant_data(caps, SYNC_ENDPOINT_KEY);
+                self.load_persistant_data(caps, LIVE_AUTOSAVE_KEY);
+                caps.storage.get(
+                    LIVE_RECORDING_KEY.to_string(),
+                    Event::LiveRecordingRecovered,
+                );
+            }
+            Event::SavedPositionsWritten { expected, res } => match res {
+                Ok(previous) if is_overwrite(expected.as_deref(), previous.as_deref()) => {
+                    model.msg = "Warning: The saved positions had been changed elsewhere, for \
+                        instance in another tab, and those changes have been overwritten."
+                        .into();
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    model.show_error(&AppError::Storage {
+                        action: StorageAction::SaveSavedPositions,
+                        error: e.to_compact_string(),
+                    });
+                }
+            },
+            Event::FlushSavedPositions => {
+                model.saved_positions_flush_pending = false;
+                self.flush_saved_positions(model, caps);
             }
             Event::SetData { res, key } => {
                 if let Err(e) = self.set_data(model, caps, res, key) {
                     model.msg = e;
                 }
             }
+            Event::RecoverFromBackup(key) => {
+                if is_data_key(&key) {
+                    caps.storage
+                        .get(format!("{key}{CORRUPT_BACKUP_SUFFIX}"), move |res| {
+                            Event::SetBackupData { res, key }
+                        });
+                } else {
+                    model.show_error(&AppError::UnknownKey(key));
+                }
+            }
+            Event::SetBackupData { res, key } => match res {
+                Ok(Some(bytes)) => match self.decode_data(model, caps, &key, &bytes, true) {
+                    Ok(()) => {
+                        model.corrupt_keys.remove(&key);
+                        self.save_key(model, caps, &key);
+                        caps.storage
+                            .delete(format!("{key}{CORRUPT_BACKUP_SUFFIX}"), |res| {
+                                storage_error(res, StorageAction::DeleteBackup)
+                            });
+                        model.msg = format_compact!("{key} has been recovered from its backup.");
+                    }
+                    Err(e) => model.msg = format_compact!("Failed to recover {key}: {e}"),
+                },
+                Ok(None) => model.show_error(&AppError::NoBackup(key)),
+                Err(e) => model.show_error(&AppError::Storage {
+                    action: StorageAction::RetrieveBackup(key),
+                    error: e.to_compact_string(),
+                }),
+            },
             Event::DownloadData => {
-                let json = serde_json::json!({
-                    SAVED_POSITIONS_KEY: (&model.saved_positions, &model.saved_positions_names),
-                    RECORDED_WAYS_KEY: &model.recorded_ways,
-                });
                 caps.file_download.file_download(
-                    serde_json::to_vec(&json).unwrap(),
+                    export::to_json(
+                        model.curr_time,
+                        &model.saved_positions,
+                        &model.saved_positions_names,
+                        &model.recorded_ways,
+                    ),
                     Some("geosuper_data.json"),
                     Some("application/json"),
                 );
             }
+            Event::ExportKml => {
+                let mut saved_positions = model.saved_positions_names.values().collect::<Vec<_>>();
+                saved_positions.sort_by(|a, b| a.name.cmp(&b.name));
+                let mut ways = model
+                    .recorded_ways
+                    .iter()
+                    .map(|(name, rec)| (name.as_str(), rec.way.nodes()))
+                    .collect::<Vec<_>>();
+                ways.sort_by_key(|(name, _)| *name);
+                caps.file_download.file_download(
+                    kml::to_kml(saved_positions, ways).into_bytes(),
+                    Some("geosuper_data.kml"),
+                    Some("application/vnd.google-earth.kml+xml"),
+                );
+            }
+            Event::ImportData(bytes) => match export::parse_export(&bytes) {
+                Ok(data) => self.import_data(model, caps, data),
+                Err(e) => model.show_error(&AppError::InvalidDataFile(e)),
+            },
+            Event::UploadData => caps
+                .file_upload
+                .file_upload(Some(".json,application/json"), |file| {
+                    Event::ImportData(file.content)
+                }),
+            Event::UploadGpx => caps
+                .file_upload
+                .file_upload(Some(".gpx,application/gpx+xml"), |file| {
+                    Event::ImportGpx(file.content)
+                }),
+            Event::ReportStorageUsage => {
+                caps.storage.list_keys(String::new(), 0, Event::StorageKeys);
+            }
+            Event::StorageKeys(Ok((keys, _))) => {
+                let usage = StorageUsage::new(keys.into_iter().filter(|key| is_app_key(key)));
+                for key in usage.pending.iter().cloned() {
+                    caps.storage
+                        .get(key.to_string(), move |res| Event::StorageKeySize {
+                            key,
+                            res,
+                        });
+                }
+                if usage.is_done() {
+                    model.msg = usage.msg(model.language);
+                } else {
+                    model.storage_usage = Some(usage);
+                }
+            }
+            Event::StorageKeys(Err(e)) => model.show_error(&AppError::Storage {
+                action: StorageAction::ListKeys,
+                error: e.to_compact_string(),
+            }),
+            Event::StorageKeySize { key, res } => {
+                if let Some(usage) = &mut model.storage_usage {
+                    match res {
+                        Ok(value) => usage.add(&key, value.map_or(0, |x| x.len())),
+                        Err(e) => {
+                            let e = AppError::Storage {
+                                action: StorageAction::Retrieve(key.clone()),
+                                error: e.to_compact_string(),
+                            };
+                            model.msg = Text::Error(&e).translate(model.language);
+                            usage.add(&key, 0);
+                        }
+                    }
+                    if usage.is_done() {
+                        model.msg = usage.msg(model.language);
+                        model.storage_usage = None;
+                    }
+                }
+            }
+            Event::ImportWays(ways) => {
+                let n = self.import_ways(model, caps, ways);
+                model.msg = Text::ImportedWays(n).translate(model.language);
+            }
+            Event::ImportGpx(bytes) => match gpx::parse_gpx(&bytes) {
+                Ok(gpx) => self.import_gpx(model, caps, gpx),
+                Err(e) => model.show_error(&AppError::InvalidGpxFile(e)),
+            },
 
             // Saved Positions
-            Event::SaveCurrPos(name) => {
+            Event::SaveCurrPos(name) => match save_curr_pos(model, name) {
+                Ok(()) => {
+                    self.evict_saved_positions(model);
+                    // Update `model.view_saved_positions`.
+                    self.view_saved_positions(model, caps);
+                    self.save_saved_positions(model, caps);
+                }
+                Err(e) => model.show_error(&e),
+            },
+            Event::SuggestPosName => {
+                model.suggested_name = None;
                 if let Some(Ok(geo)) = &model.curr_pos {
-                    if model.saved_positions_names.contains_key(&name) {
-                        model.msg =
-                            format_compact!("Error: There is already a position named {name}");
-                    } else {
-                        let pos = SavedPos::new(name.clone(), geo);
+                    caps.reverse_geocode
+                        .reverse_geocode(geo.coords, Event::SetSuggestedName);
+                }
+            }
+            Event::SetSuggestedName(response) => model.suggested_name = response.name,
+            Event::SaveAveragedPos { name, window } => {
+                let window = TimeDelta::from_std(window).unwrap_or(TimeDelta::max_value());
+                let positions = model
+                    .all_positions
+                    .as_ref()
+                    .zip(model.curr_time)
+                    .map(|(rec, t)| {
+                        rec.get_since(
+                            t.checked_sub_signed(window)
+                                .unwrap_or(DateTime::<Utc>::MIN_UTC),
+                        )
+                    })
+                    .unwrap_or_default();
+                let curr_pos = model.curr_pos.as_ref().and_then(|x| x.as_ref().ok());
+                let pos = SavedPos::new_averaged(name.clone(), positions)
+                    .or_else(|| curr_pos.map(|geo| SavedPos::new(name.clone(), geo)));
+                if model.saved_positions_names.contains_key(&name) {
+                    model.show_error(&AppError::PosNameExists(name));
+                } else if let Some(pos) = pos {
+                    model.saved_positions.insert(pos.clone());
+                    model.saved_positions_names.insert(name.clone(), pos);
+                    touch_saved_position(model, name);
+                    self.evict_saved_positions(model);
+                    // Update `model.view_saved_positions`.
+                    self.view_saved_positions(model, caps);
+                    self.save_saved_positions(model, caps);
+                } else {
+                    model.show_error(&AppError::NoCurrentPosition);
+                }
+            }
+            Event::SaveWaypointsFromWay { way_name, every_n } => {
+                if every_n == 0 {
+                    model.show_error(&AppError::ZeroNodeInterval);
+                } else if let Some(rec) = model.find_way(&way_name) {
+                    let positions = rec
+                        .way
+                        .nodes()
+                        .iter()
+                        .step_by(every_n)
+                        .enumerate()
+                        .map(|(i, node)| SavedPos {
+                            name: format_compact!("{way_name} #{}", i + 1),
+                            pos: node.pos.clone(),
+                            timestamp: node.timestamp,
+                            averaged: None,
+                        })
+                        .collect::<Vec<_>>();
+                    let mut n_saved = 0;
+                    let mut n_skipped = 0;
+                    for pos in positions {
+                        if model.saved_positions_names.contains_key(&pos.name) {
+                            n_skipped += 1;
+                            continue;
+                        }
+                        let name = pos.name.clone();
                         model.saved_positions.insert(pos.clone());
-                        model.saved_positions_names.insert(name, pos);
+                        model.saved_positions_names.insert(name.clone(), pos);
+                        touch_saved_position(model, name);
+                        n_saved += 1;
+                    }
+                    model.msg = Text::SavedFromWay {
+                        name: &way_name,
+                        saved: n_saved,
+                        skipped: n_skipped,
+                    }
+                    .translate(model.language);
+                    if n_saved > 0 {
+                        self.evict_saved_positions(model);
                         // Update `model.view_saved_positions`.
                         self.view_saved_positions(model, caps);
                         self.save_saved_positions(model, caps);
                     }
                 } else {
-                    model.msg = "Error: The current position is not known.".into();
+                    model.show_error(&AppError::WayNotFound(way_name));
                 }
             }
-            Event::DelSavedPos(name) => {
-                if let Some(pos) = model.saved_positions_names.remove(&name) {
-                    model.saved_positions.remove(&pos);
+            Event::DelSavedPos(name) => match del_saved_pos(model, &name) {
+                Ok(()) => {
                     // Update `model.view_saved_positions`.
                     self.view_saved_positions(model, caps);
                     self.save_saved_positions(model, caps);
                     model.msg = format_compact!("{name} has been removed.");
+                }
+                Err(e) => model.show_error(&e),
+            },
+            Event::RenameSavedPos { old, new } => {
+                if model.saved_positions_names.contains_key(&new) {
+                    model.show_error(&AppError::PosNameExists(new));
+                } else if let Some(old_pos) = model.saved_positions_names.remove(&old) {
+                    let pos = SavedPos {
+                        name: new.clone(),
+                        ..old_pos.clone()
+                    };
+                    replace_in_rtree(&mut model.saved_positions, &old_pos, pos.clone());
+                    model.saved_positions_names.insert(new.clone(), pos);
+                    if let Some(fence) = model.geofences.remove(&old) {
+                        model.geofences.insert(new.clone(), fence);
+                    }
+                    if model.target.as_ref() == Some(&old) {
+                        model.target = Some(new.clone());
+                    }
+                    if let Some(last_viewed) = model.saved_positions_last_viewed.remove(&old) {
+                        model
+                            .saved_positions_last_viewed
+                            .insert(new.clone(), last_viewed);
+                    }
+                    // Update `model.view_saved_positions`.
+                    self.view_saved_positions(model, caps);
+                    self.save_saved_positions(model, caps);
+                    model.msg = format_compact!("{old} has been renamed to {new}.");
                 } else {
-                    model.msg = format_compact!("Error: Position {name} does not exist.");
+                    model.show_error(&AppError::PosNotFound(old));
                 }
             }
-            Event::ViewNSavedPositions(n) => {
-                model.view_n_saved_positions = n;
-                self.view_saved_positions(model, caps);
+            Event::EditSavedPos {
+                name,
+                coords,
+                altitude,
+            } => {
+                if let Some(pos) = model.saved_positions_names.get_mut(&name) {
+                    let old_pos = pos.clone();
+                    pos.pos.coords = coords;
+                    pos.pos.altitude = altitude;
+                    // The position is no longer an average of the recorded fixes.
+                    pos.averaged = None;
+                    replace_in_rtree(&mut model.saved_positions, &old_pos, pos.clone());
+                    if let Some(fence) = model.geofences.get_mut(&name) {
+                        fence.inside = None;
+                    }
+                    // Update `model.view_saved_positions`.
+                    self.view_saved_positions(model, caps);
+                    self.save_saved_positions(model, caps);
+                    model.msg = format_compact!("{name} has been moved.");
+                } else {
+                    model.show_error(&AppError::PosNotFound(name));
+                }
             }
-
-            // Recorded Ways
-            Event::SaveAllPositions(name) => {
-                if let Some(all_positions) = &model.all_positions {
-                    if model.recorded_ways.contains_key(&name) {
-                        model.msg = format_compact!("Error: The name {name} is already in use.");
-                    } else {
-                        model.recorded_ways.insert(name, all_positions.clone());
-                        self.view_recorded_ways(model, caps);
-                        self.save_recorded_ways(model, caps);
+            Event::AddGeofence { name, radius } => {
+                if model.saved_positions_names.contains_key(&name) {
+                    model.geofences.insert(
+                        name,
+                        Geofence {
+                            radius,
+                            inside: None,
+                        },
+                    );
+                } else {
+                    model.show_error(&AppError::PosNotFound(name));
+                }
+            }
+            Event::NearestSavedPosition => {
+                model.msg = if let Some(Ok(curr_pos)) = &model.curr_pos {
+                    match model
+                        .saved_positions
+                        .nearest_neighbor_iter(&rtree_point(&curr_pos.coords))
+                        .next()
+                    {
+                        Some(pos) => {
+                            let view = ViewSavedPos::new(
+                                pos.clone(),
+                                Some(curr_pos.coords),
+                                model.heading_reference(),
+                                false,
+                                model.precision,
+                                model.unit_system,
+                            );
+                            format_compact!("Nearest saved position: {}", view.summary)
+                        }
+                        None => "There are no saved positions.".into(),
                     }
                 } else {
-                    model.msg = format_compact!("Error: No positions recorded.");
+                    Text::Error(&AppError::NoCurrentPosition).translate(model.language)
+                };
+            }
+            Event::MeasureBetween { a, b } => {
+                model.msg = match (
+                    model.saved_positions_names.get(&a),
+                    model.saved_positions_names.get(&b),
+                ) {
+                    (Some(pos_a), Some(pos_b)) => format_compact!(
+                        "From {a} to {b}: {}",
+                        format_distance_and_bearing(
+                            pos_a.pos.coords,
+                            pos_b.pos.coords,
+                            None,
+                            model.unit_system
+                        )
+                    ),
+                    (None, _) => Text::Error(&AppError::PosNotFound(a)).translate(model.language),
+                    (_, None) => Text::Error(&AppError::PosNotFound(b)).translate(model.language),
+                };
+            }
+            Event::ShowSavedCentroid => {
+                model.msg = if model.saved_positions.size() == 0 {
+                    "There are no saved positions.".into()
+                } else if let Some(coords) =
+                    centroid(model.saved_positions.iter().map(|x| x.pos.coords))
+                {
+                    format_compact!("Centroid of the saved positions: {}", format_coords(coords))
+                } else {
+                    "The saved positions don't have a centroid since they are evenly spread \
+                     around the earth."
+                        .into()
+                };
+            }
+            Event::PreviewRouteTo(name) => {
+                model.route_preview = None;
+                if !name.is_empty() {
+                    match (model.saved_positions_names.get(&name), &model.curr_pos) {
+                        (None, _) => {
+                            model.show_error(&AppError::PosNotFound(name));
+                        }
+                        (Some(target), Some(Ok(geo)))
+                            if are_antipodal(
+                                geo.coords.to_nvector(),
+                                target.pos.coords.to_nvector(),
+                            ) =>
+                        {
+                            model.msg = format_compact!(
+                                "Warning: {name} is on the opposite side of the earth, so there \
+                                 is no single shortest route to it."
+                            );
+                        }
+                        (Some(_), Some(Ok(_))) => model.route_preview = Some(name),
+                        (Some(_), _) => {
+                            model.show_error(&AppError::NoCurrentPosition);
+                        }
+                    }
                 }
             }
-            Event::DelRecordedWay(name) => {
-                if let Some(way) = model.recorded_ways.remove(&name) {
-                    // Update `model.view_recorded_ways`.
-                    self.view_recorded_ways(model, caps);
-                    self.save_recorded_ways(model, caps);
-                    model.msg = format_compact!("{name} has been removed.");
+            Event::SelectTarget(name) => {
+                if model.saved_positions_names.contains_key(&name) {
+                    model.target = Some(name);
                 } else {
-                    model.msg = format_compact!("Error: Way {name} does not exist.");
+                    model.show_error(&AppError::PosNotFound(name));
                 }
             }
-            Event::ViewNRecordedWays(n) => {
-                model.view_n_recorded_ways = n;
-                self.view_recorded_ways(model, caps);
+            Event::RemoveGeofence(name) => {
+                if model.geofences.remove(&name).is_none() {
+                    model.show_error(&AppError::NotAGeofence(name));
+                }
+            }
+            Event::DedupSavedPositions(radius) => {
+                let mut positions = model.saved_positions.iter().cloned().collect::<Vec<_>>();
+                positions.sort_by_key(|x| x.timestamp);
+                let mut removed = 0;
+                for pos in positions {
+                    if !model.saved_positions_names.contains_key(&pos.name) {
+                        // Already removed as a duplicate of an older position.
+                        continue;
+                    }
+                    let duplicates =
+                        saved_within_radius(&model.saved_positions, pos.pos.coords, radius)
+                            .filter(|x| {
+                                x.timestamp > pos.timestamp
+                                    || (x.timestamp == pos.timestamp && x.name > pos.name)
+                            })
+                            .cloned()
+                            .collect::<Vec<_>>();
+                    for duplicate in duplicates {
+                        remove_saved_position(model, &duplicate.name);
+                        removed += 1;
+                    }
+                }
+                if removed > 0 {
+                    // Update `model.view_saved_positions`.
+                    self.view_saved_positions(model, caps);
+                    self.save_saved_positions(model, caps);
+                }
+                model.msg = Text::DuplicatesRemoved(removed).translate(model.language);
+            }
+            Event::SetMaxSavedPositions(max) => {
+                model.max_saved_positions = max;
+                if self.evict_saved_positions(model) {
+                    // Update `model.view_saved_positions`.
+                    self.view_saved_positions(model, caps);
+                    self.save_saved_positions(model, caps);
+                }
+            }
+            Event::ViewNSavedPositions(n) => {
+                model.view_n_saved_positions = n;
+                self.view_saved_positions(model, caps);
+            }
+            Event::ViewSavedPositionsInBox { min, max } => {
+                model.view_box = Some((min, max));
+                self.view_saved_positions(model, caps);
+            }
+            Event::ViewSavedWithinRadius { center, radius } => {
+                model.view_radius = Some((center, radius));
+                self.view_saved_positions(model, caps);
+            }
+            Event::ClusterSavedPositions { cell_size } => {
+                model.cluster_cell_size = (cell_size > Length::ZERO).then_some(cell_size);
+                self.view_saved_positions(model, caps);
+            }
+
+            // Recorded Ways
+            Event::SaveAllPositions(name) => match save_all_positions(model, name.clone()) {
+                Ok(()) => {
+                    self.view_recorded_ways(model, caps);
+                    self.save_recorded_ways(model, caps, [&name]);
+                    self.clear_live_autosave(model, caps);
+                }
+                Err(e) => model.show_error(&e),
+            },
+            Event::SaveWayRange { name, start, end } => {
+                match save_way_range(model, name.clone(), start, end) {
+                    Ok(()) => {
+                        self.view_recorded_ways(model, caps);
+                        self.save_recorded_ways(model, caps, [&name]);
+                    }
+                    Err(e) => model.show_error(&e),
+                }
+            }
+            Event::ClearCurrentRecording => {
+                model.all_positions = None;
+                model.live_recording_trimmed = false;
+                self.clear_live_autosave(model, caps);
+                model.msg = "The current way has been cleared.".into();
+            }
+            Event::StartNewTrack => {
+                model.all_positions = Some(RecordedWay::new());
+                model.live_recording_trimmed = false;
+                self.clear_live_autosave(model, caps);
+                model.msg = "A new track has been started.".into();
+            }
+            Event::DelRecordedWay(name) => {
+                if let Some(way) = model.recorded_ways.remove(&name) {
+                    // Update `model.view_recorded_ways`.
+                    self.view_recorded_ways(model, caps);
+                    self.save_recorded_ways(model, caps, [&name]);
+                    model.msg = format_compact!("{name} has been removed.");
+                } else {
+                    model.show_error(&AppError::WayNotFound(name));
+                }
+            }
+            Event::MergeWays {
+                first,
+                second,
+                into,
+                delete_sources,
+            } => match merge_ways(model, &first, &second, into.clone(), delete_sources) {
+                Ok(()) => {
+                    self.view_recorded_ways(model, caps);
+                    if delete_sources {
+                        self.save_recorded_ways(model, caps, [&into, &first, &second]);
+                    } else {
+                        self.save_recorded_ways(model, caps, [&into]);
+                    }
+                    model.msg =
+                        format_compact!("{first} and {second} have been merged into {into}.");
+                }
+                Err(e) => model.show_error(&e),
+            },
+            Event::SplitWay { name, at_index } => self.split_way(model, caps, name, at_index),
+            Event::SplitWayAt { name, at } => {
+                let at_index = model.recorded_ways.get(&name).map(|rec| rec.index_at(at));
+                // A missing way is reported by `split_way`.
+                self.split_way(model, caps, name, at_index.unwrap_or(0));
+            }
+            Event::SetWayMeta { name, color, note } => {
+                if let Some(rec) = model.recorded_ways.get_mut(&name) {
+                    rec.meta = WayMeta { color, note };
+                    self.save_recorded_ways(model, caps, [&name]);
+                } else {
+                    model.show_error(&AppError::WayNotFound(name));
+                }
+            }
+            Event::ExportWayGpx(name) => {
+                if let Some(rec) = model.recorded_ways.get(&name) {
+                    caps.file_download.file_download(
+                        gpx::way_to_gpx(&name, &rec.way).into_bytes(),
+                        Some(format_compact!("{name}.gpx")),
+                        Some("application/gpx+xml"),
+                    );
+                } else {
+                    model.show_error(&AppError::WayNotFound(name));
+                }
+            }
+            Event::ExportWayJson(name) => {
+                if let Some(rec) = model.recorded_ways.get(&name) {
+                    let json = serde_json::json!({
+                        "name": name,
+                        "node_count": rec.way.nodes().len(),
+                        "length_metres": rec.way.length().as_metres(),
+                        "duration_seconds": rec.way.total_time().num_seconds(),
+                        "way": rec,
+                    });
+                    caps.file_download.file_download(
+                        serde_json::to_vec_pretty(&json).unwrap(),
+                        Some(format_compact!("{name}.json")),
+                        Some("application/json"),
+                    );
+                } else {
+                    model.show_error(&AppError::WayNotFound(name));
+                }
+            }
+            Event::UploadWay(name) => {
+                match (&model.sync_endpoint, model.recorded_ways.get(&name)) {
+                    (None, _) => {
+                        model.msg = Text::Error(&AppError::NoSyncEndpoint).translate(model.language)
+                    }
+                    (_, None) => {
+                        model.msg =
+                            Text::Error(&AppError::WayNotFound(name)).translate(model.language)
+                    }
+                    (Some(endpoint), Some(rec)) => {
+                        caps.http
+                            .post(endpoint.as_str())
+                            .header("Content-Type", "application/gpx+xml")
+                            .body_bytes(gpx::way_to_gpx(&name, &rec.way).into_bytes())
+                            .send(move |res| Event::WayUploaded {
+                                name,
+                                res: SyncError::from_response(res),
+                            });
+                    }
+                }
+            }
+            Event::WayUploaded { name, res } => match res {
+                Ok(()) => model.msg = format_compact!("{name} has been uploaded."),
+                Err(SyncError::Status(status)) => {
+                    model.show_error(&AppError::UploadRejected { name, status })
+                }
+                Err(SyncError::Network(error)) => {
+                    model.show_error(&AppError::UploadFailed { name, error })
+                }
+            },
+            Event::SetSyncEndpoint(endpoint) => {
+                model.sync_endpoint = (!endpoint.is_empty()).then_some(endpoint);
+                self.save_sync_endpoint(model, caps);
+            }
+            Event::ViewNRecordedWays(n) => {
+                model.view_n_recorded_ways = n;
+                self.select_recorded_ways(model);
+            }
+            Event::SetWaySort(way_sort) => {
+                model.way_sort = way_sort;
+                self.select_recorded_ways(model);
+            }
+            Event::ViewWayDetail(name) => match name {
+                Some(name) if model.find_way(&name).is_none() => {
+                    model.show_error(&AppError::WayNotFound(name));
+                }
+                name => model.view_way_detail = name,
+            },
+            Event::StartNamedRecording(name) => {
+                if name == ALL_POSITIONS_NAME
+                    || model.named_recordings.contains_key(&name)
+                    || model.recorded_ways.contains_key(&name)
+                {
+                    model.show_error(&AppError::WayNameExists(name));
+                } else {
+                    model.named_recordings.insert(name, RecordedWay::new());
+                }
+            }
+            Event::StopNamedRecording(name) => {
+                if let Some(rec) = model.named_recordings.remove(&name) {
+                    if rec.way.nodes().is_empty() {
+                        model.msg = format_compact!("{name} was stopped without any positions.");
+                    } else if model.recorded_ways.contains_key(&name) {
+                        // Keep the recording so that no positions are lost.
+                        model.named_recordings.insert(name.clone(), rec);
+                        model.show_error(&AppError::WayNameExists(name));
+                    } else {
+                        model.recorded_ways.insert(name.clone(), rec);
+                        self.view_recorded_ways(model, caps);
+                        self.save_recorded_ways(model, caps, [&name]);
+                        model.msg = format_compact!("{name} has been saved.");
+                    }
+                } else {
+                    model.show_error(&AppError::WayNotFound(name));
+                }
+            }
+            Event::RepairRecordedWay(name) => {
+                if let Some(way) = model.recorded_ways.get_mut(&name) {
+                    let (before, after) = way.repair();
+                    self.save_recorded_ways(model, caps, [&name]);
+                    model.msg = format_compact!(
+                        "{name} has been repaired. Length before: {} m, after: {} m.",
+                        before.as_metres().round(),
+                        after.as_metres().round()
+                    );
+                } else {
+                    model.show_error(&AppError::WayNotFound(name));
+                }
+            }
+            Event::DeleteWayNode { name, index } => {
+                if let Some(way) = model.recorded_ways.get_mut(&name) {
+                    if way.remove_node(index).is_some() {
+                        self.save_recorded_ways(model, caps, [&name]);
+                        model.msg = format_compact!("Node {index} has been removed from {name}.");
+                    } else {
+                        let nodes = way.way.nodes().len();
+                        model.show_error(&AppError::InvalidNodeIndex { name, nodes });
+                    }
+                } else {
+                    model.show_error(&AppError::WayNotFound(name));
+                }
+            }
+            Event::DistanceToWay(name) => {
+                model.msg = match (model.recorded_ways.get(&name), &model.curr_pos) {
+                    (None, _) => {
+                        Text::Error(&AppError::WayNotFound(name)).translate(model.language)
+                    }
+                    (Some(rec), Some(Ok(curr_pos))) => {
+                        match rec.way.closest_point(curr_pos.coords) {
+                            Some((_, _, distance)) => format_compact!(
+                                "You are {} m from {name}.",
+                                distance.as_metres().round()
+                            ),
+                            None => {
+                                Text::Error(&AppError::EmptyWay(name)).translate(model.language)
+                            }
+                        }
+                    }
+                    (Some(_), _) => {
+                        Text::Error(&AppError::NoCurrentPosition).translate(model.language)
+                    }
+                };
+            }
+            Event::RemainingAlongWay { name, reverse } => {
+                model.msg = match (model.recorded_ways.get(&name), &model.curr_pos) {
+                    (None, _) => {
+                        Text::Error(&AppError::WayNotFound(name)).translate(model.language)
+                    }
+                    (Some(rec), Some(Ok(curr_pos))) => {
+                        match rec.way.remaining_along(curr_pos.coords, reverse) {
+                            Some(remaining) => format_compact!(
+                                "{} remaining along {name}.",
+                                format_long_length(remaining, model.unit_system)
+                            ),
+                            None => {
+                                Text::Error(&AppError::EmptyWay(name)).translate(model.language)
+                            }
+                        }
+                    }
+                    (Some(_), _) => {
+                        Text::Error(&AppError::NoCurrentPosition).translate(model.language)
+                    }
+                };
+            }
+            Event::SimplifyWay { name, tolerance } => {
+                if let Some(way) = model.recorded_ways.get_mut(&name) {
+                    let nodes_before = way.way.nodes().len();
+                    way.way.simplify(tolerance);
+                    let nodes_after = way.way.nodes().len();
+                    self.save_recorded_ways(model, caps, [&name]);
+                    model.msg = Text::Simplified {
+                        name: &name,
+                        before: nodes_before,
+                        after: nodes_after,
+                    }
+                    .translate(model.language);
+                } else {
+                    model.show_error(&AppError::WayNotFound(name));
+                }
+            }
+            Event::DownsampleWay { name, target } => {
+                if let Some(way) = model.recorded_ways.get_mut(&name) {
+                    let nodes_before = way.way.nodes().len();
+                    way.way.downsample(target);
+                    let nodes_after = way.way.nodes().len();
+                    self.save_recorded_ways(model, caps, [&name]);
+                    model.msg = Text::Downsampled {
+                        name: &name,
+                        before: nodes_before,
+                        after: nodes_after,
+                    }
+                    .translate(model.language);
+                } else {
+                    model.show_error(&AppError::WayNotFound(name));
+                }
+            }
+            Event::SetLiveRecordingLimit(limit) => {
+                model.live_recording_limit = limit;
+                model.live_recording_trimmed = false;
+                self.trim_all_positions(model);
+            }
+            Event::SetLiveAutosave(None) => {
+                model.live_autosave.interval = None;
+                self.clear_live_autosave(model, caps);
+                self.save_live_autosave(model, caps);
+            }
+            Event::SetLiveAutosave(Some(interval)) => {
+                if interval.is_zero() {
+                    model.show_error(&AppError::ZeroAutosaveInterval);
+                } else {
+                    model.live_autosave.interval =
+                        Some(TimeDelta::from_std(interval).unwrap_or(TimeDelta::max_value()));
+                    self.autosave_live_recording(model, caps);
+                    self.save_live_autosave(model, caps);
+                }
+            }
+            Event::RecoverLiveRecording => {
+                caps.storage.get(
+                    LIVE_RECORDING_KEY.to_string(),
+                    Event::LiveRecordingRecovered,
+                );
+            }
+            Event::LiveRecordingRecovered(Ok(None)) => (),
+            Event::LiveRecordingRecovered(Ok(Some(bytes))) => {
+                match decode(&bytes, false, migrate_recorded_way) {
+                    Ok(recovered) => {
+                        model.live_autosave.stored = true;
+                        let n = recovered.way.nodes().len();
+                        model.all_positions = Some(match model.all_positions.take() {
+                            Some(rec) => rec.merge(&recovered),
+                            None => recovered,
+                        });
+                        self.trim_all_positions(model);
+                        model.msg = Text::RecordingRecovered(n).translate(model.language);
+                    }
+                    Err(e) => model.show_error(&AppError::Serialization {
+                        what: "the recording since app start".into(),
+                        error: e.to_compact_string(),
+                    }),
+                }
+            }
+            Event::LiveRecordingRecovered(Err(e)) => model.show_error(&AppError::Storage {
+                action: StorageAction::RecoverLiveRecording,
+                error: e.to_compact_string(),
+            }),
+
+            Event::SetUnitSystem(unit_system) => {
+                model.unit_system = unit_system;
+                self.save_unit_system(model, caps);
+            }
+            Event::SetBearingMode(mode) => model.bearing_mode = mode,
+            Event::SetSpeedUnit(speed_unit) => model.speed_unit = speed_unit,
+            Event::SetLanguage(language) => {
+                model.language = language;
+                self.save_language(model, caps);
+            }
+            Event::SetPrecision { coord, other } => {
+                model.precision = Precision {
+                    coord: coord.min(MAX_PRECITION),
+                    other: other.min(MAX_PRECITION),
+                };
             }
-
             Event::Msg(msg) => model.msg = msg,
+            Event::Error(e) => model.show_error(&e),
 
             // Time
             Event::UpdateCurrTime => {
@@ -264,28 +2012,168 @@ impl App for GeoApp {
             }
             Event::SetCurrTime(time) => {
                 model.curr_time = Some(time.try_into().unwrap());
+                self.forget_stale_pos(model, caps);
+                self.autosave_live_recording(model, caps);
             }
+            Event::SetMaxFixAge(secs) => {
+                model.max_fix_age = Some(secs);
+                self.forget_stale_pos(model, caps);
+            }
+            Event::SetMaxConsecutiveTimeouts(max) => model.max_consecutive_timeouts = Some(max),
 
             Event::None => (),
         }
-        caps.render.render();
+        self.update_screen_wake(model, caps);
+        self.update_all_positions_view(model);
+        // Only render if the view model has changed.
+        let view = ViewModel::new(model);
+        let fingerprint = view.fingerprint();
+        model.view = Some(view);
+        if model.rendered_fingerprint.replace(fingerprint) != Some(fingerprint) {
+            caps.render.render();
+        }
     }
 
     fn view(&self, model: &Self::Model) -> Self::ViewModel {
-        ViewModel::new(model)
+        model.view.clone().unwrap_or_else(|| ViewModel::new(model))
     }
 }
 
 impl GeoApp {
-    fn load_persistant_data(&self, caps: &Capabilities, key: &'static str) {
+    /// Check whether the current position has entered or left any geofence and tell the user.
+    /// Fences without a previous state are only initialized.
+    fn update_geofences(&self, model: &mut Model, caps: &Capabilities, coords: LatLong) {
+        let mut crossings = Vec::new();
+        for (name, fence) in &mut model.geofences {
+            let Some(pos) = model.saved_positions_names.get(name) else {
+                continue;
+            };
+            let distance = PLANET.distance(coords.to_nvector(), pos.pos.coords.to_nvector());
+            let inside = distance <= fence.radius;
+            match fence.inside.replace(inside) {
+                Some(false) if inside => crossings.push(format_compact!("Entered {name}")),
+                Some(true) if !inside => crossings.push(format_compact!("Left {name}")),
+                _ => (),
+            }
+        }
+        if !crossings.is_empty() {
+            model.msg = crossings.join(", ").into();
+            caps.notify.notify_user(NotifyKind::Info, model.msg.clone());
+        }
+    }
+
+    /// Notify the user when the current position comes within `ARRIVED_DISTANCE` of the target.
+    fn update_arrival(&self, model: &mut Model, caps: &Capabilities, coords: LatLong) {
+        let arrived_at = model
+            .target_pos(coords)
+            .filter(|target| {
+                PLANET
+                    .distance(coords.to_nvector(), target.pos.coords.to_nvector())
+                    .as_metres()
+                    < ARRIVED_DISTANCE
+            })
+            .map(|target| target.name.clone());
+        if let Some(name) = &arrived_at {
+            if model.arrived_at.as_ref() != Some(name) {
+                caps.notify.notify_user(
+                    NotifyKind::Arrival,
+                    Text::Arrived(name).translate(model.language),
+                );
+            }
+        }
+        model.arrived_at = arrived_at;
+    }
+
+    /// Add the distance from the position where the odometer was last increased, if it is larger
+    /// than the accuracy of the new position and `ODOMETER_MIN_STEP`.
+    fn update_odometer(&self, model: &mut Model, geo_info: &GeoInfo) {
+        let Some(prev) = model.odometer_pos else {
+            model.odometer_pos = Some(geo_info.coords);
+            return;
+        };
+        let distance = PLANET.distance(prev.to_nvector(), geo_info.coords.to_nvector());
+        let min_step = geo_info
+            .accuracy
+            .map_or(ODOMETER_MIN_STEP, |x| x.as_metres().max(ODOMETER_MIN_STEP));
+        if distance.as_metres() >= min_step {
+            model.odometer = model.odometer + distance;
+            model.odometer_pos = Some(geo_info.coords);
+        }
+    }
+
+    /// Update `model.all_positions_view`. It is only rebuilt if the start of the way or the units
+    /// have changed, or if many nodes have been added since it was last rebuilt.
+    fn update_all_positions_view(&self, model: &mut Model) {
+        let Some(rec) = &model.all_positions else {
+            model.all_positions_view = None;
+            return;
+        };
+        let nodes = rec.way.nodes().len();
+        let start = rec.way.nodes().first().map(|x| x.timestamp);
+        let speed_unit = model.speed_unit();
+        if let Some(cached) = &mut model.all_positions_view {
+            if cached.start == start
+                && cached.units == model.unit_system
+                && cached.speed_unit == speed_unit
+                && (cached.nodes..cached.nodes + VIEW_REBUILD_INTERVAL).contains(&nodes)
+                && cached.view.update_last(rec, model.unit_system)
+            {
+                return;
+            }
+        }
+        model.all_positions_view = Some(AllPositionsView {
+            view: ViewRecordedWay::new(
+                ALL_POSITIONS_NAME,
+                rec,
+                false,
+                model.unit_system,
+                speed_unit,
+            ),
+            start,
+            nodes,
+            units: model.unit_system,
+            speed_unit,
+        });
+    }
+
+    /// Keep the screen awake while the position is recorded, since the position may not be
+    /// updated while the device sleeps. The shell is only told when this changes.
+    fn update_screen_wake(&self, model: &mut Model, caps: &Capabilities) {
+        if model.screen_wake != model.geolocation_active {
+            model.screen_wake = model.geolocation_active;
+            caps.screen_wake.set_screen_wake(model.screen_wake);
+        }
+    }
+
+    /// Watch the position again with the current options if the position is being watched.
+    fn restart_geolocation(&self, model: &Model, caps: &Capabilities) {
+        if model.geolocation_active {
+            caps.geolocation.clear_watch();
+            caps.geolocation
+                .watch_position(model.effective_geo_options(), Event::GeolocationUpdate);
+        }
+    }
+
+    /// Set `enable_high_accuracy` in the options and restart the watch if it changed.
+    fn set_high_accuracy(&self, model: &mut Model, caps: &Capabilities, enable: bool) {
+        let mut opts = model.geo_options.unwrap_or(GEOLOCATION_OPTIONS);
+        if opts.enable_high_accuracy != enable {
+            opts.enable_high_accuracy = enable;
+            model.geo_options = Some(opts);
+            self.restart_geolocation(model, caps);
+        }
+    }
+
+    fn load_persistant_data(&self, caps: &Capabilities, key: impl Into<CompactString>) {
+        let key = key.into();
         caps.storage
-            .get(key.to_string(), move |res| Event::SetData {
-                res,
-                key: key.to_compact_string(),
-            });
+            .get(key.to_string(), move |res| Event::SetData { res, key });
     }
 
     /// Set data from persistant storage.
+    ///
+    /// If the data can't be decoded, it is copied to a backup key with the suffix
+    /// `CORRUPT_BACKUP_SUFFIX` and it will not be overwritten during this session.
     fn set_data(
         &self,
         model: &mut Model,
@@ -293,95 +2181,3785 @@ impl GeoApp {
         res: Result<Option<Vec<u8>>, KeyValueError>,
         key: CompactString,
     ) -> Result<(), CompactString> {
-        match (res, key) {
-            (Ok(Some(bytes)), key) if key == SAVED_POSITIONS_KEY => {
-                let (rtree, names) = bincode::deserialize(bytes.as_slice()).map_err(|e| {
-                    format_compact!("Browser Error: Error while decoding saved_positions: {e}")
-                })?;
-                model.saved_positions = rtree;
-                model.saved_positions_names = names;
+        match res {
+            Ok(Some(bytes)) => {
+                if let Err(e) = self.decode_data(model, caps, &key, &bytes, false) {
+                    caps.storage
+                        .set(format!("{key}{CORRUPT_BACKUP_SUFFIX}"), bytes, |res| {
+                            storage_error(res, StorageAction::BackUpUndecodable)
+                        });
+                    model.corrupt_keys.insert(key.clone());
+                    return Err(format_compact!(
+                        "{e}. A backup has been saved to {key}{CORRUPT_BACKUP_SUFFIX} and {key} \
+                         will not be saved until it is recovered."
+                    ));
+                }
+            }
+            Ok(None) => (),
+            Err(e) => {
+                let e = AppError::Storage {
+                    action: StorageAction::Retrieve(key),
+                    error: e.to_compact_string(),
+                };
+                return Err(Text::Error(&e).translate(model.language));
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode data from persistant storage and add it to the model. If `lenient` is set, the data
+    /// may also be JSON.
+    fn decode_data(
+        &self,
+        model: &mut Model,
+        caps: &Capabilities,
+        key: &str,
+        bytes: &[u8],
+        lenient: bool,
+    ) -> Result<(), AppError> {
+        match key {
+            SAVED_POSITIONS_KEY => {
+                let (rtree, names): (RTree<SavedPos>, HashMap<CompactString, SavedPos>) =
+                    decode(bytes, lenient, migrate_saved_positions).map_err(|e| {
+                        AppError::Serialization {
+                            what: "saved_positions".into(),
+                            error: e.to_compact_string(),
+                        }
+                    })?;
+                if !lenient {
+                    // Otherwise, the data comes from a backup and not from this key.
+                    model.saved_positions_in_storage = Some(bytes.to_vec());
+                }
+                if model.saved_positions_names.is_empty() {
+                    model.saved_positions = rtree;
+                    model.saved_positions_names = names;
+                } else {
+                    // Positions which were saved before loading take precedence.
+                    for (name, pos) in names {
+                        if !model.saved_positions_names.contains_key(&name) {
+                            model.saved_positions.insert(pos.clone());
+                            model.saved_positions_names.insert(name, pos);
+                        }
+                    }
+                }
                 // Update `model.view_saved_positions`.
                 self.view_saved_positions(model, caps);
             }
-            (Ok(Some(bytes)), key) if key == RECORDED_WAYS_KEY => {
-                let recorded_ways = bincode::deserialize(bytes.as_slice()).map_err(|e| {
-                    format_compact!("Browser Error: Error while decoding saved ways: {e}")
+            RECORDED_WAYS_INDEX_KEY => {
+                let names: BTreeSet<CompactString> = decode(bytes, lenient, migrate_unchanged)
+                    .map_err(|e| AppError::Serialization {
+                        what: "the saved ways".into(),
+                        error: e.to_compact_string(),
+                    })?;
+                for name in names {
+                    if !model.recorded_ways.contains_key(&name) {
+                        self.load_persistant_data(caps, recorded_way_key(&name));
+                    }
+                    model.recorded_ways_index.insert(name);
+                }
+            }
+            key if key.starts_with(RECORDED_WAY_KEY_PREFIX) => {
+                let name = &key[RECORDED_WAY_KEY_PREFIX.len()..];
+                let rec = decode(bytes, lenient, migrate_recorded_way).map_err(|e| {
+                    AppError::Serialization {
+                        what: format_compact!("the way {name}"),
+                        error: e.to_compact_string(),
+                    }
                 })?;
-                model.recorded_ways = recorded_ways;
+                // A way with the same name which was created before loading takes precedence.
+                model.recorded_ways.entry(name.into()).or_insert(rec);
+                // Update `model.view_recorded_ways`.
+                self.view_recorded_ways(model, caps);
+            }
+            RECORDED_WAYS_KEY => {
+                // Migrate from when all ways were saved under a single key.
+                let recorded_ways: HashMap<CompactString, RecordedWay> =
+                    decode(bytes, lenient, migrate_recorded_ways).map_err(|e| {
+                        AppError::Serialization {
+                            what: "saved ways".into(),
+                            error: e.to_compact_string(),
+                        }
+                    })?;
+                let names = recorded_ways.keys().cloned().collect::<Vec<_>>();
+                for (name, rec) in recorded_ways {
+                    model.recorded_ways.entry(name).or_insert(rec);
+                }
+                self.save_recorded_ways(model, caps, &names);
+                caps.storage.delete(RECORDED_WAYS_KEY.to_string(), |res| {
+                    storage_error(res, StorageAction::DeleteOldSavedWays)
+                });
                 // Update `model.view_recorded_ways`.
                 self.view_recorded_ways(model, caps);
             }
-            (Ok(Some(_)), key) => panic!("Bad key: {key}"),
-            (Ok(None), _) => (),
-            (Err(e), key) => {
-                return Err(format_compact!(
-                    "Internal Error: When retrieving {key}: {e}"
-                ));
+            UNIT_SYSTEM_KEY => {
+                model.unit_system = decode(bytes, lenient, migrate_unchanged).map_err(|e| {
+                    AppError::Serialization {
+                        what: "the unit system".into(),
+                        error: e.to_compact_string(),
+                    }
+                })?;
+            }
+            LANGUAGE_KEY => {
+                model.language = decode(bytes, lenient, migrate_unchanged).map_err(|e| {
+                    AppError::Serialization {
+                        what: "the language".into(),
+                        error: e.to_compact_string(),
+                    }
+                })?;
+            }
+            HIGH_ACCURACY_KEY => {
+                let enable = decode(bytes, lenient, migrate_unchanged).map_err(|e| {
+                    AppError::Serialization {
+                        what: "the accuracy".into(),
+                        error: e.to_compact_string(),
+                    }
+                })?;
+                self.set_high_accuracy(model, caps, enable);
             }
+            SYNC_ENDPOINT_KEY => {
+                model.sync_endpoint = decode(bytes, lenient, migrate_unchanged).map_err(|e| {
+                    AppError::Serialization {
+                        what: "the sync endpoint".into(),
+                        error: e.to_compact_string(),
+                    }
+                })?;
+            }
+            LIVE_AUTOSAVE_KEY => {
+                let interval: Option<std::time::Duration> =
+                    decode(bytes, lenient, migrate_unchanged).map_err(|e| {
+                        AppError::Serialization {
+                            what: "the autosave interval".into(),
+                            error: e.to_compact_string(),
+                        }
+                    })?;
+                model.live_autosave.interval = interval
+                    .filter(|x| !x.is_zero())
+                    .map(|x| TimeDelta::from_std(x).unwrap_or(TimeDelta::max_value()));
+            }
+            key => panic!("Bad key: {key}"),
         }
         Ok(())
     }
 
+    /// Save the data which belongs to a key in persistant storage.
+    fn save_key(&self, model: &mut Model, caps: &Capabilities, key: &str) {
+        match key {
+            SAVED_POSITIONS_KEY => self.save_saved_positions(model, caps),
+            RECORDED_WAYS_INDEX_KEY => self.save_recorded_ways(model, caps, []),
+            key if key.starts_with(RECORDED_WAY_KEY_PREFIX) => {
+                let name = CompactString::from(&key[RECORDED_WAY_KEY_PREFIX.len()..]);
+                self.save_recorded_ways(model, caps, [&name]);
+            }
+            // The old key is migrated when it is decoded.
+            RECORDED_WAYS_KEY => (),
+            UNIT_SYSTEM_KEY => self.save_unit_system(model, caps),
+            LANGUAGE_KEY => self.save_language(model, caps),
+            HIGH_ACCURACY_KEY => self.save_high_accuracy(model, caps),
+            SYNC_ENDPOINT_KEY => self.save_sync_endpoint(model, caps),
+            LIVE_AUTOSAVE_KEY => self.save_live_autosave(model, caps),
+            key => panic!("Bad key: {key}"),
+        }
+    }
+
+    /// Mark the saved positions as changed and schedule an `Event::FlushSavedPositions`, unless
+    /// one is already scheduled.
     fn save_saved_positions(&self, model: &mut Model, caps: &Capabilities) {
+        model.saved_positions_dirty = true;
+        if !model.saved_positions_flush_pending {
+            model.saved_positions_flush_pending = true;
+            caps.time
+                .notify_after(*SAVED_POSITIONS_DEBOUNCE, |_| Event::FlushSavedPositions);
+        }
+    }
+
+    /// Write the saved positions to persistant storage if they have changed.
+    fn flush_saved_positions(&self, model: &mut Model, caps: &Capabilities) {
+        if !std::mem::take(&mut model.saved_positions_dirty)
+            || model.corrupt_keys.contains(SAVED_POSITIONS_KEY)
+        {
+            return;
+        }
+        let bytes = encode(&(&model.saved_positions, &model.saved_positions_names));
+        let expected = model.saved_positions_in_storage.replace(bytes.clone());
+        caps.storage
+            .set(SAVED_POSITIONS_KEY.to_string(), bytes, |res| {
+                Event::SavedPositionsWritten { expected, res }
+            });
+    }
+
+    /// Save some recorded ways, each under its own key, and the index of all saved ways. Ways
+    /// which don't exist in `model.recorded_ways` are deleted from the storage.
+    fn save_recorded_ways<'a>(
+        &self,
+        model: &mut Model,
+        caps: &Capabilities,
+        names: impl IntoIterator<Item = &'a CompactString>,
+    ) {
+        // The ways may have been edited in place.
+        model.recorded_way_segments = None;
+        for name in names {
+            let key = recorded_way_key(name);
+            if model.corrupt_keys.contains(&key) {
+                continue;
+            }
+            let key = key.to_string();
+            if let Some(rec) = model.recorded_ways.get(name) {
+                model.recorded_ways_index.insert(name.clone());
+                caps.storage.set(key, encode(rec), |res| {
+                    storage_error(res, StorageAction::SaveWay)
+                });
+            } else {
+                model.recorded_ways_index.remove(name);
+                caps.storage
+                    .delete(key, |res| storage_error(res, StorageAction::DeleteWay));
+            }
+        }
+        if model.corrupt_keys.contains(RECORDED_WAYS_INDEX_KEY) {
+            return;
+        }
         caps.storage.set(
-            SAVED_POSITIONS_KEY.to_string(),
-            bincode::serialize(&(&model.saved_positions, &model.saved_positions_names)).unwrap(),
-            |res| {
-                if let Err(e) = res {
-                    Event::Msg(format_compact!(
-                        "Internal Error: Failed to serialize saved_positions: {e}"
-                    ))
-                } else {
-                    Event::None
-                }
-            },
+            RECORDED_WAYS_INDEX_KEY.to_string(),
+            encode(&model.recorded_ways_index),
+            |res| storage_error(res, StorageAction::SaveWayIndex),
         );
     }
 
-    fn save_recorded_ways(&self, model: &mut Model, caps: &Capabilities) {
+    fn save_unit_system(&self, model: &Model, caps: &Capabilities) {
+        if model.corrupt_keys.contains(UNIT_SYSTEM_KEY) {
+            return;
+        }
         caps.storage.set(
-            RECORDED_WAYS_KEY.to_string(),
-            bincode::serialize(&model.recorded_ways).unwrap(),
-            |res| {
-                if let Err(e) = res {
-                    Event::Msg(format_compact!(
-                        "Internal Error: Failed to serialize recorded_ways: {e}"
-                    ))
-                } else {
-                    Event::None
-                }
-            },
+            UNIT_SYSTEM_KEY.to_string(),
+            encode(&model.unit_system),
+            |res| storage_error(res, StorageAction::SaveUnitSystem),
         );
     }
 
-    /// Select the saved positions to view.
-    fn view_saved_positions(&self, model: &mut Model, _caps: &Capabilities) {
-        model.view_saved_positions = if let Some(Ok(curr_pos)) = &model.curr_pos {
-            model
-                .saved_positions
-                .nearest_neighbor_iter(&rtree_point(&curr_pos.coords))
-                .cloned()
-                .take(model.view_n_saved_positions)
-                .collect::<Vec<_>>()
-        } else {
-            model
-                .saved_positions
-                .iter()
-                .cloned()
-                .take(model.view_n_saved_positions)
-                .collect()
-        };
+    fn save_language(&self, model: &Model, caps: &Capabilities) {
+        if model.corrupt_keys.contains(LANGUAGE_KEY) {
+            return;
+        }
+        caps.storage
+            .set(LANGUAGE_KEY.to_string(), encode(&model.language), |res| {
+                storage_error(res, StorageAction::SaveLanguage)
+            });
     }
 
-    /// Select recorded ways to show.
-    fn view_recorded_ways(&self, model: &mut Model, _caps: &Capabilities) {
-        // TODO: Use an algorithm to select the n nearest ways.
-        model.view_recorded_ways = model
-            .recorded_ways
-            .keys()
-            .cloned()
-            .take(model.view_n_recorded_ways)
-            .collect();
-        model.view_recorded_ways.sort();
+    fn save_high_accuracy(&self, model: &Model, caps: &Capabilities) {
+        if model.corrupt_keys.contains(HIGH_ACCURACY_KEY) {
+            return;
+        }
+        let enable = model
+            .geo_options
+            .unwrap_or(GEOLOCATION_OPTIONS)
+            .enable_high_accuracy;
+        caps.storage
+            .set(HIGH_ACCURACY_KEY.to_string(), encode(&enable), |res| {
+                storage_error(res, StorageAction::SaveAccuracy)
+            });
+    }
+
+    fn save_sync_endpoint(&self, model: &Model, caps: &Capabilities) {
+        if model.corrupt_keys.contains(SYNC_ENDPOINT_KEY) {
+            return;
+        }
+        caps.storage.set(
+            SYNC_ENDPOINT_KEY.to_string(),
+            encode(&model.sync_endpoint),
+            |res| storage_error(res, StorageAction::SaveSyncEndpoint),
+        );
+    }
+
+    fn save_live_autosave(&self, model: &Model, caps: &Capabilities) {
+        if model.corrupt_keys.contains(LIVE_AUTOSAVE_KEY) {
+            return;
+        }
+        let interval = model
+            .live_autosave
+            .interval
+            .map(|x| x.to_std().unwrap_or(std::time::Duration::MAX));
+        caps.storage
+            .set(LIVE_AUTOSAVE_KEY.to_string(), encode(&interval), |res| {
+                storage_error(res, StorageAction::SaveLiveAutosave)
+            });
+    }
+
+    /// Add recorded ways with unique names, and render and save them all at once.
+    ///
+    /// Returns the number of added ways.
+    fn import_ways(
+        &self,
+        model: &mut Model,
+        caps: &Capabilities,
+        ways: impl IntoIterator<Item = (CompactString, RecordedWay)>,
+    ) -> usize {
+        let mut names = Vec::new();
+        for (name, rec) in ways {
+            let name = unique_name(name, |x| model.recorded_ways.contains_key(x));
+            model.recorded_ways.insert(name.clone(), rec);
+            names.push(name);
+        }
+        if !names.is_empty() {
+            self.view_recorded_ways(model, caps);
+            self.save_recorded_ways(model, caps, &names);
+        }
+        names.len()
+    }
+
+    /// Add saved positions with unique names, and render and save them all at once.
+    ///
+    /// Returns the number of added positions.
+    fn import_saved_positions(
+        &self,
+        model: &mut Model,
+        caps: &Capabilities,
+        positions: impl IntoIterator<Item = SavedPos>,
+    ) -> usize {
+        let mut n = 0;
+        for mut pos in positions {
+            pos.name = unique_name(pos.name, |x| model.saved_positions_names.contains_key(x));
+            model.saved_positions.insert(pos.clone());
+            model
+                .saved_positions_names
+                .insert(pos.name.clone(), pos.clone());
+            touch_saved_position(model, pos.name);
+            n += 1;
+        }
+        if n > 0 {
+            self.evict_saved_positions(model);
+            self.view_saved_positions(model, caps);
+            self.save_saved_positions(model, caps);
+        }
+        n
+    }
+
+    /// Add waypoints and tracks from a GPX file to the saved positions and recorded ways.
+    fn import_gpx(&self, model: &mut Model, caps: &Capabilities, gpx: gpx::Gpx) {
+        let curr_time = model.curr_time;
+        let n_waypoints = self.import_saved_positions(
+            model,
+            caps,
+            gpx.waypoints
+                .into_iter()
+                .enumerate()
+                .map(|(i, waypoint)| SavedPos {
+                    name: waypoint
+                        .name
+                        .unwrap_or_else(|| format_compact!("Waypoint {}", i + 1)),
+                    pos: waypoint.pos,
+                    timestamp: waypoint
+                        .timestamp
+                        .or(curr_time)
+                        .unwrap_or(DateTime::UNIX_EPOCH),
+                    averaged: None,
+                }),
+        );
+        let n_tracks = self.import_ways(
+            model,
+            caps,
+            gpx.tracks.into_iter().enumerate().map(|(i, (name, rec))| {
+                (
+                    name.unwrap_or_else(|| format_compact!("Track {}", i + 1)),
+                    rec,
+                )
+            }),
+        );
+        model.msg = Text::Imported {
+            positions: n_waypoints,
+            ways: n_tracks,
+            exported_at: None,
+        }
+        .translate(model.language);
+        if gpx.skipped_tracks > 0 {
+            model.msg += &Text::SkippedUntimedTracks(gpx.skipped_tracks).translate(model.language);
+        }
+    }
+
+    /// Add the saved positions and recorded ways of data downloaded with `Event::DownloadData`.
+    fn import_data(&self, model: &mut Model, caps: &Capabilities, data: export::Export) {
+        let n_positions = self.import_saved_positions(model, caps, data.saved_positions);
+        let n_ways = self.import_ways(model, caps, data.recorded_ways);
+        model.msg = Text::Imported {
+            positions: n_positions,
+            ways: n_ways,
+            exported_at: data.exported_at,
+        }
+        .translate(model.language);
+    }
+
+    /// End the recording since app start, and save it under a name after its start time if
+    /// `save` is set.
+    fn finalize_recording(&self, model: &mut Model, caps: &Capabilities, save: bool) {
+        let Some(rec) = model.all_positions.take() else {
+            return;
+        };
+        model.live_recording_trimmed = false;
+        self.clear_live_autosave(model, caps);
+        let Some(first) = rec.way.nodes().first() else {
+            return;
+        };
+        if save {
+            let base_name = first
+                .timestamp
+                .with_timezone(&Local)
+                .format("Recording %Y-%m-%d %H:%M:%S")
+                .to_compact_string();
+            let mut name = base_name.clone();
+            let mut n = 2;
+            while model.recorded_ways.contains_key(&name) {
+                name = format_compact!("{base_name} ({n})");
+                n += 1;
+            }
+            model.msg = format_compact!("The way has been saved as {name}.");
+            model.recorded_ways.insert(name.clone(), rec);
+            self.view_recorded_ways(model, caps);
+            self.save_recorded_ways(model, caps, [&name]);
+        }
+    }
+
+    /// Split a recorded way in two, see `Event::SplitWay`.
+    fn split_way(&self, model: &mut Model, caps: &Capabilities, name: CompactString, index: usize) {
+        let Some(rec) = model.recorded_ways.get(&name) else {
+            model.show_error(&AppError::WayNotFound(name));
+            return;
+        };
+        let Some((first, second)) = rec.split(index) else {
+            let max = rec.way.nodes().len().saturating_sub(1);
+            model.show_error(&AppError::InvalidSplitIndex { name, max });
+            return;
+        };
+        let names = [format_compact!("{name} (1)"), format_compact!("{name} (2)")];
+        if let Some(taken) = names.iter().find(|x| model.recorded_ways.contains_key(*x)) {
+            model.show_error(&AppError::WayNameExists(taken.clone()));
+            return;
+        }
+        model.recorded_ways.remove(&name);
+        let [first_name, second_name] = &names;
+        model.recorded_ways.insert(first_name.clone(), first);
+        model.recorded_ways.insert(second_name.clone(), second);
+        self.view_recorded_ways(model, caps);
+        self.save_recorded_ways(model, caps, [&name, first_name, second_name]);
+        model.msg = format_compact!("{name} has been split into {first_name} and {second_name}.");
+    }
+
+    /// Start replaying a recorded way.
+    fn replay_way(
+        &self,
+        model: &mut Model,
+        caps: &Capabilities,
+        name: CompactString,
+        speed_factor: f64,
+    ) -> Result<(), AppError> {
+        if model.geolocation_active {
+            return Err(AppError::ReplayWhileGeolocating);
+        }
+        if !(speed_factor >= MIN_REPLAY_SPEED_FACTOR && speed_factor.is_finite()) {
+            return Err(AppError::InvalidSpeedFactor(speed_factor));
+        }
+        let Some(rec) = model.find_way(&name) else {
+            return Err(AppError::WayNotFound(name));
+        };
+        let nodes = rec.way.nodes().to_vec();
+        let Some(first) = nodes.first() else {
+            return Err(AppError::NoRecordedPositions);
+        };
+        model.replay_id += 1;
+        model.replay = Some(Replay {
+            start: model.curr_time.unwrap_or(first.timestamp),
+            name: name.clone(),
+            nodes,
+            next: 0,
+            speed_factor,
+        });
+        model.msg = format_compact!("Replaying {name}.");
+        let id = model.replay_id;
+        self.replay_next(model, caps, id);
+        Ok(())
+    }
+
+    /// Handle a new position or a position error, from the GPS or from a replay.
+    fn geolocation_update(
+        &self,
+        model: &mut Model,
+        caps: &Capabilities,
+        geo_result: GeoResult<GeoInfo>,
+    ) {
+        model.curr_pos = Some(geo_result.clone());
+        if geo_result.as_ref().is_err_and(GeoError::is_timeout) {
+            model.consecutive_timeouts += 1;
+            let max = model
+                .max_consecutive_timeouts
+                .unwrap_or(DEFAULT_MAX_CONSECUTIVE_TIMEOUTS);
+            if model.consecutive_timeouts > max {
+                model.msg = "GPS is taking unusually long — check your signal.".into();
+            }
+        } else {
+            model.consecutive_timeouts = 0;
+        }
+        if let Err(GeoError::PermissionDenied) = geo_result {
+            // Retrying is pointless until the user allows the position to be accessed, so
+            // the watch is stopped until `StartGeolocation` is sent again. Other errors
+            // are temporary and the shell keeps retrying.
+            if model.geolocation_active {
+                model.geolocation_active = false;
+                caps.geolocation.clear_watch();
+            }
+            model.show_error(&AppError::PermissionDenied);
+        } else if let Ok(geo_info) = geo_result {
+            if let Some(rec) = &mut model.all_positions {
+                rec.add(&geo_info);
+            } else {
+                let mut rec = RecordedWay::new();
+                rec.add(&geo_info);
+                model.all_positions = Some(rec);
+            }
+            for rec in model.named_recordings.values_mut() {
+                rec.add(&geo_info);
+            }
+            self.trim_all_positions(model);
+            self.update_odometer(model, &geo_info);
+            self.update_geofences(model, caps, geo_info.coords);
+            self.update_arrival(model, caps, geo_info.coords);
+            model.smoothed.add(
+                model.smoothing_factor.unwrap_or(DEFAULT_SMOOTHING_FACTOR),
+                geo_info.volocity,
+                geo_info.bearing,
+            );
+        }
+    }
+
+    /// Send the next position of the replay with some id and schedule the one after it.
+    fn replay_next(&self, model: &mut Model, caps: &Capabilities, id: u64) {
+        if id != model.replay_id {
+            return;
+        }
+        let Some(replay) = &mut model.replay else {
+            return;
+        };
+        let node = &replay.nodes[replay.next];
+        let timestamp = replay.replayed_time(node.timestamp);
+        let geo_info = GeoInfo {
+            timestamp,
+            coords: node.pos.coords,
+            altitude: node.pos.altitude,
+            accuracy: node.pos.accuracy,
+            altitude_accuracy: node.pos.altitude_accuracy,
+            bearing: None,
+            volocity: None,
+            source: None,
+            satellites: None,
+        };
+        replay.next += 1;
+        if let Some(next) = replay.nodes.get(replay.next) {
+            let delay = (replay.replayed_time(next.timestamp) - timestamp)
+                .num_milliseconds()
+                .max(0) as u64;
+            // Durations are stored in nanoseconds.
+            let delay = crux_time::Duration::from_millis(delay.min(u64::MAX / 1_000_000)).unwrap();
+            caps.time
+                .notify_after(delay, move |_| Event::ReplayNext(id));
+        } else {
+            model.msg = format_compact!("The replay of {} has finished.", replay.name);
+            model.replay = None;
+        }
+        self.geolocation_update(model, caps, Ok(geo_info));
+    }
+
+    /// Save `model.all_positions` under `LIVE_RECORDING_KEY` if autosaving is enabled, the
+    /// interval has passed since it was last saved and it has changed since then.
+    fn autosave_live_recording(&self, model: &mut Model, caps: &Capabilities) {
+        let autosave = &mut model.live_autosave;
+        let (Some(interval), Some(now), Some(rec)) =
+            (autosave.interval, model.curr_time, &model.all_positions)
+        else {
+            return;
+        };
+        if autosave.saved_at.is_some_and(|t| now - t < interval) {
+            return;
+        }
+        let last = rec.way.nodes().last().map(|x| x.timestamp);
+        if last.is_none() || last == autosave.saved_until {
+            return;
+        }
+        autosave.saved_at = Some(now);
+        autosave.saved_until = last;
+        autosave.stored = true;
+        caps.storage
+            .set(LIVE_RECORDING_KEY.to_string(), encode(rec), |res| {
+                storage_error(res, StorageAction::SaveLiveRecording)
+            });
+    }
+
+    /// Delete the recording saved under `LIVE_RECORDING_KEY` since the recording since app start
+    /// has been saved as a way or discarded. Later positions are saved again.
+    fn clear_live_autosave(&self, model: &mut Model, caps: &Capabilities) {
+        let autosave = &mut model.live_autosave;
+        autosave.saved_at = None;
+        autosave.saved_until = model
+            .all_positions
+            .as_ref()
+            .and_then(|rec| rec.way.nodes().last())
+            .map(|x| x.timestamp);
+        if std::mem::take(&mut autosave.stored) {
+            caps.storage.delete(LIVE_RECORDING_KEY.to_string(), |res| {
+                storage_error(res, StorageAction::DeleteLiveRecording)
+            });
+        }
+    }
+
+    /// Drop the oldest positions from `model.all_positions` if it exceeds
+    /// `model.live_recording_limit`. The user is warned the first time this happens.
+    fn trim_all_positions(&self, model: &mut Model) {
+        let (Some(rec), Some(limit)) = (&mut model.all_positions, model.live_recording_limit)
+        else {
+            return;
+        };
+        let removed = match limit {
+            LiveRecordingLimit::Nodes(n) => {
+                let removed = rec.way.nodes().len().saturating_sub(n);
+                rec.way.remove_front(removed);
+                removed
+            }
+            LiveRecordingLimit::Seconds(secs) => match rec.way.nodes().last() {
+                Some(last) => {
+                    let since = last.timestamp - TimeDelta::seconds(secs.into());
+                    rec.remove_before(since)
+                }
+                None => 0,
+            },
+        };
+        if removed > 0 && !model.live_recording_trimmed {
+            model.live_recording_trimmed = true;
+            model.msg = "Warning: The way since app start has reached its limit and old positions \
+                         are being dropped. Save it to keep them."
+                .into();
+        }
+    }
+
+    /// Set the current position to `None` if it is older than the max fix age.
+    fn forget_stale_pos(&self, model: &mut Model, caps: &Capabilities) {
+        let max_age = model.max_fix_age.unwrap_or(DEFAULT_MAX_FIX_AGE);
+        let (Some(Ok(geo)), Some(curr_time)) = (&model.curr_pos, model.curr_time) else {
+            return;
+        };
+        if max_age == 0 || curr_time - geo.timestamp <= TimeDelta::seconds(max_age.into()) {
+            return;
+        }
+        model.curr_pos = None;
+        model.msg = format_compact!(
+            "The position is no longer shown since no position has been received for {max_age} s."
+        );
+        // Update `model.view_saved_positions`.
+        self.view_saved_positions(model, caps);
+    }
+
+    /// Select the saved positions to view.
+    fn view_saved_positions(&self, model: &mut Model, _caps: &Capabilities) {
+        model.view_saved_positions = if let Some(Ok(curr_pos)) = &model.curr_pos {
+            model
+                .saved_positions
+                .nearest_neighbor_iter(&rtree_point(&curr_pos.coords))
+                .cloned()
+                .take(model.view_n_saved_positions)
+                .collect::<Vec<_>>()
+        } else {
+            model
+                .saved_positions
+                .iter()
+                .cloned()
+                .take(model.view_n_saved_positions)
+                .collect()
+        };
+        model.view_saved_positions_in_box = if let Some((min, max)) = model.view_box {
+            let mut positions = model
+                .saved_positions
+                .locate_in_envelope_intersecting(&rtree_box(min, max))
+                .filter(|x| in_box(x.pos.coords, min, max))
+                .cloned()
+                .collect::<Vec<_>>();
+            positions.sort_by(|a, b| a.name.cmp(&b.name));
+            positions
+        } else {
+            Vec::new()
+        };
+        model.view_saved_positions_within_radius = if let Some((center, radius)) = model.view_radius
+        {
+            let mut positions = saved_within_radius(&model.saved_positions, center, radius)
+                .cloned()
+                .collect::<Vec<_>>();
+            let distance = |x: &SavedPos| {
+                PLANET
+                    .distance(center.to_nvector(), x.pos.coords.to_nvector())
+                    .as_metres()
+            };
+            positions.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+            positions
+        } else {
+            Vec::new()
+        };
+        model.saved_position_clusters = match model.cluster_cell_size {
+            Some(cell_size) => clusters(
+                model.saved_positions.iter().map(|x| x.pos.coords),
+                cell_size,
+            ),
+            None => Vec::new(),
+        };
+        let viewed = model
+            .view_saved_positions
+            .iter()
+            .chain(&model.view_saved_positions_in_box)
+            .chain(&model.view_saved_positions_within_radius)
+            .map(|x| x.name.clone())
+            .collect::<Vec<_>>();
+        for name in viewed {
+            touch_saved_position(model, name);
+        }
+    }
+
+    /// Remove the least recently viewed saved positions until there are at most
+    /// `model.max_saved_positions`. Returns whether any position was removed.
+    fn evict_saved_positions(&self, model: &mut Model) -> bool {
+        let Some(max) = model.max_saved_positions else {
+            return false;
+        };
+        let n_evicted = model.saved_positions_names.len().saturating_sub(max);
+        if n_evicted == 0 {
+            return false;
+        }
+        let mut positions = model.saved_positions_names.values().collect::<Vec<_>>();
+        positions.sort_by_key(|x| {
+            (
+                model.saved_positions_last_viewed.get(&x.name).copied(),
+                x.timestamp,
+            )
+        });
+        let evicted = positions
+            .into_iter()
+            .take(n_evicted)
+            .map(|x| x.name.clone())
+            .collect::<Vec<_>>();
+        for name in &evicted {
+            remove_saved_position(model, name);
+        }
+        model.msg = Text::Evicted {
+            names: &evicted.join(", "),
+            max,
+        }
+        .translate(model.language);
+        true
+    }
+
+    /// Select recorded ways to show after the recorded ways have changed.
+    fn view_recorded_ways(&self, model: &mut Model, _caps: &Capabilities) {
+        model.recorded_way_segments = None;
+        self.select_recorded_ways(model);
+    }
+
+    /// Select recorded ways to show.
+    fn select_recorded_ways(&self, model: &mut Model) {
+        if let Some(way_sort) = model.way_sort {
+            let mut ways = model.recorded_ways.iter().collect::<Vec<_>>();
+            // Ways without any nodes are put last, and ties are ordered by name.
+            ways.sort_by(|(name_a, a), (name_b, b)| {
+                let start_a = a.way.nodes().first().map(|x| x.timestamp);
+                let start_b = b.way.nodes().first().map(|x| x.timestamp);
+                start_a
+                    .is_none()
+                    .cmp(&start_b.is_none())
+                    .then_with(|| match way_sort {
+                        WaySort::ByName => Ordering::Equal,
+                        WaySort::ByStartTimeAsc => start_a.cmp(&start_b),
+                        WaySort::ByStartTimeDesc => start_b.cmp(&start_a),
+                        WaySort::ByLengthDesc => b
+                            .way
+                            .length()
+                            .as_metres()
+                            .total_cmp(&a.way.length().as_metres()),
+                    })
+                    .then_with(|| name_a.cmp(name_b))
+            });
+            model.view_recorded_ways = ways
+                .into_iter()
+                .map(|(name, _)| name.clone())
+                .take(model.view_n_recorded_ways)
+                .collect();
+            return;
+        }
+        let Some(Ok(curr_pos)) = &model.curr_pos else {
+            model.view_recorded_ways = model
+                .recorded_ways
+                .keys()
+                .cloned()
+                .take(model.view_n_recorded_ways)
+                .collect();
+            model.view_recorded_ways.sort();
+            return;
+        };
+        // Select the ways with the nearest segments. Ways without any segments are put last.
+        let segments = model.recorded_way_segments.get_or_insert_with(|| {
+            RTree::bulk_load(
+                model
+                    .recorded_ways
+                    .iter()
+                    .flat_map(|(name, rec)| {
+                        rec.way.lines().map(|line| WaySegment {
+                            line,
+                            way: name.clone(),
+                        })
+                    })
+                    .collect(),
+            )
+        });
+        let mut ways = Vec::with_capacity(model.view_n_recorded_ways);
+        for segment in segments.nearest_neighbor_iter(&rtree_point(&curr_pos.coords)) {
+            if ways.len() >= model.view_n_recorded_ways {
+                break;
+            }
+            if !ways.contains(&segment.way) {
+                ways.push(segment.way.clone());
+            }
+        }
+        let mut rest = model
+            .recorded_ways
+            .keys()
+            .filter(|name| !ways.contains(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        rest.sort();
+        ways.extend(
+            rest.into_iter()
+                .take(model.view_n_recorded_ways.saturating_sub(ways.len())),
+        );
+        model.view_recorded_ways = ways;
+    }
+}
+
+/// The key in persistant storage for a saved way.
+fn recorded_way_key(name: &str) -> CompactString {
+    format_compact!("{RECORDED_WAY_KEY_PREFIX}{name}")
+}
+
+/// Make an event from the result of a storage operation, which is an error if it failed.
+fn storage_error<T>(res: Result<T, KeyValueError>, action: StorageAction) -> Event {
+    if let Err(e) = res {
+        Event::Error(AppError::Storage {
+            action,
+            error: e.to_compact_string(),
+        })
+    } else {
+        Event::None
+    }
+}
+
+/// Whether a write to persistant storage overwrote something else than what the app expected to be
+/// there. Nothing is expected if the app hasn't read or written the key before.
+fn is_overwrite(expected: Option<&[u8]>, previous: Option<&[u8]>) -> bool {
+    expected.is_some_and(|expected| previous != Some(expected))
+}
+
+/// Make a name unique by appending " (imported)" and possibly a number to it.
+fn unique_name(name: CompactString, exists: impl Fn(&CompactString) -> bool) -> CompactString {
+    if !exists(&name) {
+        return name;
+    }
+    let mut unique = format_compact!("{name} (imported)");
+    let mut n = 2;
+    while exists(&unique) {
+        unique = format_compact!("{name} (imported {n})");
+        n += 1;
+    }
+    unique
+}
+
+/// Mark a saved position as viewed now.
+fn touch_saved_position(model: &mut Model, name: CompactString) {
+    model.saved_positions_view_count += 1;
+    model
+        .saved_positions_last_viewed
+        .insert(name, model.saved_positions_view_count);
+}
+
+/// Replace a saved position in the r-tree with a changed version of it. Returns whether the old
+/// position was found.
+///
+/// The r-tree finds the position to remove by equality, and `SavedPos` is compared by all fields
+/// including the name. So the position must be removed with its value from before the change,
+/// or else nothing is removed and the old value remains in the r-tree.
+fn replace_in_rtree(rtree: &mut RTree<SavedPos>, old: &SavedPos, new: SavedPos) -> bool {
+    let removed = rtree.remove(old).is_some();
+    debug_assert!(removed, "{} is not in the r-tree", old.name);
+    rtree.insert(new);
+    removed
+}
+
+/// Remove a saved position and everything referring to it. Returns whether it existed.
+fn remove_saved_position(model: &mut Model, name: &str) -> bool {
+    let Some(pos) = model.saved_positions_names.remove(name) else {
+        return false;
+    };
+    model.saved_positions.remove(&pos);
+    model.geofences.remove(name);
+    model.saved_positions_last_viewed.remove(name);
+    if model.target.as_deref() == Some(name) {
+        model.target = None;
+    }
+    true
+}
+
+/// Save the current position under a name, or under the suggested name if the name is empty.
+fn save_curr_pos(model: &mut Model, name: CompactString) -> Result<(), AppError> {
+    let Some(Ok(geo)) = &model.curr_pos else {
+        return Err(AppError::NoCurrentPosition);
+    };
+    let name = match &model.suggested_name {
+        Some(suggested) if name.is_empty() => suggested.clone(),
+        _ => name,
+    };
+    if model.saved_positions_names.contains_key(&name) {
+        // Keep the suggested name so that the user can try again.
+        return Err(AppError::PosNameExists(name));
+    }
+    model.suggested_name = None;
+    let pos = SavedPos::new(name.clone(), geo);
+    model.saved_positions.insert(pos.clone());
+    model.saved_positions_names.insert(name.clone(), pos);
+    touch_saved_position(model, name);
+    Ok(())
+}
+
+/// Delete a saved position.
+fn del_saved_pos(model: &mut Model, name: &str) -> Result<(), AppError> {
+    if remove_saved_position(model, name) {
+        Ok(())
+    } else {
+        Err(AppError::PosNotFound(name.into()))
+    }
+}
+
+/// Save the recording since app start as a recorded way.
+fn save_all_positions(model: &mut Model, name: CompactString) -> Result<(), AppError> {
+    let Some(all_positions) = &model.all_positions else {
+        return Err(AppError::NoRecordedPositions);
+    };
+    if model.recorded_ways.contains_key(&name) {
+        return Err(AppError::WayNameExists(name));
+    }
+    model.recorded_ways.insert(name, all_positions.clone());
+    Ok(())
+}
+
+/// Save the positions recorded since app start between two timestamps (inclusive) as a way.
+fn save_way_range(
+    model: &mut Model,
+    name: CompactString,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<(), AppError> {
+    if start > end {
+        return Err(AppError::InvertedTimeRange);
+    }
+    let Some(all_positions) = &model.all_positions else {
+        return Err(AppError::NoRecordedPositions);
+    };
+    if model.recorded_ways.contains_key(&name) {
+        return Err(AppError::WayNameExists(name));
+    }
+    let nodes = all_positions.get_range(start, end);
+    if nodes.is_empty() {
+        return Err(AppError::EmptyTimeRange);
+    }
+    let mut rec = RecordedWay::new();
+    for node in nodes {
+        rec.add(node);
+    }
+    model.recorded_ways.insert(name, rec);
+    Ok(())
+}
+
+/// Merge two recorded ways into a new way, see `Event::MergeWays`.
+fn merge_ways(
+    model: &mut Model,
+    first: &str,
+    second: &str,
+    into: CompactString,
+    delete_sources: bool,
+) -> Result<(), AppError> {
+    let (Some(a), Some(b)) = (
+        model.recorded_ways.get(first),
+        model.recorded_ways.get(second),
+    ) else {
+        let missing = if model.recorded_ways.contains_key(first) {
+            second
+        } else {
+            first
+        };
+        return Err(AppError::WayNotFound(missing.into()));
+    };
+    if model.recorded_ways.contains_key(&into) {
+        return Err(AppError::WayNameExists(into));
+    }
+    let merged = a.merge(b);
+    if delete_sources {
+        model.recorded_ways.remove(first);
+        model.recorded_ways.remove(second);
+    }
+    model.recorded_ways.insert(into, merged);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crux_core::testing::AppTester;
+    use crux_geolocation::GeoRequest;
+    use crux_kv::{value::Value, KeyValueOperation, KeyValueResponse, KeyValueResult};
+    use crux_time::TimeRequest;
+
+    use super::geo_traits::{Coords, RecordedPos};
+    use super::view_types::ViewMetres;
+    use super::*;
+
+    /// Create a `GeoInfo` at some coordinates (in degrees) and a timestamp (in seconds).
+    fn geo(latitude: f64, longitude: f64, secs: i64) -> GeoInfo {
+        GeoInfo {
+            timestamp: DateTime::from_timestamp(secs, 0).unwrap(),
+            coords: LatLong::from_degrees(latitude, longitude),
+            altitude: None,
+            accuracy: None,
+            altitude_accuracy: None,
+            bearing: None,
+            volocity: None,
+            source: None,
+            satellites: None,
+        }
+    }
+
+    /// Get all key-value operations among some effects.
+    fn storage_operations(effects: Vec<Effect>) -> Vec<KeyValueOperation> {
+        effects
+            .into_iter()
+            .filter_map(|effect| match effect {
+                Effect::KeyValue(req) => Some(req.operation),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get all geolocation requests among some effects.
+    fn geolocation_operations(effects: Vec<Effect>) -> Vec<GeoRequest> {
+        effects
+            .into_iter()
+            .filter_map(|effect| match effect {
+                Effect::Geolocation(req) => Some(req.operation),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get whether the screen should be kept awake for all screen wake requests among some
+    /// effects.
+    fn screen_wake_operations(effects: Vec<Effect>) -> Vec<bool> {
+        effects
+            .into_iter()
+            .filter_map(|effect| match effect {
+                Effect::ScreenWake(req) => Some(req.operation.enable),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get all notifications among some effects.
+    fn notify_operations(effects: Vec<Effect>) -> Vec<crate::NotifyUserOperation> {
+        effects
+            .into_iter()
+            .filter_map(|effect| match effect {
+                Effect::Notify(req) => Some(req.operation),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The names of the saved positions in the view.
+    fn viewed_saved_positions(model: &Model) -> Vec<CompactString> {
+        ViewModel::new(model)
+            .saved_positions
+            .into_iter()
+            .map(|x| x.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_save_pos_flow() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let update = app.update(Event::StartGeolocation, &mut model);
+        assert!(update
+            .effects
+            .iter()
+            .any(|effect| matches!(effect, Effect::Geolocation(_))));
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 2.0, 0))), &mut model);
+        let _update = app.update(Event::ViewNSavedPositions(5), &mut model);
+        assert!(viewed_saved_positions(&model).is_empty());
+
+        let _update = app.update(Event::SaveCurrPos("Home".into()), &mut model);
+        assert_eq!(viewed_saved_positions(&model), ["Home"]);
+        let update = app.update(Event::FlushSavedPositions, &mut model);
+        let operations = storage_operations(update.effects);
+        assert!(matches!(
+            &operations[..],
+            [KeyValueOperation::Set { key, .. }] if key == SAVED_POSITIONS_KEY
+        ));
+    }
+
+    #[test]
+    fn test_view_n_and_delete_flow() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::StartGeolocation, &mut model);
+        let _update = app.update(Event::ViewNSavedPositions(5), &mut model);
+        for (i, name) in ["Far", "Middle", "Near"].into_iter().enumerate() {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(i as f64, 0.0, i as i64))),
+                &mut model,
+            );
+            let _update = app.update(Event::SaveCurrPos(name.into()), &mut model);
+        }
+        // Nearest first.
+        assert_eq!(viewed_saved_positions(&model), ["Near", "Middle", "Far"]);
+        let _update = app.update(Event::ViewNSavedPositions(2), &mut model);
+        assert_eq!(viewed_saved_positions(&model), ["Near", "Middle"]);
+
+        let _update = app.update(Event::DelSavedPos("Near".into()), &mut model);
+        assert_eq!(model.msg, "Near has been removed.");
+        assert_eq!(viewed_saved_positions(&model), ["Middle", "Far"]);
+        let update = app.update(Event::FlushSavedPositions, &mut model);
+        assert_eq!(storage_operations(update.effects).len(), 1);
+
+        let _update = app.update(Event::ViewNSavedPositions(0), &mut model);
+        assert!(viewed_saved_positions(&model).is_empty());
+    }
+
+    #[test]
+    fn test_render_only_on_change() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let renders = |effects: Vec<Effect>| {
+            effects
+                .iter()
+                .filter(|effect| matches!(effect, Effect::Render(_)))
+                .count()
+        };
+        let update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 2.0, 0))), &mut model);
+        assert_eq!(renders(update.effects), 1);
+        // The view model built for the fingerprint is the one which is viewed.
+        assert!(app.view(&model) == ViewModel::new(&model));
+        assert_eq!(renders(app.update(Event::None, &mut model).effects), 0);
+        let update = app.update(Event::SetUnitSystem(UnitSystem::Metric), &mut model);
+        assert_eq!(renders(update.effects), 0);
+        let update = app.update(Event::SetUnitSystem(UnitSystem::Imperial), &mut model);
+        assert_eq!(renders(update.effects), 1);
+    }
+
+    #[test]
+    fn test_stop_geolocation_pause() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::StartGeolocation, &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(Event::StopGeolocation, &mut model);
+        let _update = app.update(Event::StartGeolocation, &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 1.0, 1))), &mut model);
+        assert_eq!(model.all_positions.unwrap().way.nodes().len(), 2);
+        assert!(model.recorded_ways.is_empty());
+    }
+
+    #[test]
+    fn test_suspend_and_resume() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        // Nothing happens if geolocation isn't active.
+        assert!(
+            geolocation_operations(app.update(Event::AppSuspended, &mut model).effects).is_empty()
+        );
+        assert!(
+            geolocation_operations(app.update(Event::AppResumed, &mut model).effects).is_empty()
+        );
+        assert!(!model.geolocation_active);
+
+        let _update = app.update(Event::StartGeolocation, &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let update = app.update(Event::AppSuspended, &mut model);
+        assert_eq!(
+            geolocation_operations(update.effects),
+            [GeoRequest::ClearWatch]
+        );
+        assert!(!model.geolocation_active);
+        let update = app.update(Event::AppResumed, &mut model);
+        assert!(matches!(
+            geolocation_operations(update.effects)[..],
+            [GeoRequest::WatchPosition(_)]
+        ));
+        assert!(model.geolocation_active);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 1.0, 1))), &mut model);
+        assert_eq!(model.all_positions.as_ref().unwrap().way.nodes().len(), 2);
+
+        // Geolocation which was stopped before the app was suspended isn't resumed.
+        let _update = app.update(Event::StopGeolocation, &mut model);
+        let _update = app.update(Event::AppSuspended, &mut model);
+        assert!(
+            geolocation_operations(app.update(Event::AppResumed, &mut model).effects).is_empty()
+        );
+        assert!(!model.geolocation_active);
+    }
+
+    #[test]
+    fn test_screen_wake() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let update = app.update(Event::StartGeolocation, &mut model);
+        assert_eq!(screen_wake_operations(update.effects), [true]);
+        // Nothing is sent if nothing changes.
+        let update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        assert!(screen_wake_operations(update.effects).is_empty());
+        let update = app.update(Event::StartGeolocation, &mut model);
+        assert!(screen_wake_operations(update.effects).is_empty());
+        let update = app.update(Event::StopGeolocation, &mut model);
+        assert_eq!(screen_wake_operations(update.effects), [false]);
+        let update = app.update(Event::StopGeolocation, &mut model);
+        assert!(screen_wake_operations(update.effects).is_empty());
+
+        let _update = app.update(Event::StartGeolocation, &mut model);
+        let update = app.update(Event::AppSuspended, &mut model);
+        assert_eq!(screen_wake_operations(update.effects), [false]);
+        let update = app.update(Event::AppResumed, &mut model);
+        assert_eq!(screen_wake_operations(update.effects), [true]);
+    }
+
+    #[test]
+    fn test_permission_denied_clears_watch() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::StartGeolocation, &mut model);
+        let update = app.update(Event::GeolocationUpdate(Err(GeoError::Timeout)), &mut model);
+        assert!(geolocation_operations(update.effects).is_empty());
+        assert!(model.geolocation_active);
+
+        let update = app.update(
+            Event::GeolocationUpdate(Err(GeoError::PermissionDenied)),
+            &mut model,
+        );
+        assert_eq!(
+            geolocation_operations(update.effects),
+            [GeoRequest::ClearWatch]
+        );
+        assert!(!model.geolocation_active);
+        assert_eq!(model.curr_pos, Some(Err(GeoError::PermissionDenied)));
+        assert!(model.msg.starts_with("Error: Permission"));
+    }
+
+    #[test]
+    fn test_consecutive_timeouts() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::SetMaxConsecutiveTimeouts(2), &mut model);
+        for _ in 0..2 {
+            let _update = app.update(Event::GeolocationUpdate(Err(GeoError::Timeout)), &mut model);
+            assert!(model.msg.is_empty());
+        }
+        let _update = app.update(Event::GeolocationUpdate(Err(GeoError::Timeout)), &mut model);
+        assert!(model.msg.starts_with("GPS is taking unusually long"));
+
+        // A position resets the count.
+        model.msg.clear();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        assert_eq!(model.consecutive_timeouts, 0);
+        for _ in 0..2 {
+            let _update = app.update(Event::GeolocationUpdate(Err(GeoError::Timeout)), &mut model);
+        }
+        assert!(model.msg.is_empty());
+    }
+
+    #[test]
+    fn test_stop_geolocation_finalize() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(
+            Event::SetStopBehavior(StopBehavior::Finalize { auto_save: false }),
+            &mut model,
+        );
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(Event::StopGeolocation, &mut model);
+        assert!(model.all_positions.is_none());
+        assert!(model.recorded_ways.is_empty());
+
+        let _update = app.update(
+            Event::SetStopBehavior(StopBehavior::Finalize { auto_save: true }),
+            &mut model,
+        );
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 1.0, 1))), &mut model);
+        let _update = app.update(Event::StopGeolocation, &mut model);
+        assert!(model.all_positions.is_none());
+        assert_eq!(model.recorded_ways.len(), 1);
+        let way = model.recorded_ways.values().next().unwrap();
+        assert_eq!(way.way.nodes().len(), 2);
+    }
+
+    #[test]
+    fn test_import_gpx_name_collisions() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let gpx = br#"<gpx version="1.1">
+  <wpt lat="1" lon="2"><name>Home</name></wpt>
+  <trk><name>Walk</name><trkseg>
+    <trkpt lat="1" lon="2"><time>2024-05-01T12:00:00Z</time></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+        for _ in 0..3 {
+            let _update = app.update(Event::ImportGpx(gpx.to_vec()), &mut model);
+        }
+        let mut positions = model
+            .saved_positions_names
+            .keys()
+            .map(CompactString::as_str)
+            .collect::<Vec<_>>();
+        positions.sort();
+        assert_eq!(positions, ["Home", "Home (imported 2)", "Home (imported)"]);
+        let mut ways = model
+            .recorded_ways
+            .keys()
+            .map(CompactString::as_str)
+            .collect::<Vec<_>>();
+        ways.sort();
+        assert_eq!(ways, ["Walk", "Walk (imported 2)", "Walk (imported)"]);
+        assert_eq!(model.saved_positions.size(), 3);
+
+        let untimed = br#"<gpx version="1.1">
+  <trk><trkseg><trkpt lat="1" lon="2"/></trkseg></trk>
+</gpx>"#;
+        let _update = app.update(Event::ImportGpx(untimed.to_vec()), &mut model);
+        assert_eq!(
+            model.msg,
+            "Imported 0 positions and 0 ways. 1 track without timestamps was skipped."
+        );
+        assert_eq!(model.recorded_ways.len(), 3);
+
+        let _update = app.update(Event::ImportGpx(b"<gpx>".to_vec()), &mut model);
+        assert!(model.msg.starts_with("Error"));
+    }
+
+    #[test]
+    fn test_rename_saved_pos() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 2.0, 0))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("A".into()), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(3.0, 4.0, 1))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("B".into()), &mut model);
+        let a = model.saved_positions_names["A"].clone();
+
+        let _update = app.update(
+            Event::RenameSavedPos {
+                old: "A".into(),
+                new: "B".into(),
+            },
+            &mut model,
+        );
+        assert!(model.msg.starts_with("Error"));
+        assert!(model.saved_positions_names.contains_key("A"));
+
+        let _update = app.update(
+            Event::RenameSavedPos {
+                old: "A".into(),
+                new: "C".into(),
+            },
+            &mut model,
+        );
+        assert!(!model.saved_positions_names.contains_key("A"));
+        let c = &model.saved_positions_names["C"];
+        assert_eq!(c.name, "C");
+        assert_eq!(c.pos, a.pos);
+        assert_eq!(c.timestamp, a.timestamp);
+        assert_eq!(model.saved_positions.size(), 2);
+        assert!(model.saved_positions.iter().any(|x| x == c));
+        assert!(!model.saved_positions.iter().any(|x| x.name == "A"));
+    }
+
+    #[test]
+    fn test_replace_in_rtree() {
+        let a = SavedPos::new("A".into(), &geo(1.0, 2.0, 0));
+        let b = SavedPos::new("B".into(), &geo(1.0, 2.0, 0));
+        let mut rtree = RTree::bulk_load(vec![a.clone(), b.clone()]);
+        let renamed = SavedPos {
+            name: "C".into(),
+            ..a.clone()
+        };
+        // Removing the renamed position fails, since it isn't equal to the stored one.
+        assert_eq!(rtree.remove(&renamed), None);
+        assert_eq!(rtree.size(), 2);
+
+        assert!(replace_in_rtree(&mut rtree, &a, renamed.clone()));
+        assert_eq!(rtree.size(), 2);
+        let mut names = rtree.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["B", "C"]);
+        assert!(rtree.iter().any(|x| *x == renamed));
+    }
+
+    #[test]
+    fn test_edit_saved_pos() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 1.0, 0))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("A".into()), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 2.0, 1))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("B".into()), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 2))), &mut model);
+        let _update = app.update(Event::ViewNSavedPositions(2), &mut model);
+        let names = |model: &Model| {
+            model
+                .view_saved_positions
+                .iter()
+                .map(|x| x.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&model), ["A", "B"]);
+
+        let _update = app.update(
+            Event::EditSavedPos {
+                name: "B".into(),
+                coords: LatLong::from_degrees(0.0, 0.5),
+                altitude: Some(Length::from_metres(10.0)),
+            },
+            &mut model,
+        );
+        assert_eq!(names(&model), ["B", "A"]);
+        let b = &model.saved_positions_names["B"];
+        assert_eq!(b.pos.coords, LatLong::from_degrees(0.0, 0.5));
+        assert_eq!(b.pos.altitude, Some(Length::from_metres(10.0)));
+        assert_eq!(b.timestamp, DateTime::from_timestamp(1, 0).unwrap());
+        assert_eq!(model.saved_positions.size(), 2);
+    }
+
+    #[test]
+    fn test_start_new_track() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 1.0, 1))), &mut model);
+        let _update = app.update(Event::SaveAllPositions("Before".into()), &mut model);
+
+        let _update = app.update(Event::StartNewTrack, &mut model);
+        assert!(model.all_positions.as_ref().unwrap().way.nodes().is_empty());
+        assert_eq!(model.recorded_ways["Before"].way.nodes().len(), 2);
+
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 1.0, 2))), &mut model);
+        let _update = app.update(Event::SaveAllPositions("After".into()), &mut model);
+        assert_eq!(model.recorded_ways["Before"].way.nodes().len(), 2);
+        let after = model.recorded_ways["After"].way.nodes();
+        assert_eq!(after.len(), 1);
+        assert_eq!(
+            after[0].timestamp(),
+            DateTime::from_timestamp(2, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_named_recordings() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::StartNamedRecording("Segment".into()), &mut model);
+        let _update = app.update(Event::StartNamedRecording("Segment".into()), &mut model);
+        assert_eq!(model.msg, "Error: The name Segment is already in use.");
+        let _update = app.update(
+            Event::StartNamedRecording(ALL_POSITIONS_NAME.into()),
+            &mut model,
+        );
+        assert_eq!(
+            model.msg,
+            format!("Error: The name {ALL_POSITIONS_NAME} is already in use.")
+        );
+
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 2.0, 0))), &mut model);
+        let _update = app.update(Event::StopNamedRecording("Segment".into()), &mut model);
+        assert_eq!(model.msg, "Segment has been saved.");
+        assert_eq!(model.recorded_ways["Segment"].way.nodes().len(), 1);
+        let _update = app.update(Event::StopNamedRecording("Segment".into()), &mut model);
+        assert_eq!(model.msg, "Error: Way Segment does not exist.");
+    }
+
+    #[test]
+    fn test_way_sort() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for (name, secs, long) in [("B", 0, 0.1), ("A", 10, 0.01), ("C", 20, 0.05)] {
+            let _update = app.update(Event::StartNewTrack, &mut model);
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(0.0, 0.0, secs))),
+                &mut model,
+            );
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(0.0, long, secs + 1))),
+                &mut model,
+            );
+            let _update = app.update(Event::SaveAllPositions(name.into()), &mut model);
+        }
+        model
+            .recorded_ways
+            .insert("Empty".into(), RecordedWay::new());
+        let _update = app.update(Event::ViewNRecordedWays(10), &mut model);
+
+        for (way_sort, expected) in [
+            (WaySort::ByName, ["A", "B", "C", "Empty"]),
+            (WaySort::ByStartTimeAsc, ["B", "A", "C", "Empty"]),
+            (WaySort::ByStartTimeDesc, ["C", "A", "B", "Empty"]),
+            (WaySort::ByLengthDesc, ["B", "C", "A", "Empty"]),
+        ] {
+            let _update = app.update(Event::SetWaySort(Some(way_sort)), &mut model);
+            assert_eq!(model.view_recorded_ways, expected, "{way_sort:?}");
+        }
+
+        // Only the newest ways are shown.
+        let _update = app.update(
+            Event::SetWaySort(Some(WaySort::ByStartTimeDesc)),
+            &mut model,
+        );
+        let _update = app.update(Event::ViewNRecordedWays(2), &mut model);
+        assert_eq!(model.view_recorded_ways, ["C", "A"]);
+    }
+
+    #[test]
+    fn test_view_nearest_recorded_ways() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.01, 1))), &mut model);
+        let _update = app.update(Event::SaveAllPositions("Far".into()), &mut model);
+        let _update = app.update(Event::StartNewTrack, &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 0.0, 2))), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 0.01, 3))), &mut model);
+        let _update = app.update(Event::SaveAllPositions("Near".into()), &mut model);
+        let _update = app.update(
+            Event::GeolocationUpdate(Ok(geo(1.001, 0.005, 4))),
+            &mut model,
+        );
+
+        let _update = app.update(Event::ViewNRecordedWays(1), &mut model);
+        assert_eq!(model.view_recorded_ways, ["Near"]);
+        let _update = app.update(Event::ViewNRecordedWays(2), &mut model);
+        assert_eq!(model.view_recorded_ways, ["Near", "Far"]);
+        assert!(model.recorded_way_segments.is_some());
+
+        // The segments are rebuilt when the ways change.
+        let _update = app.update(Event::DelRecordedWay("Near".into()), &mut model);
+        assert_eq!(model.view_recorded_ways, ["Far"]);
+        let _update = app.update(Event::ViewNRecordedWays(2), &mut model);
+        assert_eq!(model.view_recorded_ways, ["Far"]);
+        assert_eq!(model.recorded_way_segments.as_ref().unwrap().size(), 1);
+    }
+
+    #[test]
+    fn test_view_saved_positions_in_box() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for (name, lat, long) in [
+            ("Inside", 10.0, 20.0),
+            ("Corner", 10.9999, 20.9999),
+            ("North", 11.5, 20.0),
+            ("East", 10.0, 21.5),
+            ("Across", 0.0, 179.5),
+        ] {
+            let _update = app.update(Event::GeolocationUpdate(Ok(geo(lat, long, 0))), &mut model);
+            let _update = app.update(Event::SaveCurrPos(name.into()), &mut model);
+        }
+        let names = |model: &Model| {
+            model
+                .view_saved_positions_in_box
+                .iter()
+                .map(|x| x.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert!(names(&model).is_empty());
+        let _update = app.update(
+            Event::ViewSavedPositionsInBox {
+                min: LatLong::from_degrees(9.0, 19.0),
+                max: LatLong::from_degrees(11.0, 21.0),
+            },
+            &mut model,
+        );
+        assert_eq!(names(&model), ["Corner", "Inside"]);
+        let _update = app.update(
+            Event::ViewSavedPositionsInBox {
+                min: LatLong::from_degrees(-1.0, 179.0),
+                max: LatLong::from_degrees(1.0, -179.0),
+            },
+            &mut model,
+        );
+        assert_eq!(names(&model), ["Across"]);
+    }
+
+    #[test]
+    fn test_view_saved_within_radius() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        // 0.001° of latitude is about 111.2 m.
+        for (name, lat) in [("Inside", 0.00099), ("Center", 0.0), ("Outside", 0.00101)] {
+            let _update = app.update(Event::GeolocationUpdate(Ok(geo(lat, 0.0, 0))), &mut model);
+            let _update = app.update(Event::SaveCurrPos(name.into()), &mut model);
+        }
+        let radius = PLANET.distance(
+            LatLong::from_degrees(0.0, 0.0).to_nvector(),
+            LatLong::from_degrees(0.001, 0.0).to_nvector(),
+        );
+        let _update = app.update(
+            Event::ViewSavedWithinRadius {
+                center: LatLong::from_degrees(0.0, 0.0),
+                radius,
+            },
+            &mut model,
+        );
+        let names = model
+            .view_saved_positions_within_radius
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Center", "Inside"]);
+        assert_eq!(
+            ViewModel::new(&model).saved_positions_within_radius.len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_is_overwrite() {
+        assert!(!is_overwrite(None, None));
+        assert!(!is_overwrite(None, Some(b"a")));
+        assert!(!is_overwrite(Some(b"a"), Some(b"a")));
+        assert!(is_overwrite(Some(b"a"), Some(b"b")));
+        assert!(is_overwrite(Some(b"a"), None));
+    }
+
+    #[test]
+    fn test_detect_overwritten_saved_positions() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        // Save a position and answer the write of the saved positions as if it overwrote
+        // `previous`. Returns the written value.
+        let save = |model: &mut Model, name: &str, previous: Vec<u8>| {
+            let _update = app.update(Event::SaveCurrPos(name.into()), model);
+            let update = app.update(Event::FlushSavedPositions, model);
+            let mut request = update
+                .effects
+                .into_iter()
+                .find_map(|effect| match effect {
+                    Effect::KeyValue(request) => Some(request),
+                    _ => None,
+                })
+                .expect("The saved positions should be saved.");
+            let KeyValueOperation::Set { value, .. } = request.operation.clone() else {
+                panic!("The saved positions should be set.");
+            };
+            let update = app
+                .resolve(
+                    &mut request,
+                    KeyValueResult::Ok {
+                        response: KeyValueResponse::Set {
+                            previous: Value::Bytes(previous),
+                        },
+                    },
+                )
+                .unwrap();
+            for event in update.events {
+                let _update = app.update(event, model);
+            }
+            value
+        };
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        // Nothing was expected to be overwritten the first time.
+        let first = save(&mut model, "Home", b"other".to_vec());
+        assert!(model.msg.is_empty());
+
+        // Another tab has changed the saved positions since the first save.
+        save(&mut model, "Away", b"other".to_vec());
+        assert!(model.msg.starts_with("Warning:"));
+
+        // No warning if the previous value is what the app wrote.
+        model.msg.clear();
+        let expected = model.saved_positions_in_storage.clone().unwrap();
+        save(&mut model, "Third", expected);
+        assert!(model.msg.is_empty());
+        assert_ne!(first, model.saved_positions_in_storage.unwrap());
+    }
+
+    /// Resolve all timers among some effects and send the resulting events until no more timers
+    /// are started. Returns the number of resolved timers.
+    fn run_timers(
+        app: &AppTester<GeoApp, Effect>,
+        model: &mut Model,
+        effects: Vec<Effect>,
+    ) -> usize {
+        let mut timers = effects
+            .into_iter()
+            .filter_map(|effect| match effect {
+                Effect::Time(request) => Some(request),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut n = 0;
+        while let Some(mut request) = timers.pop() {
+            let TimeRequest::NotifyAfter { id, .. } = request.operation else {
+                continue;
+            };
+            n += 1;
+            let update = app
+                .resolve(&mut request, TimeResponse::DurationElapsed { id })
+                .unwrap();
+            for event in update.events {
+                timers.extend(
+                    app.update(event, model).effects.into_iter().filter_map(
+                        |effect| match effect {
+                            Effect::Time(request) => Some(request),
+                            _ => None,
+                        },
+                    ),
+                );
+            }
+        }
+        n
+    }
+
+    #[test]
+    fn test_cancel_current_position() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let position = || {
+            crux_geolocation::GeoResponse::position(
+                LatLong::from_degrees(1.0, 2.0),
+                None,
+                None,
+                DateTime::from_timestamp(0, 0).unwrap(),
+            )
+        };
+        let request = |update: crux_core::testing::Update<Effect, Event>| {
+            update
+                .effects
+                .into_iter()
+                .find_map(|effect| match effect {
+                    Effect::Geolocation(req) => Some(req),
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        let mut req = request(app.update(Event::GetCurrentPosition, &mut model));
+        assert!(matches!(req.operation, GeoRequest::GetCurrentPosition(_)));
+        let update = app.resolve(&mut req, position()).unwrap();
+        for event in update.events {
+            let _update = app.update(event, &mut model);
+        }
+        assert!(matches!(model.curr_pos, Some(Ok(_))));
+        assert!(model.current_position_request.is_none());
+
+        model.curr_pos = None;
+        let mut req = request(app.update(Event::GetCurrentPosition, &mut model));
+        let cancel = request(app.update(Event::CancelCurrentPosition, &mut model));
+        assert_eq!(cancel.operation, GeoRequest::CancelCurrentPosition);
+        // A response after the cancellation is ignored.
+        let update = app.resolve(&mut req, position()).unwrap();
+        assert!(update.events.is_empty());
+        assert_eq!(model.curr_pos, None);
+    }
+
+    #[test]
+    fn test_replay_way() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for i in 0..5 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(0.0, 0.001 * i as f64, i))),
+                &mut model,
+            );
+        }
+        let _update = app.update(Event::SaveAllPositions("Walk".into()), &mut model);
+        let _update = app.update(Event::StartNewTrack, &mut model);
+
+        let update = app.update(
+            Event::ReplayWay {
+                name: "Walk".into(),
+                speed_factor: 2.0,
+            },
+            &mut model,
+        );
+        // The first position is sent at once and the others after a timer each.
+        assert_eq!(model.all_positions.as_ref().unwrap().way.nodes().len(), 1);
+        assert_eq!(run_timers(&app, &mut model, update.effects), 4);
+        assert_eq!(model.msg, "The replay of Walk has finished.");
+        let timestamps = model
+            .all_positions
+            .as_ref()
+            .unwrap()
+            .way
+            .nodes()
+            .iter()
+            .map(|x| x.timestamp.timestamp_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps, [0, 500, 1000, 1500, 2000]);
+        let Some(Ok(curr_pos)) = &model.curr_pos else {
+            panic!("The last replayed position should be the current position.");
+        };
+        assert!((curr_pos.coords.longitude().as_degrees() - 0.004).abs() < 1e-9);
+
+        // A stopped replay ignores its timers.
+        let _update = app.update(Event::StartNewTrack, &mut model);
+        let update = app.update(
+            Event::ReplayWay {
+                name: "Walk".into(),
+                speed_factor: 1.0,
+            },
+            &mut model,
+        );
+        let _update = app.update(Event::StopReplay, &mut model);
+        assert_eq!(run_timers(&app, &mut model, update.effects), 1);
+        assert_eq!(model.all_positions.as_ref().unwrap().way.nodes().len(), 1);
+
+        // A too small speed factor is rejected.
+        let _update = app.update(
+            Event::ReplayWay {
+                name: "Walk".into(),
+                speed_factor: 1e-300,
+            },
+            &mut model,
+        );
+        assert!(
+            model
+                .msg
+                .starts_with("Error: The speed factor must be at least 0.01, not "),
+            "{}",
+            model.msg
+        );
+        assert!(model.replay.is_none());
+
+        // Replay and geolocation are mutually exclusive.
+        let _update = app.update(Event::StartGeolocation, &mut model);
+        let _update = app.update(
+            Event::ReplayWay {
+                name: "Walk".into(),
+                speed_factor: 1.0,
+            },
+            &mut model,
+        );
+        assert_eq!(
+            model.msg,
+            "Error: Geolocation must be stopped to replay a way."
+        );
+        assert!(model.replay.is_none());
+
+        // The errors follow the language.
+        let _update = app.update(Event::SetLanguage(Lang::Swedish), &mut model);
+        let _update = app.update(
+            Event::ReplayWay {
+                name: "Walk".into(),
+                speed_factor: 1.0,
+            },
+            &mut model,
+        );
+        assert_eq!(
+            model.msg,
+            "Fel: Positioneringen måste stoppas för att spela upp ett spår."
+        );
+    }
+
+    #[test]
+    fn test_replayed_time() {
+        let replay = Replay {
+            name: "Walk".into(),
+            nodes: vec![PosWithTimestamp::from(&geo(0.0, 0.0, 0))],
+            next: 0,
+            speed_factor: MIN_REPLAY_SPEED_FACTOR,
+            start: DateTime::UNIX_EPOCH,
+        };
+        assert_eq!(
+            replay.replayed_time(DateTime::from_timestamp(1, 0).unwrap()),
+            DateTime::from_timestamp(100, 0).unwrap()
+        );
+        // Too far in the future.
+        assert_eq!(
+            replay.replayed_time(DateTime::<Utc>::MAX_UTC),
+            DateTime::<Utc>::MAX_UTC
+        );
+    }
+    #[test]
+    fn test_debounce_saved_positions() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let mut effects = Vec::new();
+        for i in 0..10 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(i as f64, 0.0, i))),
+                &mut model,
+            );
+            let update = app.update(Event::SaveCurrPos(format_compact!("Pos {i}")), &mut model);
+            effects.extend(update.effects);
+        }
+        let mut timers = effects
+            .into_iter()
+            .filter_map(|effect| match effect {
+                Effect::KeyValue(_) => panic!("Nothing should be written before the timer."),
+                Effect::Time(request) => Some(request),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(timers.len(), 1);
+        let TimeRequest::NotifyAfter { id, .. } = timers[0].operation else {
+            panic!("A timer should be started.");
+        };
+        let update = app
+            .resolve(&mut timers[0], TimeResponse::DurationElapsed { id })
+            .unwrap();
+        let mut operations = Vec::new();
+        for event in update.events {
+            operations.extend(storage_operations(app.update(event, &mut model).effects));
+        }
+        let [KeyValueOperation::Set { key, value }] = &operations[..] else {
+            panic!("Expected exactly one set operation");
+        };
+        assert_eq!(key, SAVED_POSITIONS_KEY);
+        let (_, names): (RTree<SavedPos>, HashMap<CompactString, SavedPos>) =
+            decode(value, false, migrate_saved_positions).unwrap();
+        assert_eq!(names.len(), 10);
+
+        // Pending changes are written when the app is suspended.
+        let _update = app.update(Event::SaveCurrPos("Last".into()), &mut model);
+        let update = app.update(Event::AppSuspended, &mut model);
+        assert_eq!(storage_operations(update.effects).len(), 1);
+        let update = app.update(Event::FlushSavedPositions, &mut model);
+        assert!(storage_operations(update.effects).is_empty());
+    }
+
+    #[test]
+    fn test_dedup_saved_positions() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        // About 1 m apart.
+        for (name, lat, secs) in [("B", 0.00001, 1), ("A", 0.0, 0), ("C", 0.00002, 2)] {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(lat, 0.0, secs))),
+                &mut model,
+            );
+            let _update = app.update(Event::SaveCurrPos(name.into()), &mut model);
+        }
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 0.0, 3))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Far".into()), &mut model);
+
+        let _update = app.update(
+            Event::DedupSavedPositions(Length::from_metres(5.0)),
+            &mut model,
+        );
+        assert_eq!(model.msg, "2 duplicate positions have been removed.");
+        let mut names = model
+            .saved_positions_names
+            .keys()
+            .map(CompactString::as_str)
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["A", "Far"]);
+        assert_eq!(model.saved_positions.size(), 2);
+    }
+
+    #[test]
+    fn test_max_saved_positions() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::SetMaxSavedPositions(Some(2)), &mut model);
+        for (name, lat, secs) in [("A", 0.0, 0), ("B", 1.0, 1)] {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(lat, 0.0, secs))),
+                &mut model,
+            );
+            let _update = app.update(Event::SaveCurrPos(name.into()), &mut model);
+        }
+        // View A, which is the nearest, so that B is the least recently viewed.
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 2))), &mut model);
+        let _update = app.update(Event::ViewNSavedPositions(1), &mut model);
+        let _update = app.update(Event::ViewNSavedPositions(0), &mut model);
+        let _update = app.update(Event::SelectTarget("B".into()), &mut model);
+
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(2.0, 0.0, 3))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("C".into()), &mut model);
+        assert_eq!(
+            model.msg,
+            "B removed since at most 2 positions may be saved."
+        );
+        let mut names = model
+            .saved_positions_names
+            .keys()
+            .map(CompactString::as_str)
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["A", "C"]);
+        assert_eq!(model.saved_positions.size(), 2);
+        assert!(model.saved_positions.iter().all(|x| x.name != "B"));
+        assert_eq!(model.target, None);
+
+        // Lowering the limit evicts A, which was viewed before C was saved.
+        let _update = app.update(Event::SetMaxSavedPositions(Some(1)), &mut model);
+        assert_eq!(
+            model.msg,
+            "A removed since at most 1 position may be saved."
+        );
+        assert_eq!(
+            model
+                .saved_positions
+                .iter()
+                .map(|x| &x.name)
+                .collect::<Vec<_>>(),
+            ["C"]
+        );
+    }
+
+    #[test]
+    fn test_migrate_recorded_ways() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let mut rec = RecordedWay::new();
+        rec.add(&geo(0.0, 0.0, 0));
+        // Before `RecordedWay::meta` was added.
+        let legacy = HashMap::from([(CompactString::from("Walk"), (rec.way.clone(),))]);
+        let update = app.update(
+            Event::SetData {
+                res: Ok(Some(bincode::serialize(&legacy).unwrap())),
+                key: RECORDED_WAYS_KEY.into(),
+            },
+            &mut model,
+        );
+        assert_eq!(model.recorded_ways["Walk"], rec);
+        let mut sets = Vec::new();
+        let mut deletes = Vec::new();
+        for operation in storage_operations(update.effects) {
+            match operation {
+                KeyValueOperation::Set { key, value } => sets.push((key, value)),
+                KeyValueOperation::Delete { key } => deletes.push(key),
+                _ => (),
+            }
+        }
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].0, "recorded_ways/Walk");
+        assert_eq!(
+            decode::<RecordedWay>(&sets[0].1, false, migrate_recorded_way).unwrap(),
+            rec
+        );
+        assert_eq!(sets[1].0, RECORDED_WAYS_INDEX_KEY);
+        assert_eq!(
+            decode::<BTreeSet<CompactString>>(&sets[1].1, false, migrate_unchanged).unwrap(),
+            BTreeSet::from(["Walk".into()])
+        );
+        assert_eq!(deletes, [RECORDED_WAYS_KEY]);
+
+        // The migrated data is loaded with the new keys.
+        let mut model = Model::default();
+        let update = app.update(
+            Event::SetData {
+                res: Ok(Some(sets[1].1.clone())),
+                key: RECORDED_WAYS_INDEX_KEY.into(),
+            },
+            &mut model,
+        );
+        let gets = storage_operations(update.effects)
+            .into_iter()
+            .filter_map(|operation| match operation {
+                KeyValueOperation::Get { key } => Some(key),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(gets, ["recorded_ways/Walk"]);
+        let _update = app.update(
+            Event::SetData {
+                res: Ok(Some(sets[0].1.clone())),
+                key: "recorded_ways/Walk".into(),
+            },
+            &mut model,
+        );
+        assert_eq!(model.recorded_ways["Walk"], rec);
+    }
+
+    #[test]
+    fn test_live_autosave() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let set_time = |secs| Event::SetCurrTime(crux_time::Instant::new(secs, 0).unwrap());
+        let saved = |effects| {
+            storage_operations(effects)
+                .into_iter()
+                .filter_map(|operation| match operation {
+                    KeyValueOperation::Set { key, value } if key == LIVE_RECORDING_KEY => {
+                        Some(value)
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let _update = app.update(
+            Event::SetLiveAutosave(Some(std::time::Duration::ZERO)),
+            &mut model,
+        );
+        assert_eq!(model.msg, "Error: The interval must be longer than zero.");
+        let update = app.update(
+            Event::SetLiveAutosave(Some(std::time::Duration::from_secs(10))),
+            &mut model,
+        );
+        let interval = Some(std::time::Duration::from_secs(10));
+        assert!(
+            storage_operations(update.effects).contains(&KeyValueOperation::Set {
+                key: LIVE_AUTOSAVE_KEY.into(),
+                value: encode(&interval),
+            })
+        );
+        for i in 0..3 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(0.0, i as f64 / 1000.0, i))),
+                &mut model,
+            );
+        }
+        let mut values = saved(app.update(set_time(100), &mut model).effects);
+        assert_eq!(values.len(), 1);
+        // Not within the interval, and not if nothing has changed.
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.003, 3))), &mut model);
+        assert!(saved(app.update(set_time(105), &mut model).effects).is_empty());
+        values.extend(saved(app.update(set_time(110), &mut model).effects));
+        assert_eq!(values.len(), 2);
+        assert!(saved(app.update(set_time(200), &mut model).effects).is_empty());
+
+        // The app is restarted and recovers the recording.
+        let mut recovered = Model::default();
+        let update = app.update(Event::LoadPersistantData, &mut recovered);
+        let operations = storage_operations(update.effects);
+        for key in [LIVE_AUTOSAVE_KEY, LIVE_RECORDING_KEY] {
+            assert!(operations.contains(&KeyValueOperation::Get { key: key.into() }));
+        }
+        let _update = app.update(
+            Event::SetData {
+                res: Ok(Some(encode(&interval))),
+                key: LIVE_AUTOSAVE_KEY.into(),
+            },
+            &mut recovered,
+        );
+        assert_eq!(
+            recovered.live_autosave.interval,
+            Some(TimeDelta::seconds(10))
+        );
+        let _update = app.update(
+            Event::GeolocationUpdate(Ok(geo(0.0, 0.004, 4))),
+            &mut recovered,
+        );
+        let _update = app.update(
+            Event::LiveRecordingRecovered(Ok(Some(values.pop().unwrap()))),
+            &mut recovered,
+        );
+        assert_eq!(
+            recovered.msg,
+            "4 positions of the recording since app start have been recovered."
+        );
+        let rec = recovered.all_positions.as_ref().unwrap();
+        assert_eq!(rec.way.nodes().len(), 5);
+        assert_eq!(
+            rec.way.nodes()[..4],
+            model.all_positions.as_ref().unwrap().way.nodes()[..]
+        );
+
+        // The saved recording is deleted when the recording is saved as a way.
+        let update = app.update(Event::SaveAllPositions("Walk".into()), &mut recovered);
+        assert!(
+            storage_operations(update.effects).contains(&KeyValueOperation::Delete {
+                key: LIVE_RECORDING_KEY.into()
+            })
+        );
+        let update = app.update(Event::SaveAllPositions("Walk 2".into()), &mut recovered);
+        assert!(
+            !storage_operations(update.effects).contains(&KeyValueOperation::Delete {
+                key: LIVE_RECORDING_KEY.into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_save_then_delete_way() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let update = app.update(Event::SaveAllPositions("Walk".into()), &mut model);
+        let keys = storage_operations(update.effects)
+            .into_iter()
+            .filter_map(|operation| match operation {
+                KeyValueOperation::Set { key, .. } => Some(key),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(keys, ["recorded_ways/Walk", RECORDED_WAYS_INDEX_KEY]);
+
+        let update = app.update(Event::DelRecordedWay("Walk".into()), &mut model);
+        let operations = storage_operations(update.effects);
+        assert_eq!(operations.len(), 2);
+        assert!(
+            matches!(&operations[0], KeyValueOperation::Delete { key } if key == "recorded_ways/Walk")
+        );
+        let KeyValueOperation::Set { key, value } = &operations[1] else {
+            panic!("The index should be saved.");
+        };
+        assert_eq!(key, RECORDED_WAYS_INDEX_KEY);
+        assert!(
+            decode::<BTreeSet<CompactString>>(value, false, migrate_unchanged)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(model.recorded_ways.is_empty());
+    }
+
+    #[test]
+    fn test_import_ways() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let ways = (0..100)
+            .map(|i| {
+                let mut rec = RecordedWay::new();
+                rec.add(&geo(0.0, i as f64 / 100.0, i));
+                (format_compact!("Way {}", i % 50), rec)
+            })
+            .collect();
+        let _update = app.update(Event::ViewNRecordedWays(1000), &mut model);
+        let update = app.update(Event::ImportWays(ways), &mut model);
+        assert_eq!(model.recorded_ways.len(), 100);
+        assert_eq!(model.view_recorded_ways.len(), 100);
+        assert!(model.recorded_ways.contains_key("Way 7 (imported)"));
+
+        let renders = update
+            .effects
+            .iter()
+            .filter(|effect| matches!(effect, Effect::Render(_)))
+            .count();
+        assert_eq!(renders, 1);
+        let keys = storage_operations(update.effects)
+            .into_iter()
+            .filter_map(|operation| match operation {
+                KeyValueOperation::Set { key, .. } => Some(key),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // Every way is written once and the index is written once.
+        assert_eq!(keys.len(), 101);
+        assert_eq!(
+            keys.iter()
+                .filter(|key| *key == RECORDED_WAYS_INDEX_KEY)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_storage_usage() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let keys = [
+            "saved_positions",
+            "recorded_ways/Walk",
+            "other_app",
+            "unit_system",
+        ];
+        let update = app.update(
+            Event::StorageKeys(Ok((keys.map(String::from).to_vec(), 0))),
+            &mut model,
+        );
+        let gets = storage_operations(update.effects)
+            .into_iter()
+            .filter_map(|operation| match operation {
+                KeyValueOperation::Get { key } => Some(key),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            gets,
+            ["recorded_ways/Walk", "saved_positions", "unit_system"]
+        );
+
+        for (key, size) in [
+            ("saved_positions", 2000),
+            ("recorded_ways/Walk", 3000),
+            ("other_app", 100_000),
+        ] {
+            let _update = app.update(
+                Event::StorageKeySize {
+                    key: key.into(),
+                    res: Ok(Some(vec![0; size])),
+                },
+                &mut model,
+            );
+        }
+        assert!(model.storage_usage.is_some());
+        let _update = app.update(
+            Event::StorageKeySize {
+                key: "unit_system".into(),
+                res: Ok(None),
+            },
+            &mut model,
+        );
+        assert!(model.storage_usage.is_none());
+        // 5000 bytes of values are 6668 characters in base64, and the keys are 44 characters,
+        // with two bytes per character.
+        assert_eq!(
+            model.msg,
+            "3 keys use 13.1 KB of persistant storage, which is 0.3% of about 5 MB."
+        );
+    }
+
+    #[test]
+    fn test_corrupt_data() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let corrupt = b"\xff\xff\xff".to_vec();
+        let update = app.update(
+            Event::SetData {
+                res: Ok(Some(corrupt.clone())),
+                key: SAVED_POSITIONS_KEY.into(),
+            },
+            &mut model,
+        );
+        assert!(
+            model.msg.contains("saved_positions.corrupt"),
+            "{}",
+            model.msg
+        );
+        let operations = storage_operations(update.effects);
+        assert_eq!(
+            operations,
+            [KeyValueOperation::Set {
+                key: "saved_positions.corrupt".into(),
+                value: corrupt,
+            }]
+        );
+
+        // The corrupt data must not be overwritten.
+        model.curr_pos = Some(Ok(geo(1.0, 2.0, 0)));
+        let update = app.update(Event::SaveCurrPos("Home".into()), &mut model);
+        assert!(model.saved_positions_names.contains_key("Home"));
+        assert!(storage_operations(update.effects).is_empty());
+        let update = app.update(Event::FlushSavedPositions, &mut model);
+        assert!(storage_operations(update.effects).is_empty());
+
+        // Other data is still saved.
+        let update = app.update(Event::SetUnitSystem(UnitSystem::Imperial), &mut model);
+        assert_eq!(storage_operations(update.effects).len(), 1);
+    }
+
+    #[test]
+    fn test_recover_from_backup() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(
+            Event::SetData {
+                res: Ok(Some(b"{".to_vec())),
+                key: RECORDED_WAYS_INDEX_KEY.into(),
+            },
+            &mut model,
+        );
+        assert!(model.corrupt_keys.contains(RECORDED_WAYS_INDEX_KEY));
+
+        let update = app.update(
+            Event::RecoverFromBackup(RECORDED_WAYS_INDEX_KEY.into()),
+            &mut model,
+        );
+        assert_eq!(
+            storage_operations(update.effects),
+            [KeyValueOperation::Get {
+                key: "recorded_ways_index.corrupt".into()
+            }]
+        );
+
+        // The backup is still corrupt.
+        let _update = app.update(
+            Event::SetBackupData {
+                res: Ok(Some(b"{".to_vec())),
+                key: RECORDED_WAYS_INDEX_KEY.into(),
+            },
+            &mut model,
+        );
+        assert!(model.msg.starts_with("Failed to recover"), "{}", model.msg);
+        assert!(model.corrupt_keys.contains(RECORDED_WAYS_INDEX_KEY));
+
+        // The backup is valid JSON.
+        let update = app.update(
+            Event::SetBackupData {
+                res: Ok(Some(br#"["Walk"]"#.to_vec())),
+                key: RECORDED_WAYS_INDEX_KEY.into(),
+            },
+            &mut model,
+        );
+        assert!(model.corrupt_keys.is_empty());
+        assert!(model.recorded_ways_index.contains("Walk"));
+        let operations = storage_operations(update.effects);
+        assert!(operations.contains(&KeyValueOperation::Get {
+            key: "recorded_ways/Walk".into()
+        }));
+        assert!(operations.iter().any(
+            |operation| matches!(operation, KeyValueOperation::Set { key, .. } if key == RECORDED_WAYS_INDEX_KEY)
+        ));
+        assert!(operations.contains(&KeyValueOperation::Delete {
+            key: "recorded_ways_index.corrupt".into()
+        }));
+    }
+
+    #[test]
+    fn test_load_saved_positions() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model {
+            curr_pos: Some(Ok(geo(1.0, 2.0, 0))),
+            ..Model::default()
+        };
+        let _update = app.update(Event::SaveCurrPos("Home".into()), &mut model);
+        let update = app.update(Event::FlushSavedPositions, &mut model);
+        let [KeyValueOperation::Set { key, value }] = &storage_operations(update.effects)[..]
+        else {
+            panic!("Expected exactly one set operation");
+        };
+        assert_eq!(key, SAVED_POSITIONS_KEY);
+        // Saved positions from before the envelope and `SavedPos::averaged` were added. A struct
+        // is encoded like a tuple and the r-tree is rebuilt from the names.
+        let legacy_names = model
+            .saved_positions_names
+            .iter()
+            .map(|(name, x)| (name, (&x.name, &x.pos, x.timestamp)))
+            .collect::<HashMap<_, _>>();
+        let legacy = bincode::serialize(&(RTree::<SavedPos>::new(), legacy_names)).unwrap();
+
+        for bytes in [value.clone(), legacy] {
+            let mut loaded = Model::default();
+            let _update = app.update(
+                Event::SetData {
+                    res: Ok(Some(bytes)),
+                    key: SAVED_POSITIONS_KEY.into(),
+                },
+                &mut loaded,
+            );
+            assert!(loaded.corrupt_keys.is_empty());
+            assert_eq!(loaded.saved_positions_names, model.saved_positions_names);
+        }
+    }
+
+    #[test]
+    fn test_live_recording_node_limit() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(
+            Event::SetLiveRecordingLimit(Some(LiveRecordingLimit::Nodes(10))),
+            &mut model,
+        );
+        for i in 0..500 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo((i % 5) as f64 / 100.0, 0.0, i))),
+                &mut model,
+            );
+            let way = &model.all_positions.as_ref().unwrap().way;
+            assert!(way.nodes().len() <= 10);
+            let mut expected = RecordedWay::new();
+            for node in way.nodes() {
+                expected.add(node);
+            }
+            assert!((way.length() - expected.way.length()).as_metres().abs() < 1e-3);
+        }
+        let way = &model.all_positions.as_ref().unwrap().way;
+        assert_eq!(way.nodes().len(), 10);
+        assert_eq!(way.nodes()[0].timestamp.timestamp(), 490);
+        assert!(model.live_recording_trimmed);
+    }
+
+    #[test]
+    fn test_odometer() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        // Jitter of about 1 m around the start.
+        for i in 0..10 {
+            let jitter = if i % 2 == 0 { 0.0 } else { 0.00001 };
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(jitter, 0.0, i))),
+                &mut model,
+            );
+        }
+        assert_eq!(model.odometer, Length::ZERO);
+
+        // Walk 10 steps of about 11 m to the north.
+        for i in 1..=10 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(i as f64 / 10000.0, 0.0, 10 + i))),
+                &mut model,
+            );
+        }
+        let expected = PLANET.distance(
+            LatLong::from_degrees(0.0, 0.0).to_nvector(),
+            LatLong::from_degrees(0.001, 0.0).to_nvector(),
+        );
+        assert!((model.odometer - expected).as_metres().abs() < 1e-3);
+
+        // A position with a bad accuracy doesn't count until the step exceeds the accuracy.
+        let mut inaccurate = geo(0.0012, 0.0, 30);
+        inaccurate.accuracy = Some(Length::from_metres(50.0));
+        let _update = app.update(Event::GeolocationUpdate(Ok(inaccurate)), &mut model);
+        assert!((model.odometer - expected).as_metres().abs() < 1e-3);
+
+        let _update = app.update(Event::ResetOdometer, &mut model);
+        assert_eq!(model.odometer, Length::ZERO);
+        assert_eq!(model.odometer_pos, None);
+    }
+
+    #[test]
+    fn test_smoothed_heading_wraparound() {
+        let mut smoothed = Smoothed::default();
+        assert_eq!(smoothed.heading(), None);
+        for degrees in [359.0, 1.0, 358.0, 2.0, 359.0, 1.0] {
+            smoothed.add(0.5, None, Some(Angle::from_degrees(degrees)));
+            let heading = smoothed.heading().unwrap().as_degrees();
+            assert!(heading.abs() < 2.0, "{heading}");
+        }
+        assert_eq!(smoothed.speed(), None);
+
+        // Opposite headings cancel out.
+        let mut smoothed = Smoothed::default();
+        smoothed.add(0.5, None, Some(Angle::from_degrees(90.0)));
+        smoothed.add(0.5, None, Some(Angle::from_degrees(270.0)));
+        assert_eq!(smoothed.heading(), None);
+    }
+
+    #[test]
+    fn test_smoothed_speed() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::SetSmoothingFactor(0.5), &mut model);
+        for (i, speed) in [2.0, 4.0, 0.0].into_iter().enumerate() {
+            let mut geo_info = geo(0.0, 0.0, i as i64);
+            geo_info.volocity = Some(Speed::from_metres_per_second(speed));
+            geo_info.bearing = Some(Angle::from_degrees((350.0 + 10.0 * i as f64) % 360.0));
+            let _update = app.update(Event::GeolocationUpdate(Ok(geo_info)), &mut model);
+        }
+        // 2 -> 3 -> 1.5
+        let speed = model.smoothed.speed().unwrap().as_metres_per_second();
+        assert!((speed - 1.5).abs() < 1e-9);
+        let view = ViewModel::new(&model);
+        assert!(view.curr_pos_properties[0].starts_with("Speed: 0.0 m/s (smoothed: 1.5 m/s)"));
+        assert!(view.curr_pos_properties[1].starts_with("Heading 10° (smoothed: 3°)"));
+
+        let _update = app.update(Event::SetSmoothingFactor(1.5), &mut model);
+        assert!(model.msg.starts_with("Error"));
+        assert_eq!(model.smoothing_factor, Some(0.5));
+    }
+
+    #[test]
+    fn test_view_coords() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        assert_eq!(ViewModel::new(&model).curr_pos_coords, None);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 2.0, 0))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Home".into()), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(3.0, 4.0, 1))), &mut model);
+        let _update = app.update(Event::ViewNSavedPositions(1), &mut model);
+        let view = ViewModel::new(&model);
+        let coords = view.curr_pos_coords.unwrap();
+        assert!((coords.latitude - 3.0).abs() < 1e-9);
+        assert!((coords.longitude - 4.0).abs() < 1e-9);
+        let coords = view.saved_positions[0].coords;
+        assert!((coords.latitude - 1.0).abs() < 1e-9);
+        assert!((coords.longitude - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_view_accuracy() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let accurate = |accuracy: f64, secs: i64| GeoInfo {
+            accuracy: Some(Length::from_metres(accuracy)),
+            ..geo(1.0, 2.0, secs)
+        };
+        let _update = app.update(Event::GeolocationUpdate(Ok(accurate(12.5, 0))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Home".into()), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 2.0, 1))), &mut model);
+        let _update = app.update(Event::ViewNSavedPositions(1), &mut model);
+        let view = ViewModel::new(&model);
+        assert_eq!(view.curr_pos_accuracy, None);
+        assert_eq!(
+            view.saved_positions[0].accuracy.map(|x| x.0),
+            model.saved_positions_names["Home"]
+                .pos
+                .accuracy
+                .map(|x| x.as_metres())
+        );
+        assert_eq!(view.saved_positions[0].accuracy, Some(ViewMetres(12.5)));
+
+        let _update = app.update(Event::GeolocationUpdate(Ok(accurate(3.0, 2))), &mut model);
+        let view = ViewModel::new(&model);
+        assert_eq!(view.curr_pos_accuracy, Some(ViewMetres(3.0)));
+        // Serialized as a plain number.
+        assert_eq!(
+            serde_json::to_string(&view.curr_pos_accuracy).unwrap(),
+            "3.0"
+        );
+    }
+
+    #[test]
+    fn test_view_way_detail() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(
+            Event::GeolocationUpdate(Ok(geo(0.001, 0.0, 10))),
+            &mut model,
+        );
+        assert_eq!(ViewModel::new(&model).way_detail, None);
+
+        let _update = app.update(Event::ViewWayDetail(Some("Nowhere".into())), &mut model);
+        assert_eq!(model.msg, "Error: Way Nowhere does not exist.");
+        assert_eq!(ViewModel::new(&model).way_detail, None);
+
+        let _update = app.update(
+            Event::ViewWayDetail(Some(ALL_POSITIONS_NAME.into())),
+            &mut model,
+        );
+        let detail = ViewModel::new(&model).way_detail.unwrap();
+        assert_eq!(detail.nodes.len(), 2);
+        assert_eq!(detail.segment_speeds.len(), 1);
+        assert!((detail.segment_speeds[0] - 11.1).abs() < 0.1);
+
+        let _update = app.update(Event::ViewWayDetail(None), &mut model);
+        assert_eq!(ViewModel::new(&model).way_detail, None);
+    }
+
+    #[test]
+    fn test_forget_stale_pos() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let set_time = |secs| Event::SetCurrTime(crux_time::Instant::new(secs, 0).unwrap());
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(Event::SetMaxFixAge(30), &mut model);
+        let _update = app.update(set_time(30), &mut model);
+        assert!(matches!(model.curr_pos, Some(Ok(_))));
+        let _update = app.update(set_time(31), &mut model);
+        assert_eq!(model.curr_pos, None);
+
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 32))), &mut model);
+        let _update = app.update(Event::SetMaxFixAge(0), &mut model);
+        let _update = app.update(set_time(1000), &mut model);
+        assert!(matches!(model.curr_pos, Some(Ok(_))));
+        // Lowering the limit forgets the position at once.
+        let _update = app.update(Event::SetMaxFixAge(60), &mut model);
+        assert_eq!(model.curr_pos, None);
+
+        // By default the position is never forgotten.
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(set_time(100_000), &mut model);
+        assert!(matches!(model.curr_pos, Some(Ok(_))));
+    }
+
+    #[test]
+    fn test_save_averaged_pos() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let save = |name: &str| Event::SaveAveragedPos {
+            name: name.into(),
+            window: std::time::Duration::from_secs(60),
+        };
+        let _update = app.update(save("Nowhere"), &mut model);
+        assert_eq!(model.msg, "Error: The current position is not known.");
+
+        // Without the current time, only the current position is saved.
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 100))), &mut model);
+        let _update = app.update(
+            Event::GeolocationUpdate(Ok(geo(0.0, 0.002, 110))),
+            &mut model,
+        );
+        let _update = app.update(save("Current"), &mut model);
+        let current = &model.saved_positions_names["Current"];
+        assert_eq!(current.averaged, None);
+        assert_eq!(current.pos.coords, LatLong::from_degrees(0.0, 0.002));
+
+        // Two positions with equal accuracies are averaged to their midpoint.
+        let _update = app.update(
+            Event::SetCurrTime(crux_time::Instant::new(120, 0).unwrap()),
+            &mut model,
+        );
+        let _update = app.update(save("Averaged"), &mut model);
+        let averaged = &model.saved_positions_names["Averaged"];
+        assert_eq!(averaged.averaged.as_ref().unwrap().samples, 2);
+        let midpoint = LatLong::from_degrees(0.0, 0.001).to_nvector();
+        assert!(PLANET.distance(midpoint, averaged.nvector()).as_metres() < 1e-3);
+    }
+
+    #[test]
+    fn test_save_waypoints_from_way() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for i in 0..10 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(i as f64 / 1000.0, 0.0, i))),
+                &mut model,
+            );
+        }
+        let _update = app.update(Event::SaveAllPositions("Walk".into()), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 0.0, 10))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Walk #2".into()), &mut model);
+
+        let _update = app.update(
+            Event::SaveWaypointsFromWay {
+                way_name: "Walk".into(),
+                every_n: 3,
+            },
+            &mut model,
+        );
+        assert_eq!(
+            model.msg,
+            "Saved 3 positions from Walk. 1 was skipped since its name is already in use."
+        );
+        for (name, secs) in [("Walk #1", 0), ("Walk #3", 6), ("Walk #4", 9)] {
+            let pos = &model.saved_positions_names[name];
+            assert_eq!(pos.timestamp, DateTime::from_timestamp(secs, 0).unwrap());
+            assert!((pos.pos.coords.latitude().as_degrees() - secs as f64 / 1000.0).abs() < 1e-9);
+        }
+        // The position which was already named "Walk #2" is kept.
+        assert_eq!(
+            model.saved_positions_names["Walk #2"].timestamp,
+            DateTime::from_timestamp(10, 0).unwrap()
+        );
+        assert_eq!(model.saved_positions.size(), 4);
+    }
+
+    #[test]
+    fn test_export_way_json() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for i in 0..5 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(i as f64 / 1000.0, 0.0, i * 10))),
+                &mut model,
+            );
+        }
+        let _update = app.update(Event::SaveAllPositions("Walk".into()), &mut model);
+        let update = app.update(Event::ExportWayJson("Walk".into()), &mut model);
+        let request = update
+            .effects
+            .into_iter()
+            .find_map(|effect| match effect {
+                Effect::FileDownload(request) => Some(request.operation),
+                _ => None,
+            })
+            .expect("The way should be downloaded.");
+        assert_eq!(request.file_name.as_deref(), Some("Walk.json"));
+        let json: serde_json::Value = serde_json::from_slice(&request.content).unwrap();
+        assert_eq!(json["name"], "Walk");
+        assert_eq!(json["node_count"], 5);
+        assert_eq!(json["duration_seconds"], 40);
+        assert_eq!(json["way"]["way"]["nodes"].as_array().unwrap().len(), 5);
+
+        let _update = app.update(Event::ExportWayJson("Run".into()), &mut model);
+        assert_eq!(model.msg, "Error: Way Run does not exist.");
+    }
+
+    #[test]
+    fn test_download_and_import_data() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model {
+            curr_time: Some(DateTime::from_timestamp(86400, 0).unwrap()),
+            ..Model::default()
+        };
+        for i in 0..3 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(i as f64 / 1000.0, 0.0, i))),
+                &mut model,
+            );
+        }
+        let _update = app.update(Event::SaveCurrPos("Home".into()), &mut model);
+        let _update = app.update(Event::SaveAllPositions("Walk".into()), &mut model);
+        let update = app.update(Event::DownloadData, &mut model);
+        let request = update
+            .effects
+            .into_iter()
+            .find_map(|effect| match effect {
+                Effect::FileDownload(request) => Some(request.operation),
+                _ => None,
+            })
+            .expect("The data should be downloaded.");
+        let json: serde_json::Value = serde_json::from_slice(&request.content).unwrap();
+        assert_eq!(json["format"], "crux_geo_app");
+        assert_eq!(json["version"], export::EXPORT_VERSION);
+        assert_eq!(json["exported_at"], "1970-01-02T00:00:00Z");
+
+        let _update = app.update(Event::ImportData(request.content.clone()), &mut model);
+        assert_eq!(
+            model.msg,
+            "Imported 1 position and 1 way exported 1970-01-02."
+        );
+        assert!(model.saved_positions_names.contains_key("Home (imported)"));
+        assert!(model.recorded_ways.contains_key("Walk (imported)"));
+        assert_eq!(model.saved_positions.size(), 2);
+
+        let mut json = json;
+        json["format"] = "gpx".into();
+        let _update = app.update(
+            Event::ImportData(serde_json::to_vec(&json).unwrap()),
+            &mut model,
+        );
+        assert_eq!(
+            model.msg,
+            "Error: Failed to read the data file: Unknown format: gpx"
+        );
+        assert_eq!(model.saved_positions.size(), 2);
+        assert_eq!(model.recorded_ways.len(), 2);
+    }
+
+    #[test]
+    fn test_export_kml() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for i in 0..3 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(i as f64 / 1000.0, 0.0, i))),
+                &mut model,
+            );
+        }
+        let _update = app.update(Event::SaveAllPositions("Walk".into()), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Home".into()), &mut model);
+        let update = app.update(Event::ExportKml, &mut model);
+        let request = update
+            .effects
+            .into_iter()
+            .find_map(|effect| match effect {
+                Effect::FileDownload(request) => Some(request.operation),
+                _ => None,
+            })
+            .expect("The KML should be downloaded.");
+        assert_eq!(request.file_name.as_deref(), Some("geosuper_data.kml"));
+        assert_eq!(
+            request.mime_type.as_deref(),
+            Some("application/vnd.google-earth.kml+xml")
+        );
+        let kml = String::from_utf8(request.content).unwrap();
+        assert!(kml.contains("<name>Home</name>"));
+        assert!(kml.contains("<name>Walk</name>"));
+        assert!(kml.contains("<LineString>"));
+    }
+
+    #[test]
+    fn test_downsample_way() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for i in 0..10 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(i as f64 / 1000.0, 0.0, i))),
+                &mut model,
+            );
+        }
+        let _update = app.update(Event::SaveAllPositions("Walk".into()), &mut model);
+        let update = app.update(
+            Event::DownsampleWay {
+                name: "Walk".into(),
+                target: 4,
+            },
+            &mut model,
+        );
+        assert_eq!(model.msg, "Walk has been downsampled from 10 to 4 nodes.");
+        assert_eq!(model.recorded_ways["Walk"].way.nodes().len(), 4);
+        assert!(!storage_operations(update.effects).is_empty());
+
+        let _update = app.update(
+            Event::DownsampleWay {
+                name: "Run".into(),
+                target: 4,
+            },
+            &mut model,
+        );
+        assert_eq!(model.msg, "Error: Way Run does not exist.");
+    }
+
+    #[test]
+    fn test_merge_ways() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for i in 0..4 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(0.0, i as f64 / 1000.0, i))),
+                &mut model,
+            );
+        }
+        let _update = app.update(Event::SaveAllPositions("A".into()), &mut model);
+        let _update = app.update(Event::StartNewTrack, &mut model);
+        // The last node has the same timestamp as a node of the first way.
+        for i in [3, 4, 5] {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(0.0, i as f64 / 1000.0, i))),
+                &mut model,
+            );
+        }
+        let _update = app.update(Event::SaveAllPositions("B".into()), &mut model);
+        let (a, b) = (
+            model.recorded_ways["A"].clone(),
+            model.recorded_ways["B"].clone(),
+        );
+
+        let merge = |into: &str, delete_sources| Event::MergeWays {
+            first: "A".into(),
+            second: "B".into(),
+            into: into.into(),
+            delete_sources,
+        };
+        let _update = app.update(merge("A+B", false), &mut model);
+        assert_eq!(model.msg, "A and B have been merged into A+B.");
+        let merged = &model.recorded_ways["A+B"];
+        assert_eq!(merged.way.nodes().len(), 4 + 3 - 1);
+        // The last node of A is at the same place as the first node of B.
+        assert!(
+            (merged.way.length() - (a.way.length() + b.way.length()))
+                .as_metres()
+                .abs()
+                < 1e-6
+        );
+        assert!(model.recorded_ways.contains_key("A"));
+
+        let _update = app.update(merge("A+B", false), &mut model);
+        assert_eq!(model.msg, "Error: The name A+B is already in use.");
+        let _update = app.update(
+            Event::MergeWays {
+                first: "A".into(),
+                second: "C".into(),
+                into: "A+C".into(),
+                delete_sources: false,
+            },
+            &mut model,
+        );
+        assert_eq!(model.msg, "Error: Way C does not exist.");
+
+        let update = app.update(merge("Both", true), &mut model);
+        let mut names = model
+            .recorded_ways
+            .keys()
+            .map(CompactString::as_str)
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["A+B", "Both"]);
+        let deletes = storage_operations(update.effects)
+            .into_iter()
+            .filter_map(|op| match op {
+                KeyValueOperation::Delete { key } => Some(key),
+                _ => None,
+            })
+            .count();
+        assert_eq!(deletes, 2);
+    }
+
+    #[test]
+    fn test_split_way() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for i in 0..5 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(0.0, i as f64 / 1000.0, i * 10))),
+                &mut model,
+            );
+        }
+        let _update = app.update(Event::SaveAllPositions("Walk".into()), &mut model);
+        let _update = app.update(
+            Event::SplitWay {
+                name: "Walk".into(),
+                at_index: 5,
+            },
+            &mut model,
+        );
+        assert_eq!(
+            model.msg,
+            "Error: Walk can only be split at an index between 1 and 4."
+        );
+        let _update = app.update(
+            Event::SplitWay {
+                name: "Walk".into(),
+                at_index: 2,
+            },
+            &mut model,
+        );
+        assert_eq!(model.msg, "Walk has been split into Walk (1) and Walk (2).");
+        assert!(!model.recorded_ways.contains_key("Walk"));
+        let (first, second) = (
+            &model.recorded_ways["Walk (1)"],
+            &model.recorded_ways["Walk (2)"],
+        );
+        assert_eq!(first.way.nodes().len(), 2);
+        assert_eq!(second.way.nodes().len(), 3);
+
+        // Split the second part at a timestamp between its nodes.
+        let _update = app.update(
+            Event::SplitWayAt {
+                name: "Walk (2)".into(),
+                at: DateTime::from_timestamp(35, 0).unwrap(),
+            },
+            &mut model,
+        );
+        let counts =
+            ["Walk (2) (1)", "Walk (2) (2)"].map(|x| model.recorded_ways[x].way.nodes().len());
+        assert_eq!(counts, [2, 1]);
+        let _update = app.update(
+            Event::SplitWayAt {
+                name: "Walk (1)".into(),
+                at: DateTime::from_timestamp(100, 0).unwrap(),
+            },
+            &mut model,
+        );
+        assert!(model.msg.starts_with("Error"));
+        assert!(model.recorded_ways.contains_key("Walk (1)"));
+        let _update = app.update(
+            Event::SplitWayAt {
+                name: "Run".into(),
+                at: DateTime::from_timestamp(0, 0).unwrap(),
+            },
+            &mut model,
+        );
+        assert_eq!(model.msg, "Error: Way Run does not exist.");
+    }
+
+    #[test]
+    fn test_save_way_range() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for i in 0..10 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(0.0, 0.001 * i as f64, 10 * i))),
+                &mut model,
+            );
+        }
+        let time = |secs| DateTime::from_timestamp(secs, 0).unwrap();
+        // The ends need not be the timestamps of any positions.
+        let _update = app.update(
+            Event::SaveWayRange {
+                name: "Middle".into(),
+                start: time(25),
+                end: time(60),
+            },
+            &mut model,
+        );
+        let timestamps = |model: &Model, name: &str| {
+            model.recorded_ways[name]
+                .way
+                .nodes()
+                .iter()
+                .map(|x| x.timestamp.timestamp())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(timestamps(&model, "Middle"), [30, 40, 50, 60]);
+        let _update = app.update(
+            Event::SaveWayRange {
+                name: "Ends".into(),
+                start: time(0),
+                end: time(90),
+            },
+            &mut model,
+        );
+        assert_eq!(timestamps(&model, "Ends").len(), 10);
+
+        for (start, end, msg) in [
+            (
+                time(60),
+                time(30),
+                "Error: The start of the time range is after the end.",
+            ),
+            (
+                time(91),
+                time(100),
+                "Error: No positions were recorded in the time range.",
+            ),
+        ] {
+            let _update = app.update(
+                Event::SaveWayRange {
+                    name: "Invalid".into(),
+                    start,
+                    end,
+                },
+                &mut model,
+            );
+            assert_eq!(model.msg, msg);
+        }
+        assert!(!model.recorded_ways.contains_key("Invalid"));
+    }
+
+    #[test]
+    fn test_remaining_along_way() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        // Three segments of about 1112 m each.
+        for i in 0..4 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(0.0, 0.01 * i as f64, i))),
+                &mut model,
+            );
+        }
+        let _update = app.update(Event::SaveAllPositions("Route".into()), &mut model);
+        // Beside the second segment.
+        let _update = app.update(
+            Event::GeolocationUpdate(Ok(geo(0.001, 0.012, 4))),
+            &mut model,
+        );
+        for (reverse, msg) in [
+            (false, "2002 m remaining along Route."),
+            (true, "1334 m remaining along Route."),
+        ] {
+            let _update = app.update(
+                Event::RemainingAlongWay {
+                    name: "Route".into(),
+                    reverse,
+                },
+                &mut model,
+            );
+            assert_eq!(model.msg, msg);
+        }
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.025, 5))), &mut model);
+        let _update = app.update(
+            Event::RemainingAlongWay {
+                name: "Route".into(),
+                reverse: false,
+            },
+            &mut model,
+        );
+        assert_eq!(model.msg, "556 m remaining along Route.");
+    }
+
+    #[test]
+    fn test_app_errors() {
+        let mut model = Model::default();
+        assert_eq!(
+            save_curr_pos(&mut model, "Home".into()),
+            Err(AppError::NoCurrentPosition)
+        );
+        assert_eq!(
+            save_all_positions(&mut model, "Walk".into()),
+            Err(AppError::NoRecordedPositions)
+        );
+        assert_eq!(
+            del_saved_pos(&mut model, "Home"),
+            Err(AppError::PosNotFound("Home".into()))
+        );
+
+        let app = AppTester::<GeoApp, _>::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 2.0, 0))), &mut model);
+        assert_eq!(save_curr_pos(&mut model, "Home".into()), Ok(()));
+        assert_eq!(
+            save_curr_pos(&mut model, "Home".into()),
+            Err(AppError::PosNameExists("Home".into()))
+        );
+        assert_eq!(save_all_positions(&mut model, "Walk".into()), Ok(()));
+        assert_eq!(
+            save_all_positions(&mut model, "Walk".into()),
+            Err(AppError::WayNameExists("Walk".into()))
+        );
+        assert_eq!(del_saved_pos(&mut model, "Home"), Ok(()));
+        assert!(model.saved_positions_names.is_empty());
+
+        // The messages are the same as before the errors were typed.
+        let _update = app.update(Event::SaveAllPositions("Walk".into()), &mut model);
+        assert_eq!(model.msg, "Error: The name Walk is already in use.");
+        let _update = app.update(Event::DelSavedPos("Home".into()), &mut model);
+        assert_eq!(model.msg, "Error: Position Home does not exist.");
+        model.curr_pos = None;
+        let _update = app.update(Event::SaveCurrPos("Home".into()), &mut model);
+        assert_eq!(model.msg, "Error: The current position is not known.");
+    }
+
+    #[test]
+    fn test_set_language() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::DelSavedPos("Home".into()), &mut model);
+        assert_eq!(model.msg, "Error: Position Home does not exist.");
+        assert_eq!(ViewModel::new(&model).gps_status, "No GPS information");
+
+        let update = app.update(Event::SetLanguage(Lang::Swedish), &mut model);
+        assert_eq!(
+            storage_operations(update.effects),
+            [KeyValueOperation::Set {
+                key: LANGUAGE_KEY.into(),
+                value: encode(&Lang::Swedish),
+            }]
+        );
+        let _update = app.update(Event::DelSavedPos("Home".into()), &mut model);
+        assert_eq!(model.msg, "Fel: Positionen Home finns inte.");
+        assert_eq!(ViewModel::new(&model).gps_status, "Ingen GPS-information");
+
+        // The language is loaded from persistant storage.
+        let mut loaded = Model::default();
+        let _update = app.update(
+            Event::SetData {
+                res: Ok(Some(encode(&Lang::Swedish))),
+                key: LANGUAGE_KEY.into(),
+            },
+            &mut loaded,
+        );
+        assert_eq!(loaded.language, Lang::Swedish);
+    }
+
+    #[test]
+    fn test_set_bearing_mode() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::ViewNSavedPositions(10), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 1.0, 0))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Target".into()), &mut model);
+        let heading_east = GeoInfo {
+            bearing: Some(Angle::from_degrees(90.0)),
+            ..geo(0.0, 0.0, 1)
+        };
+        let _update = app.update(
+            Event::GeolocationUpdate(Ok(heading_east.clone())),
+            &mut model,
+        );
+        let summary = |model: &Model| ViewModel::new(model).saved_positions[0].summary.clone();
+        assert!(
+            summary(&model).ends_with(", 45° (NE)"),
+            "{}",
+            summary(&model)
+        );
+
+        let _update = app.update(
+            Event::SetBearingMode(BearingMode::RelativeToHeading),
+            &mut model,
+        );
+        assert!(
+            summary(&model).ends_with(", 315° from the heading"),
+            "{}",
+            summary(&model)
+        );
+
+        // Absolute when the heading is unknown.
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 2))), &mut model);
+        assert!(
+            summary(&model).ends_with(", 45° (NE)"),
+            "{}",
+            summary(&model)
+        );
+
+        // Absolute when the heading is not a number or when standing still.
+        let heading_nan = GeoInfo {
+            bearing: Some(Angle::from_degrees(f64::NAN)),
+            ..geo(0.0, 0.0, 3)
+        };
+        let _update = app.update(Event::GeolocationUpdate(Ok(heading_nan)), &mut model);
+        assert!(
+            summary(&model).ends_with(", 45° (NE)"),
+            "{}",
+            summary(&model)
+        );
+        let stationary = GeoInfo {
+            bearing: Some(Angle::from_degrees(90.0)),
+            volocity: Some(Speed::from_metres_per_second(0.0)),
+            ..geo(0.0, 0.0, 4)
+        };
+        let _update = app.update(Event::GeolocationUpdate(Ok(stationary)), &mut model);
+        assert!(
+            summary(&model).ends_with(", 45° (NE)"),
+            "{}",
+            summary(&model)
+        );
+    }
+
+    #[test]
+    fn test_set_rate_window() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        // A position every 30 s for 5 minutes.
+        for i in 0..=10 {
+            let _update = app.update(
+                Event::GeolocationUpdate(Ok(geo(0.0, 0.001 * i as f64, 30 * i))),
+                &mut model,
+            );
+        }
+        model.curr_time = Some(DateTime::from_timestamp(300, 0).unwrap());
+        let status = |model: &Model| ViewModel::new(model).gps_status;
+        assert!(
+            status(&model).ends_with("3 positions in the last minute."),
+            "{}",
+            status(&model)
+        );
+
+        let _update = app.update(
+            Event::SetRateWindow(std::time::Duration::from_secs(300)),
+            &mut model,
+        );
+        assert!(status(&model).ends_with("11 positions in the last 5 minutes."));
+        let _update = app.update(
+            Event::SetRateWindow(std::time::Duration::from_secs(45)),
+            &mut model,
+        );
+        assert!(status(&model).ends_with("2 positions in the last 45 s."));
+
+        for window in [
+            std::time::Duration::ZERO,
+            std::time::Duration::from_millis(999),
+        ] {
+            let _update = app.update(Event::SetRateWindow(window), &mut model);
+            assert_eq!(model.msg, "Error: The window must be at least 1 s.");
+            assert_eq!(model.rate_window(), TimeDelta::seconds(45));
+        }
+    }
+
+    #[test]
+    fn test_set_speed_unit() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let moving = GeoInfo {
+            volocity: Some(Speed::from_metres_per_second(10.0)),
+            ..geo(0.0, 0.0, 0)
+        };
+        let _update = app.update(Event::GeolocationUpdate(Ok(moving)), &mut model);
+        let speed = |model: &Model| ViewModel::new(model).curr_pos_properties[0].clone();
+        assert_eq!(speed(&model), "Speed: 10.0 m/s (smoothed: 10.0 m/s)");
+
+        let _update = app.update(Event::SetSpeedUnit(Some(SpeedUnit::Knots)), &mut model);
+        assert_eq!(speed(&model), "Speed: 19.4 kn (smoothed: 19.4 kn)");
+        // The unit system doesn't affect an explicitly chosen speed unit.
+        let _update = app.update(Event::SetUnitSystem(UnitSystem::Imperial), &mut model);
+        assert_eq!(speed(&model), "Speed: 19.4 kn (smoothed: 19.4 kn)");
+
+        let _update = app.update(Event::SetSpeedUnit(None), &mut model);
+        assert_eq!(speed(&model), "Speed: 22.4 mph (smoothed: 22.4 mph)");
+    }
+
+    #[test]
+    fn test_preview_route_to() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for (name, lat, long) in [("Target", 40.0, -74.0), ("Antipode", -59.0, -162.0)] {
+            let _update = app.update(Event::GeolocationUpdate(Ok(geo(lat, long, 0))), &mut model);
+            let _update = app.update(Event::SaveCurrPos(name.into()), &mut model);
+        }
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(59.0, 18.0, 1))), &mut model);
+
+        let _update = app.update(Event::PreviewRouteTo("Target".into()), &mut model);
+        assert!(model.msg.is_empty());
+        let route = ViewModel::new(&model).route_preview.unwrap();
+        assert_eq!(route.target, "Target");
+        let distance = |a: view_types::ViewCoords, lat: f64, long: f64| {
+            PLANET.distance(
+                LatLong::from_degrees(a.latitude, a.longitude).to_nvector(),
+                LatLong::from_degrees(lat, long).to_nvector(),
+            )
+        };
+        assert!(distance(route.points[0], 59.0, 18.0).as_metres() < 1e-3);
+        assert!(distance(*route.points.last().unwrap(), 40.0, -74.0).as_metres() < 1e-3);
+
+        let _update = app.update(Event::PreviewRouteTo("Antipode".into()), &mut model);
+        assert!(model.msg.starts_with("Warning:"), "{}", model.msg);
+        assert!(ViewModel::new(&model).route_preview.is_none());
+
+        let _update = app.update(Event::PreviewRouteTo("Nowhere".into()), &mut model);
+        assert_eq!(model.msg, "Error: Position Nowhere does not exist.");
+        let _update = app.update(Event::PreviewRouteTo("Target".into()), &mut model);
+        let _update = app.update(Event::PreviewRouteTo("".into()), &mut model);
+        assert!(model.route_preview.is_none());
+    }
+
+    #[test]
+    fn test_measure_between() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        // One degree of longitude apart along the equator, which is about 111.2 km.
+        for (name, long) in [("A", 0.0), ("B", 1.0)] {
+            let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, long, 0))), &mut model);
+            let _update = app.update(Event::SaveCurrPos(name.into()), &mut model);
+        }
+        let _update = app.update(
+            Event::MeasureBetween {
+                a: "A".into(),
+                b: "B".into(),
+            },
+            &mut model,
+        );
+        let distance = model
+            .msg
+            .strip_prefix("From A to B: ")
+            .and_then(|x| x.split_once(" m,"))
+            .map(|(metres, bearing)| {
+                assert_eq!(bearing, " 90° (E)");
+                metres.parse::<f64>().unwrap()
+            })
+            .unwrap_or_else(|| panic!("Unexpected message: {}", model.msg));
+        let expected = PLANET.radius().as_metres() * 1f64.to_radians();
+        assert!((distance - expected).abs() < 1.0, "{distance}");
+
+        let _update = app.update(
+            Event::MeasureBetween {
+                a: "A".into(),
+                b: "C".into(),
+            },
+            &mut model,
+        );
+        assert_eq!(model.msg, "Error: Position C does not exist.");
+    }
+
+    #[test]
+    fn test_cluster_saved_positions() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for (name, lat) in [("A", 59.0), ("B", 59.0005), ("C", 60.0)] {
+            let _update = app.update(Event::GeolocationUpdate(Ok(geo(lat, 18.0, 0))), &mut model);
+            let _update = app.update(Event::SaveCurrPos(name.into()), &mut model);
+        }
+        assert!(ViewModel::new(&model).saved_position_clusters.is_empty());
+        let _update = app.update(
+            Event::ClusterSavedPositions {
+                cell_size: Length::from_metres(1000.0),
+            },
+            &mut model,
+        );
+        let counts = |model: &Model| {
+            ViewModel::new(model)
+                .saved_position_clusters
+                .iter()
+                .map(|(_, n)| *n)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(counts(&model), [2, 1]);
+
+        // The clusters are updated when positions are saved.
+        let _update = app.update(
+            Event::GeolocationUpdate(Ok(geo(60.0001, 18.0, 1))),
+            &mut model,
+        );
+        let _update = app.update(Event::SaveCurrPos("D".into()), &mut model);
+        assert_eq!(counts(&model), [2, 2]);
+
+        let _update = app.update(
+            Event::ClusterSavedPositions {
+                cell_size: Length::ZERO,
+            },
+            &mut model,
+        );
+        assert!(counts(&model).is_empty());
+    }
+
+    #[test]
+    fn test_set_precision() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 2.0, 0))), &mut model);
+        let _update = app.update(Event::SetPrecision { coord: 2, other: 0 }, &mut model);
+        assert_eq!(
+            ViewModel::new(&model).curr_pos_properties[..2],
+            ["Latitude: 1.00° North", "Longitude: 2.00° East"]
+        );
+        let _update = app.update(
+            Event::SetPrecision {
+                coord: 100,
+                other: 100,
+            },
+            &mut model,
+        );
+        assert_eq!(
+            ViewModel::new(&model).curr_pos_properties[0],
+            "Latitude: 1.000000000° North"
+        );
+    }
+
+    #[test]
+    fn test_position_source() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 2.0, 0))), &mut model);
+        assert_eq!(ViewModel::new(&model).curr_pos_properties.len(), 2);
+        let geo_info = GeoInfo {
+            source: Some(crux_geolocation::PositionSource::Gps),
+            satellites: Some(7),
+            ..geo(1.0, 2.0, 1)
+        };
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo_info)), &mut model);
+        assert_eq!(
+            ViewModel::new(&model).curr_pos_properties[2..],
+            ["Source: GPS", "Satellites: 7"]
+        );
+    }
+
+    #[test]
+    fn test_show_saved_centroid() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::ShowSavedCentroid, &mut model);
+        assert_eq!(model.msg, "There are no saved positions.");
+        for (name, lat, long) in [("A", 10.0, 179.0), ("B", -10.0, -179.0)] {
+            let _update = app.update(Event::GeolocationUpdate(Ok(geo(lat, long, 0))), &mut model);
+            let _update = app.update(Event::SaveCurrPos(name.into()), &mut model);
+        }
+        let _update = app.update(Event::ShowSavedCentroid, &mut model);
+        // Naive averaging of the longitudes would give 0°.
+        assert!(model.msg.starts_with("Centroid of the saved positions: "));
+        assert!(model.msg.contains("180.00000°"), "{}", model.msg);
+    }
+
+    #[test]
+    fn test_set_high_accuracy() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::StartGeolocation, &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let update = app.update(Event::SetHighAccuracy(false), &mut model);
+        let geo_requests = update
+            .effects
+            .iter()
+            .filter_map(|effect| match effect {
+                Effect::Geolocation(req) => Some(req.operation),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let opts = GeoOptions {
+            enable_high_accuracy: false,
+            ..GEOLOCATION_OPTIONS
+        };
+        assert_eq!(
+            geo_requests,
+            [GeoRequest::ClearWatch, GeoRequest::WatchPosition(opts)]
+        );
+        let sets = storage_operations(update.effects)
+            .into_iter()
+            .filter_map(|operation| match operation {
+                KeyValueOperation::Set { key, value } => Some((key, value)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sets, [(HIGH_ACCURACY_KEY.to_string(), encode(&false))]);
+        assert_eq!(model.all_positions.unwrap().way.nodes().len(), 1);
+    }
+
+    #[test]
+    fn test_geofence() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Home".into()), &mut model);
+        let _update = app.update(
+            Event::AddGeofence {
+                name: "Home".into(),
+                radius: Length::from_metres(100.0),
+            },
+            &mut model,
+        );
+        model.msg = CompactString::default();
+
+        // The first position only initializes the state.
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 1))), &mut model);
+        assert_eq!(model.msg, "");
+        assert_eq!(model.geofences["Home"].inside, Some(true));
+
+        // About 111 m away.
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.001, 0.0, 2))), &mut model);
+        assert_eq!(model.msg, "Left Home");
+        model.msg = CompactString::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.002, 0.0, 3))), &mut model);
+        assert_eq!(model.msg, "");
+
+        // About 56 m away.
+        let _update = app.update(
+            Event::GeolocationUpdate(Ok(geo(0.0005, 0.0, 4))),
+            &mut model,
+        );
+        assert_eq!(model.msg, "Entered Home");
+
+        let _update = app.update(Event::DelSavedPos("Home".into()), &mut model);
+        assert!(model.geofences.is_empty());
+        let _update = app.update(
+            Event::AddGeofence {
+                name: "Home".into(),
+                radius: Length::from_metres(100.0),
+            },
+            &mut model,
+        );
+        assert!(model.msg.starts_with("Error"));
+    }
+
+    #[test]
+    fn test_target() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Near".into()), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.01, 0.0, 1))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Far".into()), &mut model);
+        assert_eq!(
+            ViewModel::new(&model).target.as_deref(),
+            Some("You have arrived at Far.")
+        );
+
+        // About 1112 m from Far and 556 m from Near, without any speed.
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.005, 0.0, 2))), &mut model);
+        let _update = app.update(Event::SelectTarget("Far".into()), &mut model);
+        assert_eq!(
+            ViewModel::new(&model).target.as_deref(),
+            Some("Far: 556 m, ETA —")
+        );
+
+        let _update = app.update(Event::DelSavedPos("Far".into()), &mut model);
+        assert_eq!(model.target, None);
+        assert!(ViewModel::new(&model).target.unwrap().starts_with("Near: "));
+    }
+
+    #[test]
+    fn test_notify_arrival() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Home".into()), &mut model);
+        let update = app.update(Event::GeolocationUpdate(Ok(geo(0.01, 0.0, 1))), &mut model);
+        assert!(notify_operations(update.effects).is_empty());
+
+        // About 11 m from Home.
+        let update = app.update(
+            Event::GeolocationUpdate(Ok(geo(0.0001, 0.0, 2))),
+            &mut model,
+        );
+        assert_eq!(
+            notify_operations(update.effects),
+            [crate::NotifyUserOperation {
+                kind: NotifyKind::Arrival,
+                message: "You have arrived at Home.".into(),
+            }]
+        );
+        // The user is only notified once.
+        let update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 3))), &mut model);
+        assert!(notify_operations(update.effects).is_empty());
+    }
+
+    #[test]
+    fn test_suggest_pos_name() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(1.0, 2.0, 0))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("Town Hall".into()), &mut model);
+        let update = app.update(Event::SuggestPosName, &mut model);
+        let mut request = update
+            .effects
+            .into_iter()
+            .find_map(|effect| match effect {
+                Effect::ReverseGeocode(request) => Some(request),
+                _ => None,
+            })
+            .expect("Expected a reverse geocode request");
+        assert_eq!(request.operation.coords, LatLong::from_degrees(1.0, 2.0));
+        let update = app
+            .resolve(
+                &mut request,
+                ReverseGeocodeResponse {
+                    name: Some("Town Hall".into()),
+                },
+            )
+            .unwrap();
+        for event in update.events {
+            let _update = app.update(event, &mut model);
+        }
+        assert_eq!(
+            ViewModel::new(&model).suggested_name.as_deref(),
+            Some("Town Hall")
+        );
+
+        // The suggestion is kept when the position can't be saved.
+        let _update = app.update(Event::SaveCurrPos("".into()), &mut model);
+        assert_eq!(
+            model.msg,
+            "Error: There is already a position named Town Hall"
+        );
+        assert_eq!(model.suggested_name.as_deref(), Some("Town Hall"));
+
+        let _update = app.update(Event::DelSavedPos("Town Hall".into()), &mut model);
+        let _update = app.update(Event::SaveCurrPos("".into()), &mut model);
+        assert!(model.saved_positions_names.contains_key("Town Hall"));
+        assert_eq!(model.suggested_name, None);
+    }
+
+    #[test]
+    fn test_upload_way() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let mut rec = RecordedWay::new();
+        rec.add(&geo(1.0, 2.0, 0));
+        model.recorded_ways.insert("Walk".into(), rec);
+
+        let _update = app.update(Event::UploadWay("Walk".into()), &mut model);
+        assert!(model.msg.starts_with("Error: No sync endpoint"));
+
+        let _update = app.update(
+            Event::SetSyncEndpoint("https://example.com/ways".into()),
+            &mut model,
+        );
+        let update = app.update(Event::UploadWay("Walk".into()), &mut model);
+        let request = update
+            .effects
+            .into_iter()
+            .find_map(|effect| match effect {
+                Effect::Http(request) => Some(request.operation),
+                _ => None,
+            })
+            .expect("Expected an HTTP request");
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.url, "https://example.com/ways");
+        assert!(request
+            .headers
+            .iter()
+            .any(|header| header.name.eq_ignore_ascii_case("content-type")
+                && header.value == "application/gpx+xml"));
+        let gpx = String::from_utf8(request.body).unwrap();
+        assert!(gpx.contains("<name>Walk</name>"), "{gpx}");
+    }
+
+    #[test]
+    fn test_way_uploaded() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        for (res, msg) in [
+            (Ok(()), "Walk has been uploaded."),
+            (
+                Err(SyncError::Status(500)),
+                "Error: The server rejected Walk with status 500.",
+            ),
+            (
+                Err(SyncError::Network("timeout".into())),
+                "Error: Failed to reach the server to upload Walk: timeout",
+            ),
+        ] {
+            let _update = app.update(
+                Event::WayUploaded {
+                    name: "Walk".into(),
+                    res,
+                },
+                &mut model,
+            );
+            assert_eq!(model.msg, msg);
+        }
+
+        let response = |status| {
+            crux_http::testing::ResponseBuilder::with_status(status)
+                .body(Vec::new())
+                .build()
+        };
+        assert_eq!(
+            SyncError::from_response(Ok(response(crux_http::http::StatusCode::Created))),
+            Ok(())
+        );
+        assert_eq!(
+            SyncError::from_response(Ok(response(crux_http::http::StatusCode::NotFound))),
+            Err(SyncError::Status(404))
+        );
+    }
+
+    #[test]
+    fn test_nearest_saved_position() {
+        let app = AppTester::<GeoApp, _>::default();
+        let mut model = Model::default();
+        let _update = app.update(Event::NearestSavedPosition, &mut model);
+        assert_eq!(model.msg, "Error: The current position is not known.");
+
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.0, 0))), &mut model);
+        let _update = app.update(Event::NearestSavedPosition, &mut model);
+        assert_eq!(model.msg, "There are no saved positions.");
+
+        let _update = app.update(Event::SaveCurrPos("Start".into()), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.01, 0.0, 1))), &mut model);
+        let _update = app.update(Event::SaveCurrPos("North".into()), &mut model);
+        let _update = app.update(Event::GeolocationUpdate(Ok(geo(0.0, 0.001, 2))), &mut model);
+        let _update = app.update(Event::NearestSavedPosition, &mut model);
+        assert_eq!(model.msg, "Nearest saved position: Start: 111 m, 270° (W)");
+        assert_eq!(model.view_n_saved_positions, 0);
     }
 }
@@ -0,0 +1,230 @@
+//! The format of data in persistant storage.
+//!
+//! All data is saved as bincode in a `PersistEnvelope` with the version of the format, so that
+//! old data can be migrated when the format changes. The envelope starts with `PERSIST_MAGIC`.
+//! Data without it was saved before the envelope was added, and is raw bincode which is treated as
+//! version 0.
+
+use std::collections::HashMap;
+
+use bincode::Options as _;
+use compact_str::CompactString;
+use rstar::RTree;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::geo_types::{decode_legacy_saved_positions, LegacyRecordedWay, RecordedWay, SavedPos};
+
+/// The current version of the format of persisted data.
+///
+/// Version 2 added `RecordedWay::meta`.
+pub const PERSIST_VERSION: u16 = 2;
+
+/// The first bytes of all data in a `PersistEnvelope`.
+///
+/// Data saved before the envelope was added starts with the length of a collection or the index
+/// of an enum variant, which is never this.
+pub const PERSIST_MAGIC: [u8; 4] = *b"GEOA";
+
+/// Persisted data together with the version of its format.
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistEnvelope<T> {
+    /// Always `PERSIST_MAGIC`.
+    magic: [u8; 4],
+    version: u16,
+    payload: T,
+}
+
+/// Bincode options like `bincode::deserialize` but which reject trailing bytes.
+fn strict_bincode() -> impl bincode::Options {
+    bincode::DefaultOptions::new().with_fixint_encoding()
+}
+
+/// Encode data to persist with the current version.
+pub fn encode<T: Serialize>(payload: &T) -> Vec<u8> {
+    bincode::serialize(&PersistEnvelope {
+        magic: PERSIST_MAGIC,
+        version: PERSIST_VERSION,
+        payload,
+    })
+    .unwrap()
+}
+
+/// Decode persisted data.
+///
+/// Data of an older version is passed to `migrate` together with its version. The payload is
+/// passed for data in an envelope and the whole data for version 0. If `lenient` is set, data
+/// without an envelope may also be JSON of the payload.
+pub fn decode<T: DeserializeOwned>(
+    bytes: &[u8],
+    lenient: bool,
+    migrate: impl FnOnce(u16, &[u8]) -> bincode::Result<T>,
+) -> bincode::Result<T> {
+    let Some(versioned) = bytes.strip_prefix(&PERSIST_MAGIC) else {
+        return migrate(0, bytes).or_else(|e| {
+            if lenient {
+                serde_json::from_slice(bytes).map_err(|_| e)
+            } else {
+                Err(e)
+            }
+        });
+    };
+    match bincode::deserialize::<u16>(versioned)? {
+        PERSIST_VERSION => strict_bincode()
+            .deserialize::<PersistEnvelope<T>>(bytes)
+            .map(|envelope| envelope.payload),
+        version if version > 0 && version < PERSIST_VERSION => migrate(version, &versioned[2..]),
+        version => Err(Box::new(bincode::ErrorKind::Custom(format!(
+            "Unknown version: {version}"
+        )))),
+    }
+}
+
+/// Migrate data whose format hasn't changed since version 0.
+pub fn migrate_unchanged<T: DeserializeOwned>(_version: u16, bytes: &[u8]) -> bincode::Result<T> {
+    strict_bincode().deserialize(bytes)
+}
+
+/// Migrate saved positions to the current version.
+pub fn migrate_saved_positions(
+    version: u16,
+    bytes: &[u8],
+) -> bincode::Result<(RTree<SavedPos>, HashMap<CompactString, SavedPos>)> {
+    match version {
+        // Before version 1, saved positions didn't have `SavedPos::averaged`.
+        0 => decode_legacy_saved_positions(bytes),
+        1 => strict_bincode().deserialize(bytes),
+        _ => unreachable!("Saved positions of version {version} are not older than the current"),
+    }
+}
+
+/// Migrate a recorded way to the current version.
+pub fn migrate_recorded_way(version: u16, bytes: &[u8]) -> bincode::Result<RecordedWay> {
+    match version {
+        // Before version 2, ways didn't have any metadata.
+        0 | 1 => strict_bincode()
+            .deserialize::<LegacyRecordedWay>(bytes)
+            .map(Into::into),
+        _ => unreachable!("A recorded way of version {version} is not older than the current"),
+    }
+}
+
+/// Migrate recorded ways saved under a single key to the current version.
+pub fn migrate_recorded_ways(
+    version: u16,
+    bytes: &[u8],
+) -> bincode::Result<HashMap<CompactString, RecordedWay>> {
+    match version {
+        0 | 1 => strict_bincode()
+            .deserialize::<HashMap<CompactString, LegacyRecordedWay>>(bytes)
+            .map(|ways| ways.into_iter().map(|(k, v)| (k, v.into())).collect()),
+        _ => unreachable!("Recorded ways of version {version} are not older than the current"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use jord::LatLong;
+
+    use super::super::geo_types::Position;
+    use super::*;
+
+    fn saved_positions() -> (RTree<SavedPos>, HashMap<CompactString, SavedPos>) {
+        let pos = SavedPos {
+            name: "Home".into(),
+            pos: Position {
+                coords: LatLong::from_degrees(1.0, 2.0),
+                altitude: None,
+                accuracy: None,
+                altitude_accuracy: None,
+            },
+            timestamp: DateTime::from_timestamp(3, 0).unwrap(),
+            averaged: None,
+        };
+        (
+            RTree::bulk_load(vec![pos.clone()]),
+            HashMap::from([(pos.name.clone(), pos)]),
+        )
+    }
+
+    #[test]
+    fn test_decode_current() {
+        let (rtree, names) = saved_positions();
+        let bytes = encode(&(&rtree, &names));
+        assert_eq!(bytes[..4], PERSIST_MAGIC);
+        assert_eq!(bytes[4..6], PERSIST_VERSION.to_le_bytes());
+        let (decoded_rtree, decoded_names) =
+            decode(&bytes, false, migrate_saved_positions).unwrap();
+        assert_eq!(decoded_names, names);
+        assert_eq!(decoded_rtree.size(), 1);
+    }
+
+    #[test]
+    fn test_decode_legacy() {
+        let (_, names) = saved_positions();
+        // Saved positions without `SavedPos::averaged`. A struct is encoded like a tuple and the
+        // r-tree is rebuilt from the names.
+        let legacy_names = names
+            .iter()
+            .map(|(name, x)| (name, (&x.name, &x.pos, x.timestamp)))
+            .collect::<HashMap<_, _>>();
+        let bytes = bincode::serialize(&(RTree::<SavedPos>::new(), legacy_names)).unwrap();
+        let (decoded_rtree, decoded_names) =
+            decode(&bytes, false, migrate_saved_positions).unwrap();
+        assert_eq!(decoded_names, names);
+        assert_eq!(decoded_rtree.size(), 1);
+
+        let bytes = bincode::serialize(&7u32).unwrap();
+        assert_eq!(decode::<u32>(&bytes, false, migrate_unchanged).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_migrate_recorded_way() {
+        let (_, names) = saved_positions();
+        let mut rec = RecordedWay::new();
+        rec.add(&names["Home"]);
+        // A way without `meta`. A struct with one field is encoded like a 1-tuple.
+        let legacy = (&rec.way,);
+
+        let bytes = bincode::serialize(&legacy).unwrap();
+        assert_eq!(decode(&bytes, false, migrate_recorded_way).unwrap(), rec);
+        let bytes = bincode::serialize(&PersistEnvelope {
+            magic: PERSIST_MAGIC,
+            version: 1,
+            payload: legacy,
+        })
+        .unwrap();
+        assert_eq!(decode(&bytes, false, migrate_recorded_way).unwrap(), rec);
+        let bytes = bincode::serialize(&HashMap::from([("Walk", legacy)])).unwrap();
+        assert_eq!(
+            decode(&bytes, false, migrate_recorded_ways).unwrap(),
+            HashMap::from([("Walk".into(), rec.clone())])
+        );
+
+        let json = serde_json::to_vec(&serde_json::json!({ "way": rec.way })).unwrap();
+        assert_eq!(decode(&json, true, migrate_recorded_way).unwrap(), rec);
+
+        rec.meta.note = Some("Windy".into());
+        let bytes = encode(&rec);
+        assert_eq!(decode(&bytes, false, migrate_recorded_way).unwrap(), rec);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(decode::<u32>(b"", false, migrate_unchanged).is_err());
+        let bytes = bincode::serialize(&PersistEnvelope {
+            magic: PERSIST_MAGIC,
+            version: PERSIST_VERSION + 1,
+            payload: 7u64,
+        })
+        .unwrap();
+        assert!(decode::<u64>(&bytes, false, migrate_unchanged).is_err());
+        // Data with the magic bytes is never decoded as legacy data.
+        assert!(decode::<[u8; 4]>(&PERSIST_MAGIC, false, migrate_unchanged).is_err());
+        assert!(decode::<Vec<u8>>(b"[7]", false, migrate_unchanged).is_err());
+        assert_eq!(
+            decode::<Vec<u8>>(b"[7]", true, migrate_unchanged).unwrap(),
+            [7]
+        );
+    }
+}
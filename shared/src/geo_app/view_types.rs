@@ -4,25 +4,117 @@
 //! have to bother with that.
 
 use std::fmt;
+use std::hash::{DefaultHasher, Hash as _, Hasher as _};
 
 use arrayvec::ArrayVec;
 use chrono::{prelude::*, TimeDelta};
 use compact_str::{format_compact, CompactString, ToCompactString};
-use crux_geolocation::GeoInfo;
-use jord::{spherical::Sphere, LatLong};
+use crux_geolocation::{GeoError, GeoInfo};
+use jord::{spherical::Sphere, Angle, LatLong, Length, Speed};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 
 use super::geo_traits::*;
-use super::{Event, Model, RecordedWay, SavedPos, PLANET};
+use super::geo_types::{are_antipodal, Line, MIN_MOVING_SPEED};
+use super::i18n::Text;
+use super::{travel_bearing, Event, Model, RecordedWay, SavedPos, SpeedUnit, UnitSystem, PLANET};
+
+/// The name of the way since app start.
+pub(crate) const ALL_POSITIONS_NAME: &str = "Since app start";
 
 /// Precition for latitude and longitude.
 const COORD_PRECITION: usize = 5;
 /// Precition for altitude, volocity and other things.
 const PRECITION: usize = 1;
+/// The largest precition which can be set with `Event::SetPrecision`.
+pub(crate) const MAX_PRECITION: usize = 9;
+/// The number of segments a route preview is divided into, see `Event::PreviewRouteTo`.
+const ROUTE_PREVIEW_SEGMENTS: usize = 64;
+
+/// The number of decimals to show for the current position and saved positions, set with
+/// `Event::SetPrecision`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Precision {
+    /// Decimals of latitudes and longitudes in degrees.
+    pub coord: usize,
+    /// Decimals of altitudes, speeds and other things.
+    pub other: usize,
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Self {
+            coord: COORD_PRECITION,
+            other: PRECITION,
+        }
+    }
+}
+
+/// Meters per foot.
+const METRES_PER_FOOT: f64 = 0.3048;
+/// Meters per mile.
+const METRES_PER_MILE: f64 = 1609.344;
+/// Meters per second per mile per hour.
+const METRES_PER_SECOND_PER_MPH: f64 = 0.44704;
+/// Meters per second per kilometre per hour.
+const METRES_PER_SECOND_PER_KMH: f64 = 1.0 / 3.6;
+/// Meters per second per knot (nautical mile per hour).
+const METRES_PER_SECOND_PER_KNOT: f64 = 1852.0 / 3600.0;
+
+/// Format a short length, like an altitude or an accuracy, in meters or feet.
+fn format_length(length: Length, precition: usize, units: UnitSystem) -> CompactString {
+    match units {
+        UnitSystem::Metric => format_compact!("{:.*} m", precition, length.as_metres()),
+        UnitSystem::Imperial => {
+            format_compact!("{:.*} ft", precition, length.as_metres() / METRES_PER_FOOT)
+        }
+    }
+}
+
+/// Format a possibly long length, like a distance or the length of a way. Imperial lengths of a
+/// tenth of a mile or more are given in miles.
+pub(crate) fn format_long_length(length: Length, units: UnitSystem) -> CompactString {
+    let metres = length.as_metres();
+    match units {
+        UnitSystem::Metric => format_compact!("{} m", metres.round()),
+        UnitSystem::Imperial if metres < METRES_PER_MILE / 10.0 => {
+            format_compact!("{} ft", (metres / METRES_PER_FOOT).round())
+        }
+        UnitSystem::Imperial => format_compact!("{:.2} mi", metres / METRES_PER_MILE),
+    }
+}
+
+/// Format a speed in some unit.
+fn format_speed(speed: Speed, precition: usize, unit: SpeedUnit) -> CompactString {
+    let metres_per_second = speed.as_metres_per_second();
+    let (value, symbol) = match unit {
+        SpeedUnit::MetersPerSecond => (metres_per_second, "m/s"),
+        SpeedUnit::KmPerHour => (metres_per_second / METRES_PER_SECOND_PER_KMH, "km/h"),
+        SpeedUnit::Mph => (metres_per_second / METRES_PER_SECOND_PER_MPH, "mph"),
+        SpeedUnit::Knots => (metres_per_second / METRES_PER_SECOND_PER_KNOT, "kn"),
+    };
+    format_compact!("{:.*} {}", precition, value, symbol)
+}
+
+/// The 16 points of the compass, starting at north and going clockwise.
+const COMPASS_POINTS: [&str; 16] = [
+    "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW",
+    "NNW",
+];
+
+/// Get the compass point (like "NE") closest to a bearing.
+fn compass_point(bearing: Angle) -> &'static str {
+    let degrees = bearing.as_degrees().rem_euclid(360.0);
+    let sector = (degrees / 22.5).round() as usize % COMPASS_POINTS.len();
+    COMPASS_POINTS[sector]
+}
 
 /// Format latitude, longitude, altitude and accuracy.
-fn format_pos(pos: &(impl Coords + Altitude)) -> ArrayVec<CompactString, 5> {
+fn format_pos(
+    pos: &(impl Coords + Altitude),
+    precision: Precision,
+    units: UnitSystem,
+) -> ArrayVec<CompactString, 5> {
     let latitude = pos.coords().latitude().as_degrees();
     let longitude = pos.coords().longitude().as_degrees();
     let north_south = if latitude >= 0.0 { "North" } else { "South" };
@@ -30,38 +122,84 @@ fn format_pos(pos: &(impl Coords + Altitude)) -> ArrayVec<CompactString, 5> {
     let mut properties = ArrayVec::new();
     properties.push(format_compact!(
         "Latitude: {:.*}° {}",
-        COORD_PRECITION,
+        precision.coord,
         latitude,
         north_south
     ));
     properties.push(format_compact!(
         "Longitude: {:.*}° {}",
-        COORD_PRECITION,
+        precision.coord,
         longitude,
         east_west,
     ));
     if let Some(altitude) = pos.altitude() {
         properties.push(format_compact!(
-            "Altitude: {:.*} meters",
-            PRECITION,
-            altitude.as_metres()
+            "Altitude: {}",
+            format_length(altitude, precision.other, units)
         ));
     }
     if let Some(accuracy) = pos.accuracy() {
         properties.push(format_compact!(
-            "Accuracy: {} meters",
-            accuracy.as_metres().round()
+            "Accuracy: {}",
+            format_length(accuracy, 0, units)
         ));
     }
     if let Some(altitude_accuracy) = pos.altitude_accuracy() {
         properties.push(format_compact!(
-            "Altitude accuracy: {} meters",
-            altitude_accuracy.as_metres().round()
+            "Altitude accuracy: {}",
+            format_length(altitude_accuracy, 0, units)
         ));
     }
     properties
 }
 
+/// Format latitude and longitude on one line.
+pub(crate) fn format_coords(coords: LatLong) -> CompactString {
+    let latitude = coords.latitude().as_degrees();
+    let longitude = coords.longitude().as_degrees();
+    format_compact!(
+        "{:.*}° {}, {:.*}° {}",
+        COORD_PRECITION,
+        latitude,
+        if latitude >= 0.0 { "North" } else { "South" },
+        COORD_PRECITION,
+        longitude,
+        if longitude >= 0.0 { "East" } else { "West" },
+    )
+}
+
+/// A bearing relative to a heading, both clockwise from north, in [0°, 360°). Straight ahead is
+/// 0°.
+fn relative_bearing(bearing: Angle, heading: Angle) -> Angle {
+    let degrees = (bearing.as_degrees() - heading.as_degrees()).rem_euclid(360.0);
+    // `rem_euclid` may round tiny negative numbers up to 360.
+    Angle::from_degrees(if degrees >= 360.0 { 0.0 } else { degrees })
+}
+
+/// Format the distance along the surface and the initial bearing from one point to another. If
+/// `heading` is set, the bearing is relative to it, see `BearingMode::RelativeToHeading`.
+pub(crate) fn format_distance_and_bearing(
+    from: LatLong,
+    to: LatLong,
+    heading: Option<Angle>,
+    units: UnitSystem,
+) -> CompactString {
+    let bearing = Sphere::initial_bearing(from.to_nvector(), to.to_nvector());
+    let distance = format_long_length(PLANET.distance(from.to_nvector(), to.to_nvector()), units);
+    match heading {
+        Some(heading) => format_compact!(
+            "{distance}, {}° from the heading",
+            // A bearing like 359.6° is rounded up to 360°, which should be shown as 0°.
+            relative_bearing(bearing, heading).as_degrees().round() % 360.0
+        ),
+        None => format_compact!(
+            "{distance}, {}° ({})",
+            bearing.as_degrees().round(),
+            compass_point(bearing)
+        ),
+    }
+}
+
 /// Format a timestamp.
 fn format_timestamp(timestamp: DateTime<Utc>) -> CompactString {
     timestamp
@@ -70,6 +208,127 @@ fn format_timestamp(timestamp: DateTime<Utc>) -> CompactString {
         .to_compact_string()
 }
 
+/// Format a duration as hours, minutes and seconds, as `H:MM:SS`. If `clock` is set, the hours
+/// have at least two digits like on a clock, as `HH:MM:SS`. Negative durations are formatted as
+/// zero.
+fn format_duration(duration: TimeDelta, clock: bool) -> CompactString {
+    let secs = duration.num_seconds().max(0);
+    let width = if clock { 2 } else { 1 };
+    format_compact!(
+        "{:0width$}:{:02}:{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// How long the recording since app start has been running, or `None` if it doesn't have any
+/// nodes or the current time isn't known yet.
+fn recording_duration(model: &Model) -> Option<TimeDelta> {
+    let first = model.all_positions.as_ref()?.way().nodes().first()?;
+    fix_age(first.timestamp(), model.curr_time)
+}
+
+/// The age of the last position after which the GPS is considered stale.
+const GPS_STALE_AFTER: TimeDelta = TimeDelta::seconds(10);
+
+/// The age of a position retrieved at `timestamp`, or `None` if the current time isn't known yet.
+/// The current time may lag behind a bit, so the age is never negative.
+fn fix_age(timestamp: DateTime<Utc>, curr_time: Option<DateTime<Utc>>) -> Option<TimeDelta> {
+    curr_time.map(|t| (t - timestamp).max(TimeDelta::zero()))
+}
+
+/// Whether a position of a certain age is too old to be trusted.
+fn is_stale(age: Option<TimeDelta>) -> bool {
+    age.is_some_and(|x| x > GPS_STALE_AFTER)
+}
+
+/// The distance to a target within which the target is considered reached.
+pub(crate) const ARRIVED_DISTANCE: f64 = 20.0;
+
+/// Whether the direction of travel is toward a target or not.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TravelDirection {
+    /// The target is at most 45° from the heading.
+    Toward,
+    /// The target is between 45° and 135° to the left.
+    Left,
+    /// The target is between 45° and 135° to the right.
+    Right,
+    /// The target is more than 135° from the heading.
+    Away,
+}
+
+impl TravelDirection {
+    /// The direction of travel given the heading and the bearing to the target, both clockwise
+    /// from north.
+    fn new(heading: Angle, target_bearing: Angle) -> Self {
+        // The angle from the heading to the target in (-180°, 180°], positive to the right.
+        let mut diff = (target_bearing.as_degrees() - heading.as_degrees()).rem_euclid(360.0);
+        if diff > 180.0 {
+            diff -= 360.0;
+        }
+        match diff {
+            x if x.abs() <= 45.0 => Self::Toward,
+            x if x.abs() >= 135.0 => Self::Away,
+            x if x > 0.0 => Self::Right,
+            _ => Self::Left,
+        }
+    }
+}
+
+impl fmt::Display for TravelDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Toward => "toward",
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Away => "away",
+        })
+    }
+}
+
+/// The direction of travel relative to the target selected with `Event::SelectTarget`, or `None`
+/// if there is no selected target or if the heading is unknown, for instance when standing
+/// still. See `travel_bearing`.
+fn target_direction(model: &Model, curr_pos: &GeoInfo) -> Option<TravelDirection> {
+    let target = model.saved_positions_names.get(model.target.as_ref()?)?;
+    let target_bearing =
+        Sphere::initial_bearing(curr_pos.coords.to_nvector(), target.pos.coords.to_nvector());
+    Some(TravelDirection::new(
+        travel_bearing(curr_pos)?,
+        target_bearing,
+    ))
+}
+
+/// The estimated time to travel a distance at a speed, or "—" if the speed is unknown or too low
+/// to be considered moving.
+fn format_eta(distance: Length, speed: Option<Speed>) -> CompactString {
+    speed
+        .filter(|speed| speed.as_metres_per_second() >= MIN_MOVING_SPEED)
+        .and_then(|speed| {
+            let secs = distance.as_metres() / speed.as_metres_per_second();
+            TimeDelta::try_seconds(secs.round() as i64)
+        })
+        .map_or_else(|| "—".into(), |x| format_duration(x, false))
+}
+
+/// The distance and the estimated time of arrival to the target, see `Event::SelectTarget`.
+fn format_target(model: &Model, curr_coords: LatLong) -> Option<CompactString> {
+    let target = model.target_pos(curr_coords)?;
+    let distance = PLANET.distance(curr_coords.to_nvector(), target.pos.coords.to_nvector());
+    Some(if distance.as_metres() < ARRIVED_DISTANCE {
+        Text::Arrived(&target.name).translate(model.language)
+    } else {
+        format_compact!(
+            "{}: {}, ETA {}",
+            target.name,
+            format_long_length(distance, model.unit_system),
+            format_eta(distance, model.smoothed.speed())
+        )
+    })
+}
+
 /// A trait for things which consists of a short summary, some properties, and maybe even some more
 /// properties.
 pub trait ViewObject {
@@ -81,6 +340,154 @@ pub trait ViewObject {
     fn more_properties(&self) -> &[CompactString] {
         &[]
     }
+    /// Other actions than deletion which can be made on the object, given as a label and an
+    /// event.
+    fn actions(&self) -> Vec<(&'static str, Event)> {
+        Vec::new()
+    }
+}
+
+/// Latitude and longitude in degrees, for shells which place things on a map.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct ViewCoords {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl From<LatLong> for ViewCoords {
+    fn from(x: LatLong) -> Self {
+        Self {
+            latitude: x.latitude().as_degrees(),
+            longitude: x.longitude().as_degrees(),
+        }
+    }
+}
+
+// The view model must be comparable and hashable, so the coordinates are compared bitwise.
+impl PartialEq for ViewCoords {
+    fn eq(&self, other: &Self) -> bool {
+        self.latitude.to_bits() == other.latitude.to_bits()
+            && self.longitude.to_bits() == other.longitude.to_bits()
+    }
+}
+
+impl Eq for ViewCoords {}
+
+impl std::hash::Hash for ViewCoords {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.latitude.to_bits().hash(state);
+        self.longitude.to_bits().hash(state);
+    }
+}
+
+/// A length in metres, for shells which draw things on a map, like the accuracy circle around a
+/// position. It is serialized as a plain number.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(transparent)]
+pub struct ViewMetres(pub f64);
+
+impl From<Length> for ViewMetres {
+    fn from(x: Length) -> Self {
+        Self(x.as_metres())
+    }
+}
+
+// Compared bitwise like `ViewCoords`.
+impl PartialEq for ViewMetres {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for ViewMetres {}
+
+impl std::hash::Hash for ViewMetres {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+/// The nodes of a way and the speed of every segment, see `Event::ViewWayDetail`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ViewWayDetail {
+    pub name: CompactString,
+    pub nodes: Vec<ViewCoords>,
+    /// The speed in metres per second between every two consecutive nodes.
+    pub segment_speeds: Vec<f64>,
+}
+
+impl ViewWayDetail {
+    fn new(name: &str, rec: &RecordedWay) -> Self {
+        Self {
+            name: name.into(),
+            nodes: rec.way.nodes().iter().map(|x| x.coords().into()).collect(),
+            segment_speeds: rec
+                .way
+                .segment_speeds()
+                .into_iter()
+                .map(|x| x.as_metres_per_second())
+                .collect(),
+        }
+    }
+}
+
+// Compared bitwise like `ViewCoords`.
+impl PartialEq for ViewWayDetail {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.nodes == other.nodes
+            && self.segment_speeds.len() == other.segment_speeds.len()
+            && self
+                .segment_speeds
+                .iter()
+                .zip(&other.segment_speeds)
+                .all(|(a, b)| a.to_bits() == b.to_bits())
+    }
+}
+
+impl Eq for ViewWayDetail {}
+
+impl std::hash::Hash for ViewWayDetail {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.nodes.hash(state);
+        for speed in &self.segment_speeds {
+            speed.to_bits().hash(state);
+        }
+    }
+}
+
+/// The great-circle route from the current position to a saved position, see
+/// `Event::PreviewRouteTo`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ViewRoute {
+    /// The name of the saved position.
+    pub target: CompactString,
+    /// Evenly spaced points along the route, from the current position to the target.
+    pub points: Vec<ViewCoords>,
+    /// The total distance.
+    pub distance: CompactString,
+}
+
+impl ViewRoute {
+    /// `None` if the target doesn't exist or is antipodal to the current position.
+    fn new(model: &Model, curr_coords: LatLong) -> Option<Self> {
+        let name = model.route_preview.as_ref()?;
+        let target = model.saved_positions_names.get(name)?.pos.coords;
+        if are_antipodal(curr_coords.to_nvector(), target.to_nvector()) {
+            return None;
+        }
+        let line = Line::new(curr_coords, target);
+        Some(Self {
+            target: name.clone(),
+            points: line
+                .sample(ROUTE_PREVIEW_SEGMENTS)
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            distance: format_long_length(line.length(), model.unit_system),
+        })
+    }
 }
 
 /// Information about a saved position.
@@ -92,41 +499,56 @@ pub struct ViewSavedPos {
     pub summary: CompactString,
     /// A number of properties, like latitude and timestamp.
     pub properties: ArrayVec<CompactString, 6>,
+    /// Information about how the position was averaged. May be empty.
+    pub more_properties: ArrayVec<CompactString, 1>,
     /// Whether it can be deleted.
     pub deleateable: bool,
+    /// The coordinates of the position. Added last to keep the order of the other fields.
+    pub coords: ViewCoords,
+    /// The accuracy of the position as the radius of a circle around it, if it is known.
+    pub accuracy: Option<ViewMetres>,
 }
 
 impl ViewSavedPos {
-    fn new(saved_pos: SavedPos, curr_pos: Option<LatLong>, deleateable: bool) -> Self {
+    pub(crate) fn new(
+        saved_pos: SavedPos,
+        curr_pos: Option<LatLong>,
+        heading: Option<Angle>,
+        deleateable: bool,
+        precision: Precision,
+        units: UnitSystem,
+    ) -> Self {
         let summary = if let Some(curr_coords) = curr_pos {
             format_compact!(
-                "{}: {} m, {}°",
+                "{}: {}",
                 saved_pos.name,
-                PLANET
-                    .distance(curr_coords.to_nvector(), saved_pos.pos.coords.to_nvector())
-                    .as_metres()
-                    .round(),
-                Sphere::initial_bearing(
-                    curr_coords.to_nvector(),
-                    saved_pos.pos.coords.to_nvector()
-                )
-                .as_degrees()
-                .round()
+                format_distance_and_bearing(curr_coords, saved_pos.pos.coords, heading, units)
             )
         } else {
             saved_pos.name.clone()
         };
 
         let mut properties = ArrayVec::new();
-        properties.extend(format_pos(&saved_pos));
+        properties.extend(format_pos(&saved_pos, precision, units));
         properties.push(format_compact!(
             "Saved at: {}",
             format_timestamp(saved_pos.timestamp)
         ));
+        let mut more_properties = ArrayVec::new();
+        if let Some(averaging) = saved_pos.averaged {
+            more_properties.push(format_compact!(
+                "Averaged from {} fixes, σ {}",
+                averaging.samples,
+                format_length(averaging.std_dev, precision.other, units)
+            ));
+        }
         Self {
+            coords: saved_pos.pos.coords.into(),
+            accuracy: saved_pos.pos.accuracy.map(Into::into),
             name: saved_pos.name,
             summary,
             properties,
+            more_properties,
             deleateable,
         }
     }
@@ -146,20 +568,42 @@ impl ViewObject for ViewSavedPos {
             None
         }
     }
+    fn more_properties(&self) -> &[CompactString] {
+        &self.more_properties
+    }
 }
 
-/// Information about speed and bearing.
-fn format_speed_and_heading(geo: &GeoInfo) -> ArrayVec<CompactString, 2> {
+/// Information about speed and bearing, together with their smoothed values if they are known.
+fn format_speed_and_heading(
+    geo: &GeoInfo,
+    smoothed_speed: Option<Speed>,
+    smoothed_heading: Option<Angle>,
+    precision: Precision,
+    speed_unit: SpeedUnit,
+) -> ArrayVec<CompactString, 2> {
     let mut properties = ArrayVec::new();
     if let Some(speed) = geo.volocity {
-        properties.push(format_compact!(
-            "Speed: {:.*} m/s",
-            PRECITION,
-            speed.as_metres_per_second()
-        ));
+        let mut text = format_compact!(
+            "Speed: {}",
+            format_speed(speed, precision.other, speed_unit)
+        );
+        if let Some(smoothed) = smoothed_speed {
+            text += &format_compact!(
+                " (smoothed: {})",
+                format_speed(smoothed, precision.other, speed_unit)
+            );
+        }
+        properties.push(text);
     }
     if let Some(heading) = geo.bearing {
-        properties.push(format_compact!("Heading {}°", heading.as_degrees().round()));
+        let mut text = format_compact!("Heading {}°", heading.as_degrees().round());
+        if let Some(smoothed) = smoothed_heading {
+            text += &format_compact!(
+                " (smoothed: {}°)",
+                smoothed.as_degrees().rem_euclid(360.0).round()
+            );
+        }
+        properties.push(text);
     }
     properties
 }
@@ -171,40 +615,161 @@ pub struct ViewRecordedWay {
     pub name: CompactString,
     /// The elapsed time, distance and average speed.
     pub summary: CompactString,
-    /// A number of properties, like number of nodes.
-    pub properties: ArrayVec<CompactString, 3>,
+    /// A number of properties, like number of nodes, speed and elevation gain.
+    pub properties: ArrayVec<CompactString, 14>,
     pub deleateable: bool,
+    /// The coordinates of the first and last nodes. Empty if the way is empty.
+    pub more_properties: ArrayVec<CompactString, 2>,
+    /// The coordinates of the first node, or `None` if the way is empty.
+    pub start_coords: Option<ViewCoords>,
+    /// The coordinates of the last node, or `None` if the way is empty.
+    pub end_coords: Option<ViewCoords>,
+    /// The south-west and north-east corners of a box around the way, for instance to fit a map
+    /// to it, or `None` if the way is empty. The box is wrong for ways across the antimeridian,
+    /// see `Way::bounding_box`.
+    pub bounding_box: Option<(ViewCoords, ViewCoords)>,
 }
 
 impl ViewRecordedWay {
-    pub(crate) fn new(name: impl fmt::Display, rec: &RecordedWay, deleateable: bool) -> Self {
-        let summary = format_compact!("{}: {} meters", name, rec.way.length().as_metres().round());
-        let properties = if rec.way.nodes().len() > 0 {
-            ArrayVec::from([
-                format_compact!("Number of nodes: {}", rec.way.nodes().len()),
-                format_compact!(
-                    "Start time: {}",
-                    format_timestamp(rec.way().nodes().first().unwrap().timestamp())
-                ),
-                format_compact!(
-                    "End time: {}",
-                    format_timestamp(rec.way().nodes().last().unwrap().timestamp())
-                ),
-            ])
+    pub(crate) fn new(
+        name: impl fmt::Display,
+        rec: &RecordedWay,
+        deleateable: bool,
+        units: UnitSystem,
+        speed_unit: SpeedUnit,
+    ) -> Self {
+        let summary = format_compact!("{}: {}", name, format_long_length(rec.way.length(), units));
+        let mut properties = ArrayVec::new();
+        if rec.way.nodes().len() > 0 {
+            properties.push(format_compact!(
+                "Number of nodes: {}",
+                rec.way.nodes().len()
+            ));
+            properties.push(format_compact!(
+                "Start time: {}",
+                format_timestamp(rec.way().nodes().first().unwrap().timestamp())
+            ));
+            properties.push(format_compact!(
+                "End time: {}",
+                format_timestamp(rec.way().nodes().last().unwrap().timestamp())
+            ));
+            if let Some(speed) = rec.way.average_speed() {
+                properties.push(format_compact!(
+                    "Average speed: {}",
+                    format_speed(speed, PRECITION, speed_unit)
+                ));
+            }
+            let min_moving_speed = Speed::from_metres_per_second(MIN_MOVING_SPEED);
+            properties.push(format_compact!(
+                "Total time: {}",
+                format_duration(rec.way.total_time(), false)
+            ));
+            properties.push(format_compact!(
+                "Moving time: {}",
+                format_duration(rec.way.moving_time(min_moving_speed), false)
+            ));
+            if let Some(speed) = rec.way.moving_average_speed(min_moving_speed) {
+                properties.push(format_compact!(
+                    "Moving average speed: {}",
+                    format_speed(speed, PRECITION, speed_unit)
+                ));
+            }
+            if let Some(speed) = rec.way.max_speed() {
+                properties.push(format_compact!(
+                    "Max speed: {}",
+                    format_speed(speed, PRECITION, speed_unit)
+                ));
+            }
+            if let Some(accuracy) = rec.way.accuracy_percentile(0.95) {
+                properties.push(format_compact!(
+                    "95% of the nodes have an accuracy of at most {}",
+                    format_length(accuracy, 0, units)
+                ));
+            }
+            if let Some(gain) = rec.way.elevation_gain() {
+                properties.push(format_compact!(
+                    "Elevation gain: {}",
+                    format_length(gain, 0, units)
+                ));
+            }
+            if let Some(loss) = rec.way.elevation_loss() {
+                properties.push(format_compact!(
+                    "Elevation loss: {}",
+                    format_length(loss, 0, units)
+                ));
+                // Some line has altitudes at both ends, so the 3D length may differ.
+                properties.push(format_compact!(
+                    "Length including climbing: {}",
+                    format_long_length(rec.way.length_3d(), units)
+                ));
+            }
         } else {
-            let mut p = ArrayVec::new();
-            p.push("The way doesn't have any nodes.".to_compact_string());
-            p
-        };
+            properties.push("The way doesn't have any nodes.".to_compact_string());
+        }
+        if let Some(color) = &rec.meta.color {
+            properties.push(format_compact!("Color: {color}"));
+        }
+        if let Some(note) = &rec.meta.note {
+            properties.push(format_compact!("Note: {note}"));
+        }
+        let (first, last) = (rec.way.nodes().first(), rec.way.nodes().last());
+        let mut more_properties = ArrayVec::new();
+        if let (Some(first), Some(last)) = (first, last) {
+            more_properties.push(format_compact!("Start: {}", format_coords(first.coords())));
+            more_properties.push(format_compact!("End: {}", format_coords(last.coords())));
+        }
         Self {
             name: name.to_compact_string(),
             summary,
             properties,
             deleateable,
+            more_properties,
+            start_coords: first.map(|x| x.coords().into()),
+            end_coords: last.map(|x| x.coords().into()),
+            bounding_box: rec
+                .way
+                .bounding_box()
+                .map(|(min, max)| (min.into(), max.into())),
         }
     }
 }
 
+impl ViewRecordedWay {
+    /// Update the view after nodes have been added to the way. Only the length, the number of
+    /// nodes and the end time and coordinates are updated, so the start time is kept and other
+    /// properties may be stale.
+    ///
+    /// Returns `false` and does nothing if the view was made for an empty way, in which case it
+    /// must be rebuilt with `ViewRecordedWay::new`.
+    pub(crate) fn update_last(&mut self, rec: &RecordedWay, units: UnitSystem) -> bool {
+        let Some(last) = rec.way().nodes().last() else {
+            return false;
+        };
+        if self.properties.len() < 3 {
+            return false;
+        }
+        self.summary = format_compact!(
+            "{}: {}",
+            self.name,
+            format_long_length(rec.way.length(), units)
+        );
+        self.properties[0] = format_compact!("Number of nodes: {}", rec.way.nodes().len());
+        self.properties[2] = format_compact!("End time: {}", format_timestamp(last.timestamp()));
+        if let Some(end) = self.more_properties.get_mut(1) {
+            *end = format_compact!("End: {}", format_coords(last.coords()));
+        }
+        let end: ViewCoords = last.coords().into();
+        self.end_coords = Some(end);
+        if let Some((min, max)) = &mut self.bounding_box {
+            min.latitude = min.latitude.min(end.latitude);
+            min.longitude = min.longitude.min(end.longitude);
+            max.latitude = max.latitude.max(end.latitude);
+            max.longitude = max.longitude.max(end.longitude);
+        }
+        true
+    }
+}
+
 impl ViewObject for ViewRecordedWay {
     fn summary(&self) -> &CompactString {
         &self.summary
@@ -212,6 +777,9 @@ impl ViewObject for ViewRecordedWay {
     fn properties(&self) -> &[CompactString] {
         &self.properties
     }
+    fn more_properties(&self) -> &[CompactString] {
+        &self.more_properties
+    }
     fn delete(&self) -> Option<Event> {
         if self.deleateable {
             Some(Event::DelRecordedWay(self.name.clone()))
@@ -219,6 +787,17 @@ impl ViewObject for ViewRecordedWay {
             None
         }
     }
+    fn actions(&self) -> Vec<(&'static str, Event)> {
+        // Only saved ways are deleteable, and only saved ways can be acted upon.
+        if self.deleateable {
+            vec![
+                ("Export GPX", Event::ExportWayGpx(self.name.clone())),
+                ("Export JSON", Event::ExportWayJson(self.name.clone())),
+            ]
+        } else {
+            Vec::new()
+        }
+    }
 }
 
 /// The entire view model. This is everything sent to the UI.
@@ -227,82 +806,724 @@ pub struct ViewModel {
     /// Information about the GPS status. May display an error, especially if current_pos is
     /// `None`. Otherwise it should display accuracy and such.
     pub gps_status: CompactString,
+    /// The error from the last position update if it failed. This lets the UI react differently
+    /// to, for instance, `GeoError::PermissionDenied`.
+    pub gps_error: Option<GeoError>,
+    /// Whether the last position is so old that it may no longer be correct, for instance
+    /// because the signal is lost.
+    pub gps_stale: bool,
     /// Properties like latitude and volocity about the current position. May be empty.
-    pub curr_pos_properties: ArrayVec<CompactString, 7>,
+    pub curr_pos_properties: ArrayVec<CompactString, 9>,
+    /// The total distance travelled since the app was started or the odometer was reset.
+    pub odometer: CompactString,
+    /// Whether the recording since app start is saved regularly, see `Event::SetLiveAutosave`.
+    pub live_autosave: bool,
+    /// A name for the current position to suggest when saving it, see `Event::SuggestPosName`.
+    pub suggested_name: Option<CompactString>,
+    /// The distance and the estimated time of arrival to the selected or nearest saved position.
+    /// `None` if the current position or the target is unknown.
+    pub target: Option<CompactString>,
+    /// Whether the current heading is toward the selected target, see `target_direction`.
+    pub target_direction: Option<TravelDirection>,
+    /// The route set with `Event::PreviewRouteTo`. `None` if the current position is unknown.
+    pub route_preview: Option<ViewRoute>,
     /// Saved positions to show.
     pub saved_positions: Vec<ViewSavedPos>,
-    /// Recorded ways to show. Shows at least always the way since the app started.
+    /// Saved positions within the rectangle set with `Event::ViewSavedPositionsInBox`.
+    pub saved_positions_in_box: Vec<ViewSavedPos>,
+    /// Saved positions within the radius set with `Event::ViewSavedWithinRadius`, nearest first.
+    pub saved_positions_within_radius: Vec<ViewSavedPos>,
+    /// Clusters of saved positions, see `Event::ClusterSavedPositions`. Each cluster is given by
+    /// its centroid and the number of positions in it, largest first.
+    pub saved_position_clusters: Vec<(ViewCoords, usize)>,
+    /// Recorded ways to show. Shows at least always the way since the app started followed by all
+    /// active named recordings.
     pub recorded_ways: SmallVec<[ViewRecordedWay; 1]>,
     /// A message that should be displayed to the user.
     pub msg: Option<CompactString>,
+    /// The coordinates of the current position, for shells with a map. Text shells can use
+    /// `curr_pos_properties` instead.
+    pub curr_pos_coords: Option<ViewCoords>,
+    /// The accuracy of the current position as the radius of a circle around
+    /// `curr_pos_coords`, for shells with a map. Text shells can use `gps_status` instead.
+    pub curr_pos_accuracy: Option<ViewMetres>,
+    /// The way set with `Event::ViewWayDetail`.
+    pub way_detail: Option<ViewWayDetail>,
+    /// How long the recording since app start has been running, as `HH:MM:SS`. It is updated
+    /// with the current time rather than with each position.
+    pub recording_duration: Option<CompactString>,
 }
 
 impl ViewModel {
+    /// A hash of the whole view model. `GeoApp::update` compares it with the fingerprint of the
+    /// last rendered view model and only requests a render if they differ, so shells don't need
+    /// to compare view models themselves.
+    ///
+    /// The fingerprint is only comparable with fingerprints computed by the same build of the app.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
     pub fn new(model: &Model) -> Self {
-        let gps_status = match &model.curr_pos {
-            None => "No GPS information".into(),
-            Some(Err(e)) => format_compact!("GPS Error: {}", e),
+        let mut gps_stale = false;
+        let lang = model.language;
+        let mut gps_status = match &model.curr_pos {
+            None => Text::NoGpsInformation.translate(lang),
+            Some(Err(e)) => Text::GpsError(e).translate(lang),
             Some(Ok(GeoInfo {
+                timestamp,
                 accuracy,
                 altitude_accuracy,
                 ..
             })) => {
                 let mut text = CompactString::new("");
+                let age = fix_age(*timestamp, model.curr_time);
+                gps_stale = is_stale(age);
+                if let Some(age) = age {
+                    text += &Text::LastFix {
+                        secs: age.num_seconds(),
+                        stale: gps_stale,
+                    }
+                    .translate(lang);
+                }
                 if let Some(a) = accuracy {
-                    text += &format_compact!("Accuracy: {:.*} m, ", PRECITION, a.as_metres());
+                    let accuracy = format_length(*a, model.precision.other, model.unit_system);
+                    text += &Text::Accuracy(&accuracy).translate(lang);
                 }
                 if let Some(aa) = altitude_accuracy {
-                    text +=
-                        &format_compact!("Altitude accuracy: {:.*} m, ", PRECITION, aa.as_metres());
+                    let accuracy = format_length(*aa, model.precision.other, model.unit_system);
+                    text += &Text::AltitudeAccuracy(&accuracy).translate(lang);
                 }
-                let positions_in_last_minute = model
+                let window = model.rate_window();
+                let positions_in_window = model
                     .all_positions
                     .as_ref()
                     .and_then(|rec| {
                         model
                             .curr_time
                             .as_ref()
-                            .map(|t| rec.get_since(*t - TimeDelta::minutes(1)).len())
+                            .map(|t| match t.checked_sub_signed(window) {
+                                Some(since) => rec.get_since(since).len(),
+                                None => rec.way.nodes().len(),
+                            })
                     })
                     .unwrap_or(0);
-                text +=
-                    &format_compact!("{} positions in the last minute.", positions_in_last_minute);
+                text += &Text::PositionsInWindow {
+                    n: positions_in_window,
+                    secs: window.num_seconds(),
+                }
+                .translate(lang);
                 text
             }
         };
+        if model.power_saver() {
+            gps_status += &Text::PowerSaverOn.translate(lang);
+        }
+        let gps_error = model
+            .curr_pos
+            .as_ref()
+            .and_then(|x| x.as_ref().err())
+            .cloned();
         let curr_pos: Option<&GeoInfo> = model.curr_pos.as_ref().map(|x| x.as_ref().ok()).flatten();
         let mut curr_pos_properties = ArrayVec::new();
         if let Some(p) = curr_pos {
-            curr_pos_properties.extend(format_speed_and_heading(p));
-            curr_pos_properties.extend(format_pos(p));
+            curr_pos_properties.extend(format_speed_and_heading(
+                p,
+                model.smoothed.speed(),
+                model.smoothed.heading(),
+                model.precision,
+                model.speed_unit(),
+            ));
+            curr_pos_properties.extend(format_pos(p, model.precision, model.unit_system));
+            if let Some(source) = p.source {
+                curr_pos_properties.push(format_compact!("Source: {source}"));
+            }
+            if let Some(satellites) = p.satellites {
+                curr_pos_properties.push(format_compact!("Satellites: {satellites}"));
+            }
         }
         let saved_positions = model
             .view_saved_positions
             .clone()
             .into_iter()
-            .map(|p| ViewSavedPos::new(p, curr_pos.map(|x| x.coords), true))
+            .map(|p| {
+                ViewSavedPos::new(
+                    p,
+                    curr_pos.map(|x| x.coords),
+                    model.heading_reference(),
+                    true,
+                    model.precision,
+                    model.unit_system,
+                )
+            })
             .collect();
-        let recorded_ways = model
-            .all_positions
+        let saved_positions_in_box = model
+            .view_saved_positions_in_box
             .iter()
-            .map(|x| ViewRecordedWay::new("Since app start", x, false))
-            .chain(
-                model
-                    .view_recorded_ways
-                    .iter()
-                    .map(|name| ViewRecordedWay::new(name, &model.recorded_ways[name], true)),
-            )
+            .cloned()
+            .map(|p| {
+                ViewSavedPos::new(
+                    p,
+                    curr_pos.map(|x| x.coords),
+                    model.heading_reference(),
+                    true,
+                    model.precision,
+                    model.unit_system,
+                )
+            })
+            .collect();
+        let saved_positions_within_radius = model
+            .view_saved_positions_within_radius
+            .iter()
+            .cloned()
+            .map(|p| {
+                ViewSavedPos::new(
+                    p,
+                    curr_pos.map(|x| x.coords),
+                    model.heading_reference(),
+                    true,
+                    model.precision,
+                    model.unit_system,
+                )
+            })
+            .collect();
+        let recorded_ways = model
+            .all_positions_view
+            .as_ref()
+            .map(|x| x.view.clone())
+            .or_else(|| {
+                model.all_positions.as_ref().map(|x| {
+                    ViewRecordedWay::new(
+                        ALL_POSITIONS_NAME,
+                        x,
+                        false,
+                        model.unit_system,
+                        model.speed_unit(),
+                    )
+                })
+            })
+            .into_iter()
+            .chain(model.named_recordings.iter().map(|(name, x)| {
+                ViewRecordedWay::new(
+                    format_compact!("{name} (recording)"),
+                    x,
+                    false,
+                    model.unit_system,
+                    model.speed_unit(),
+                )
+            }))
+            .chain(model.view_recorded_ways.iter().map(|name| {
+                ViewRecordedWay::new(
+                    name,
+                    &model.recorded_ways[name],
+                    true,
+                    model.unit_system,
+                    model.speed_unit(),
+                )
+            }))
             .collect();
         Self {
             gps_status,
+            gps_stale,
+            gps_error,
             curr_pos_properties,
+            odometer: format_long_length(model.odometer, model.unit_system),
+            live_autosave: model.live_autosave.interval.is_some(),
+            suggested_name: model.suggested_name.clone(),
+            target: curr_pos.and_then(|p| format_target(model, p.coords)),
+            target_direction: curr_pos.and_then(|p| target_direction(model, p)),
+            route_preview: curr_pos.and_then(|p| ViewRoute::new(model, p.coords)),
             saved_positions,
+            saved_positions_in_box,
+            saved_positions_within_radius,
+            saved_position_clusters: model
+                .saved_position_clusters
+                .iter()
+                .map(|(coords, n)| ((*coords).into(), *n))
+                .collect(),
             recorded_ways,
             msg: if model.msg.is_empty() {
                 None
             } else {
                 Some(model.msg.clone())
             },
+            curr_pos_coords: curr_pos.map(|x| x.coords.into()),
+            curr_pos_accuracy: curr_pos.and_then(|x| x.accuracy).map(Into::into),
+            recording_duration: recording_duration(model).map(|x| format_duration(x, true)),
+            way_detail: model.view_way_detail.as_ref().and_then(|name| {
+                model
+                    .find_way(name)
+                    .map(|rec| ViewWayDetail::new(name, rec))
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_units() {
+        let geo = GeoInfo {
+            timestamp: DateTime::UNIX_EPOCH,
+            coords: LatLong::from_degrees(1.0, -2.0),
+            altitude: Some(Length::from_metres(100.0)),
+            accuracy: Some(Length::from_metres(3.048)),
+            altitude_accuracy: None,
+            bearing: None,
+            volocity: Some(Speed::from_metres_per_second(4.4704)),
+            source: None,
+            satellites: None,
+        };
+        assert_eq!(
+            format_pos(&geo, Precision::default(), UnitSystem::Metric).as_slice(),
+            [
+                "Latitude: 1.00000° North",
+                "Longitude: -2.00000° West",
+                "Altitude: 100.0 m",
+                "Accuracy: 3 m",
+            ]
+        );
+        assert_eq!(
+            format_pos(&geo, Precision::default(), UnitSystem::Imperial).as_slice(),
+            [
+                "Latitude: 1.00000° North",
+                "Longitude: -2.00000° West",
+                "Altitude: 328.1 ft",
+                "Accuracy: 10 ft",
+            ]
+        );
+        assert_eq!(
+            format_speed_and_heading(
+                &geo,
+                None,
+                None,
+                Precision::default(),
+                SpeedUnit::MetersPerSecond
+            )
+            .as_slice(),
+            ["Speed: 4.5 m/s"]
+        );
+        assert_eq!(
+            format_speed_and_heading(&geo, None, None, Precision::default(), SpeedUnit::Mph)
+                .as_slice(),
+            ["Speed: 10.0 mph"]
+        );
+    }
+
+    #[test]
+    fn test_format_precision() {
+        let geo = GeoInfo {
+            timestamp: DateTime::UNIX_EPOCH,
+            coords: LatLong::from_degrees(1.0, -2.0),
+            altitude: Some(Length::from_metres(100.0)),
+            accuracy: None,
+            altitude_accuracy: None,
+            bearing: None,
+            volocity: Some(Speed::from_metres_per_second(4.4704)),
+            source: None,
+            satellites: None,
+        };
+        let precision = Precision { coord: 2, other: 3 };
+        assert_eq!(
+            format_pos(&geo, precision, UnitSystem::Metric).as_slice(),
+            [
+                "Latitude: 1.00° North",
+                "Longitude: -2.00° West",
+                "Altitude: 100.000 m",
+            ]
+        );
+        assert_eq!(
+            format_speed_and_heading(&geo, None, None, precision, SpeedUnit::MetersPerSecond)
+                .as_slice(),
+            ["Speed: 4.470 m/s"]
+        );
+        let precision = Precision { coord: 0, other: 0 };
+        assert_eq!(
+            format_pos(&geo, precision, UnitSystem::Metric)[..2],
+            ["Latitude: 1° North", "Longitude: -2° West"]
+        );
+    }
+
+    #[test]
+    fn test_format_long_length() {
+        let short = Length::from_metres(100.0);
+        let long = Length::from_metres(3218.688);
+        assert_eq!(format_long_length(short, UnitSystem::Metric), "100 m");
+        assert_eq!(format_long_length(long, UnitSystem::Metric), "3219 m");
+        assert_eq!(format_long_length(short, UnitSystem::Imperial), "328 ft");
+        assert_eq!(format_long_length(long, UnitSystem::Imperial), "2.00 mi");
+    }
+
+    #[test]
+    fn test_compass_point() {
+        let table = [
+            (0.0, "N"),
+            (22.5, "NNE"),
+            (45.0, "NE"),
+            (67.5, "ENE"),
+            (90.0, "E"),
+            (112.5, "ESE"),
+            (135.0, "SE"),
+            (157.5, "SSE"),
+            (180.0, "S"),
+            (202.5, "SSW"),
+            (225.0, "SW"),
+            (247.5, "WSW"),
+            (270.0, "W"),
+            (292.5, "WNW"),
+            (315.0, "NW"),
+            (337.5, "NNW"),
+        ];
+        for (degrees, point) in table {
+            assert_eq!(compass_point(Angle::from_degrees(degrees)), point);
+            assert_eq!(compass_point(Angle::from_degrees(degrees + 10.0)), point);
+            assert_eq!(compass_point(Angle::from_degrees(degrees - 10.0)), point);
+        }
+        // Wraparound.
+        assert_eq!(compass_point(Angle::from_degrees(359.0)), "N");
+        assert_eq!(compass_point(Angle::from_degrees(360.0)), "N");
+        assert_eq!(compass_point(Angle::from_degrees(-1.0)), "N");
+        assert_eq!(compass_point(Angle::from_degrees(-90.0)), "W");
+    }
+
+    #[test]
+    fn test_update_last() {
+        let node = |secs| GeoInfo {
+            timestamp: DateTime::from_timestamp(secs, 0).unwrap(),
+            coords: LatLong::from_degrees(0.0, secs as f64 / 1000.0),
+            altitude: None,
+            accuracy: None,
+            altitude_accuracy: None,
+            bearing: None,
+            volocity: None,
+            source: None,
+            satellites: None,
+        };
+        let mut rec = RecordedWay::new();
+        let mut view = ViewRecordedWay::new(
+            "Walk",
+            &rec,
+            false,
+            UnitSystem::Metric,
+            SpeedUnit::MetersPerSecond,
+        );
+        rec.add(&node(0));
+        assert!(!view.update_last(&rec, UnitSystem::Metric));
+
+        view = ViewRecordedWay::new(
+            "Walk",
+            &rec,
+            false,
+            UnitSystem::Metric,
+            SpeedUnit::MetersPerSecond,
+        );
+        let start_time = view.properties[1].clone();
+        for secs in 1..10 {
+            rec.add(&node(secs));
+            assert!(view.update_last(&rec, UnitSystem::Metric));
+            assert_eq!(view.properties[1], start_time);
+        }
+        let rebuilt = ViewRecordedWay::new(
+            "Walk",
+            &rec,
+            false,
+            UnitSystem::Metric,
+            SpeedUnit::MetersPerSecond,
+        );
+        assert_eq!(view.summary, rebuilt.summary);
+        assert_eq!(view.properties[..3], rebuilt.properties[..3]);
+        assert_eq!(view.more_properties, rebuilt.more_properties);
+        assert_eq!(view.end_coords, rebuilt.end_coords);
+        assert_eq!(view.bounding_box, rebuilt.bounding_box);
+    }
+
+    #[test]
+    fn test_way_endpoints() {
+        let mut rec = RecordedWay::new();
+        let view = ViewRecordedWay::new(
+            "Walk",
+            &rec,
+            false,
+            UnitSystem::Metric,
+            SpeedUnit::MetersPerSecond,
+        );
+        assert!(view.more_properties.is_empty());
+        assert_eq!((view.start_coords, view.end_coords), (None, None));
+
+        for (secs, latitude, longitude) in [(0, 1.0, -2.0), (1, 1.5, -2.5), (2, -3.0, 4.0)] {
+            rec.add(&GeoInfo {
+                timestamp: DateTime::from_timestamp(secs, 0).unwrap(),
+                coords: LatLong::from_degrees(latitude, longitude),
+                altitude: None,
+                accuracy: None,
+                altitude_accuracy: None,
+                bearing: None,
+                volocity: None,
+                source: None,
+                satellites: None,
+            });
+        }
+        let view = ViewRecordedWay::new(
+            "Walk",
+            &rec,
+            false,
+            UnitSystem::Metric,
+            SpeedUnit::MetersPerSecond,
+        );
+        assert_eq!(
+            view.more_properties.as_slice(),
+            [
+                "Start: 1.00000° North, -2.00000° West",
+                "End: -3.00000° South, 4.00000° East",
+            ]
+        );
+        let (start, end) = (view.start_coords.unwrap(), view.end_coords.unwrap());
+        assert!((start.latitude - 1.0).abs() < 1e-9 && (start.longitude + 2.0).abs() < 1e-9);
+        assert!((end.latitude + 3.0).abs() < 1e-9 && (end.longitude - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_format_speed() {
+        let speed = Speed::from_metres_per_second(10.0);
+        assert_eq!(
+            format_speed(speed, 1, SpeedUnit::MetersPerSecond),
+            "10.0 m/s"
+        );
+        assert_eq!(format_speed(speed, 1, SpeedUnit::KmPerHour), "36.0 km/h");
+        assert_eq!(format_speed(speed, 1, SpeedUnit::Mph), "22.4 mph");
+        assert_eq!(format_speed(speed, 2, SpeedUnit::Knots), "19.44 kn");
+        assert_eq!(
+            SpeedUnit::from(UnitSystem::Metric),
+            SpeedUnit::MetersPerSecond
+        );
+        assert_eq!(SpeedUnit::from(UnitSystem::Imperial), SpeedUnit::Mph);
+    }
+
+    #[test]
+    fn test_format_eta() {
+        let distance = Length::from_metres(3600.0);
+        assert_eq!(
+            format_eta(distance, Some(Speed::from_metres_per_second(2.0))),
+            "0:30:00"
+        );
+        assert_eq!(
+            format_eta(distance, Some(Speed::from_metres_per_second(0.5))),
+            "2:00:00"
+        );
+        assert_eq!(
+            format_eta(distance, Some(Speed::from_metres_per_second(0.0))),
+            "—"
+        );
+        assert_eq!(
+            format_eta(distance, Some(Speed::from_metres_per_second(1e-9))),
+            "—"
+        );
+        assert_eq!(
+            format_eta(distance, Some(Speed::from_metres_per_second(0.4))),
+            "—"
+        );
+        assert_eq!(format_eta(distance, None), "—");
+    }
+
+    #[test]
+    fn test_gps_staleness() {
+        let fix = DateTime::from_timestamp(100, 0).unwrap();
+        let at = |secs| Some(DateTime::from_timestamp(secs, 0).unwrap());
+        assert_eq!(fix_age(fix, None), None);
+        assert!(!is_stale(fix_age(fix, None)));
+        assert_eq!(fix_age(fix, at(105)), Some(TimeDelta::seconds(5)));
+        assert!(!is_stale(fix_age(fix, at(105))));
+        assert!(!is_stale(fix_age(fix, at(110))));
+        assert!(is_stale(fix_age(fix, at(111))));
+        // The current time is updated less often than positions may arrive.
+        assert_eq!(fix_age(fix, at(99)), Some(TimeDelta::zero()));
+    }
+
+    #[test]
+    fn test_gps_error_message() {
+        let model = Model {
+            curr_pos: Some(Err(GeoError::TimeoutWithMessage {
+                message: "Timeout expired".into(),
+            })),
+            ..Model::default()
+        };
+        assert_eq!(
+            ViewModel::new(&model).gps_status,
+            "GPS Error: Position retrieval timed out: Timeout expired"
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let mut model = Model::default();
+        let view = ViewModel::new(&model);
+        assert_eq!(view.fingerprint(), ViewModel::new(&model).fingerprint());
+        model.msg = "Hello".into();
+        assert_ne!(view.fingerprint(), ViewModel::new(&model).fingerprint());
+    }
+
+    #[test]
+    fn test_relative_bearing() {
+        let relative = |bearing: f64, heading: f64| {
+            relative_bearing(Angle::from_degrees(bearing), Angle::from_degrees(heading))
+                .as_degrees()
+        };
+        assert!((relative(10.0, 350.0) - 20.0).abs() < 1e-9);
+        assert!((relative(350.0, 10.0) - 340.0).abs() < 1e-9);
+        assert!((relative(-90.0, 180.0) - 90.0).abs() < 1e-9);
+        assert_eq!(relative(123.0, 123.0), 0.0);
+        let degrees = relative(0.0, 1e-14);
+        assert!((0.0..360.0).contains(&degrees), "{degrees}");
+
+        let from = LatLong::from_degrees(0.0, 0.0);
+        let to = LatLong::from_degrees(1.0, 1.0);
+        let text = format_distance_and_bearing(from, to, None, UnitSystem::Metric);
+        assert!(text.ends_with(", 45° (NE)"), "{text}");
+        let text = format_distance_and_bearing(
+            from,
+            to,
+            Some(Angle::from_degrees(90.0)),
+            UnitSystem::Metric,
+        );
+        assert!(text.ends_with(", 315° from the heading"), "{text}");
+        let text = format_distance_and_bearing(
+            from,
+            to,
+            Some(Angle::from_degrees(45.4)),
+            UnitSystem::Metric,
+        );
+        assert!(text.ends_with(", 0° from the heading"), "{text}");
+    }
+
+    #[test]
+    fn test_travel_direction() {
+        let direction = |heading, target| {
+            TravelDirection::new(Angle::from_degrees(heading), Angle::from_degrees(target))
+        };
+        assert_eq!(direction(0.0, 0.0), TravelDirection::Toward);
+        assert_eq!(direction(350.0, 30.0), TravelDirection::Toward);
+        assert_eq!(direction(10.0, 340.0), TravelDirection::Toward);
+        assert_eq!(direction(0.0, 90.0), TravelDirection::Right);
+        assert_eq!(direction(300.0, 30.0), TravelDirection::Right);
+        assert_eq!(direction(0.0, 270.0), TravelDirection::Left);
+        assert_eq!(direction(30.0, 300.0), TravelDirection::Left);
+        assert_eq!(direction(0.0, 180.0), TravelDirection::Away);
+        assert_eq!(direction(350.0, 160.0), TravelDirection::Away);
+        assert_eq!(direction(170.0, 340.0), TravelDirection::Away);
+        // Headings may be negative or above 360°.
+        assert_eq!(direction(-90.0, 270.0), TravelDirection::Toward);
+        assert_eq!(direction(450.0, 0.0), TravelDirection::Left);
+    }
+
+    #[test]
+    fn test_target_direction() {
+        let mut model = Model::default();
+        let mut geo = GeoInfo {
+            timestamp: DateTime::UNIX_EPOCH,
+            coords: LatLong::from_degrees(0.0, 0.0),
+            altitude: None,
+            accuracy: None,
+            altitude_accuracy: None,
+            bearing: None,
+            volocity: None,
+            source: None,
+            satellites: None,
+        };
+        // The target is to the north.
+        let target = SavedPos::new(
+            "Target".into(),
+            &GeoInfo {
+                coords: LatLong::from_degrees(1.0, 0.0),
+                ..geo.clone()
+            },
+        );
+        model
+            .saved_positions_names
+            .insert(target.name.clone(), target);
+        model.curr_pos = Some(Ok(geo.clone()));
+        assert_eq!(ViewModel::new(&model).target_direction, None);
+        model.target = Some("Target".into());
+        // The heading is unknown.
+        assert_eq!(ViewModel::new(&model).target_direction, None);
+        geo.bearing = Some(Angle::from_degrees(90.0));
+        model.curr_pos = Some(Ok(geo.clone()));
+        assert_eq!(
+            ViewModel::new(&model).target_direction,
+            Some(TravelDirection::Left)
+        );
+        geo.volocity = Some(Speed::from_metres_per_second(1.0));
+        model.curr_pos = Some(Ok(geo.clone()));
+        assert_eq!(
+            ViewModel::new(&model).target_direction,
+            Some(TravelDirection::Left)
+        );
+
+        // A stationary fix, where the heading is meaningless or not a number.
+        geo.volocity = Some(Speed::from_metres_per_second(0.0));
+        model.curr_pos = Some(Ok(geo.clone()));
+        assert_eq!(ViewModel::new(&model).target_direction, None);
+        geo.volocity = None;
+        geo.bearing = Some(Angle::from_degrees(f64::NAN));
+        model.curr_pos = Some(Ok(geo));
+        assert_eq!(ViewModel::new(&model).target_direction, None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        for (secs, expected, clock_expected) in [
+            (-5, "0:00:00", "00:00:00"),
+            (0, "0:00:00", "00:00:00"),
+            (59, "0:00:59", "00:00:59"),
+            (61, "0:01:01", "00:01:01"),
+            (3600, "1:00:00", "01:00:00"),
+            (36_000 + 754, "10:12:34", "10:12:34"),
+            (100 * 3600, "100:00:00", "100:00:00"),
+        ] {
+            assert_eq!(format_duration(TimeDelta::seconds(secs), false), expected);
+            assert_eq!(
+                format_duration(TimeDelta::seconds(secs), true),
+                clock_expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_recording_duration() {
+        let mut model = Model::default();
+        assert_eq!(ViewModel::new(&model).recording_duration, None);
+        model.curr_time = DateTime::from_timestamp(100, 0);
+        // There are no nodes.
+        model.all_positions = Some(RecordedWay::new());
+        assert_eq!(ViewModel::new(&model).recording_duration, None);
+        for secs in [10, 40] {
+            model.all_positions.as_mut().unwrap().add(&GeoInfo {
+                timestamp: DateTime::from_timestamp(secs, 0).unwrap(),
+                coords: LatLong::from_degrees(0.0, secs as f64 / 1000.0),
+                altitude: None,
+                accuracy: None,
+                altitude_accuracy: None,
+                bearing: None,
+                volocity: None,
+                source: None,
+                satellites: None,
+            });
         }
+        assert_eq!(
+            ViewModel::new(&model).recording_duration.as_deref(),
+            Some("00:01:30")
+        );
+        // The duration follows the current time.
+        model.curr_time = DateTime::from_timestamp(3700, 0);
+        assert_eq!(
+            ViewModel::new(&model).recording_duration.as_deref(),
+            Some("01:01:30")
+        );
+        // The current time isn't known.
+        model.curr_time = None;
+        assert_eq!(ViewModel::new(&model).recording_duration, None);
     }
 }
@@ -22,3 +22,121 @@ pub(crate) fn lte(left: f64, right: f64) -> bool {
 pub(crate) fn gte(left: f64, right: f64) -> bool {
     left >= right || eq(left, right)
 }
+
+/// The values which aren't NaN, sorted with [f64::total_cmp].
+fn sorted(values: &[f64]) -> Vec<f64> {
+    let mut values = values
+        .iter()
+        .copied()
+        .filter(|x| !x.is_nan())
+        .collect::<Vec<_>>();
+    values.sort_by(f64::total_cmp);
+    values
+}
+
+/// The arithmetic mean of the values. NaN values are ignored, and `None` is returned if there
+/// are no other values.
+pub(crate) fn mean(values: &[f64]) -> Option<f64> {
+    let (sum, n) = values
+        .iter()
+        .filter(|x| !x.is_nan())
+        .fold((0.0, 0usize), |(sum, n), x| (sum + x, n + 1));
+    (n > 0).then(|| sum / n as f64)
+}
+
+/// The `p`:th percentile of the values, where `p` is between 0 and 1, interpolating linearly
+/// between the closest values. NaN values are ignored, and `None` is returned if there are no
+/// other values or if `p` is not between 0 and 1.
+pub(crate) fn percentile(values: &[f64], p: f64) -> Option<f64> {
+    if !(0.0..=1.0).contains(&p) {
+        return None;
+    }
+    let values = sorted(values);
+    if values.is_empty() {
+        return None;
+    }
+    let rank = p * (values.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    let fraction = rank - lower as f64;
+    Some(values[lower] + (values[upper] - values[lower]) * fraction)
+}
+
+/// The median of the values, see [percentile].
+pub(crate) fn median(values: &[f64]) -> Option<f64> {
+    percentile(values, 0.5)
+}
+
+/// The population standard deviation of the values. NaN values are ignored, and `None` is
+/// returned if there are no other values.
+pub(crate) fn std_dev(values: &[f64]) -> Option<f64> {
+    let mean = mean(values)?;
+    let squares = values
+        .iter()
+        .filter(|x| !x.is_nan())
+        .map(|x| (x - mean).powi(2))
+        .collect::<Vec<_>>();
+    Some(self::mean(&squares)?.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(mean(&[]), None);
+        assert_eq!(median(&[]), None);
+        assert_eq!(percentile(&[], 0.9), None);
+        assert_eq!(std_dev(&[]), None);
+        // Only NaN is like empty.
+        assert_eq!(mean(&[f64::NAN]), None);
+        assert_eq!(median(&[f64::NAN, f64::NAN]), None);
+        assert_eq!(std_dev(&[f64::NAN]), None);
+    }
+
+    #[test]
+    fn test_single_element() {
+        assert_eq!(mean(&[3.0]), Some(3.0));
+        assert_eq!(median(&[3.0]), Some(3.0));
+        assert_eq!(percentile(&[3.0], 0.0), Some(3.0));
+        assert_eq!(percentile(&[3.0], 1.0), Some(3.0));
+        assert_eq!(std_dev(&[3.0]), Some(0.0));
+    }
+
+    #[test]
+    fn test_odd_and_even_length() {
+        let odd = [5.0, 1.0, 3.0];
+        assert_eq!(mean(&odd), Some(3.0));
+        assert_eq!(median(&odd), Some(3.0));
+        assert_eq!(percentile(&odd, 0.0), Some(1.0));
+        assert_eq!(percentile(&odd, 0.25), Some(2.0));
+        assert_eq!(percentile(&odd, 1.0), Some(5.0));
+
+        let even = [4.0, 1.0, 3.0, 2.0];
+        assert_eq!(mean(&even), Some(2.5));
+        assert_eq!(median(&even), Some(2.5));
+        assert!((percentile(&even, 0.9).unwrap() - 3.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_std_dev() {
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(std_dev(&values), Some(2.0));
+    }
+
+    #[test]
+    fn test_nan_is_ignored() {
+        let values = [f64::NAN, 1.0, 2.0, f64::NAN, 3.0];
+        assert_eq!(mean(&values), Some(2.0));
+        assert_eq!(median(&values), Some(2.0));
+        assert_eq!(percentile(&values, 1.0), Some(3.0));
+        assert!((std_dev(&values).unwrap() - (2.0f64 / 3.0).sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bad_percentile() {
+        assert_eq!(percentile(&[1.0], 1.5), None);
+        assert_eq!(percentile(&[1.0], -0.1), None);
+        assert_eq!(percentile(&[1.0], f64::NAN), None);
+    }
+}
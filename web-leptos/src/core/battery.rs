@@ -0,0 +1,52 @@
+//! Report the battery status to the core using the
+//! [Battery Status API](https://developer.mozilla.org/en-US/docs/Web/API/Battery_Status_API).
+//!
+//! The API is deprecated or missing in some browsers, so it is feature detected and nothing
+//! happens if it is absent.
+use leptos::signal_prelude::*;
+use leptos::{spawn_local, web_sys};
+use shared::Event;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::BatteryManager;
+
+/// Send an `Event::BatteryStatus` now and whenever the battery level or charging state changes.
+pub fn watch_battery(set_event: WriteSignal<Event>) {
+    let navigator = web_sys::window()
+        .expect("There should be a window.")
+        .navigator();
+    // `getBattery` is not bound by web-sys since it is non-standard, so it is looked up and called
+    // dynamically.
+    let Ok(get_battery) = js_sys::Reflect::get(&navigator, &JsValue::from_str("getBattery"))
+        .and_then(|x| x.dyn_into::<js_sys::Function>())
+    else {
+        return;
+    };
+    let Ok(promise) = get_battery
+        .call0(&navigator)
+        .and_then(|x| x.dyn_into::<js_sys::Promise>())
+    else {
+        return;
+    };
+    spawn_local(async move {
+        let Ok(battery) = JsFuture::from(promise).await else {
+            return;
+        };
+        let battery: BatteryManager = battery.unchecked_into();
+        let send_status = {
+            let battery = battery.clone();
+            move || {
+                set_event.set(Event::BatteryStatus {
+                    level: battery.level(),
+                    charging: battery.charging(),
+                })
+            }
+        };
+        send_status();
+        let on_change = Closure::<dyn Fn()>::new(send_status);
+        battery.set_onlevelchange(Some(on_change.as_ref().unchecked_ref()));
+        battery.set_onchargingchange(Some(on_change.as_ref().unchecked_ref()));
+        // The listeners should live as long as the app.
+        on_change.forget();
+    });
+}
@@ -0,0 +1,47 @@
+//! Let the user pick a file with a hidden `<input type="file">` and send it to the core.
+use std::rc::Rc;
+
+use leptos::{spawn_local, web_sys};
+use shared::{FileUploadRequest, FileUploadResponse, Request};
+use wasm_bindgen::{closure::Closure, JsCast};
+use wasm_bindgen_futures::JsFuture;
+
+use super::Backend;
+
+/// Show a file picker and resolve the request with the picked file.
+///
+/// If the user cancels the picker, the request is never resolved.
+pub fn upload_file(backend: Rc<Backend>, mut req: Request<FileUploadRequest>) {
+    let document = web_sys::window()
+        .expect("There should be a window.")
+        .document()
+        .expect("There should be a document.");
+    let input: web_sys::HtmlInputElement = document
+        .create_element("input")
+        .expect("Failed to create an input element.")
+        .unchecked_into();
+    input.set_type("file");
+    if let Some(accept) = &req.operation.accept {
+        input.set_accept(accept);
+    }
+    let on_change = Closure::once_into_js({
+        let input = input.clone();
+        move || {
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            spawn_local(async move {
+                let Ok(buffer) = JsFuture::from(file.array_buffer()).await else {
+                    return;
+                };
+                let response = FileUploadResponse {
+                    file_name: Some(file.name().into()),
+                    content: js_sys::Uint8Array::new(&buffer).to_vec(),
+                };
+                backend.process_effects(backend.core.resolve(&mut req, response));
+            });
+        }
+    });
+    input.set_onchange(Some(on_change.unchecked_ref()));
+    input.click();
+}
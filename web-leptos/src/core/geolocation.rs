@@ -2,7 +2,6 @@
 //! [Geolocation Web API](https://developer.mozilla.org/en-US/docs/Web/API/Geolocation_API)
 //! using leptos_use.
 use std::cell::RefCell;
-use std::cmp;
 use std::rc::Rc;
 use std::time::Duration;
 
@@ -13,6 +12,7 @@ use leptos::{
 };
 use leptos_use::{use_geolocation_with_options, UseGeolocationOptions, UseGeolocationReturn};
 use shared::Request;
+use wasm_bindgen::{closure::Closure, JsCast};
 
 use super::Backend;
 
@@ -28,6 +28,10 @@ pub enum Event {
     Stop,
 }
 
+/// A request for the current position which hasn't been resolved yet. It is `None` when it has
+/// been resolved or cancelled.
+pub type PendingPosition = Rc<RefCell<Option<Request<GeoRequest>>>>;
+
 /// A state for the geo watcher.
 enum GeoWatch {
     /// No watch at the moment.
@@ -38,7 +42,7 @@ enum GeoWatch {
         stop_fn: Box<dyn Fn()>,
     },
     /// The watch is retrying with a timeout.
-    Retry { n: u64, handle: TimeoutHandle },
+    Retry { n: u32, handle: TimeoutHandle },
 }
 
 pub fn create_geo_watch() -> WriteSignal<Event> {
@@ -93,18 +97,29 @@ impl GeoWatch {
             let timestamp = get_timestamp.get();
             let error = get_error.get();
             let geo_response = if let Some(err) = error {
-                let backend = backend.clone();
-                let req = request.clone();
-                let handle = set_timeout_with_handle(
-                    move || set_event.set(Event::Watch { backend, req, opts }),
-                    retry_time(n_retries),
-                )
-                .unwrap();
-                *self_.borrow_mut() = Self::Retry {
-                    n: n_retries + 1,
-                    handle,
-                };
-                convert_error(err)
+                if err.code() == web_sys::PositionError::PERMISSION_DENIED {
+                    // Retrying won't help until the user allows access to the position. The core
+                    // clears the watch and starts a new one when it wants to try again.
+                    self_.borrow_mut().stop();
+                    convert_error(err)
+                } else if let Some(delay) = opts.retry.delay(n_retries) {
+                    let backend = backend.clone();
+                    let req = request.clone();
+                    let handle = set_timeout_with_handle(
+                        move || set_event.set(Event::Watch { backend, req, opts }),
+                        Duration::from_millis(delay),
+                    )
+                    .unwrap();
+                    *self_.borrow_mut() = Self::Retry {
+                        n: n_retries + 1,
+                        handle,
+                    };
+                    convert_error(err)
+                } else {
+                    // No more retries according to the retry policy.
+                    self_.borrow_mut().stop();
+                    GeoResponse::PositionUnavailableError
+                }
             } else {
                 let (Some(coords), Some(timestamp)) = (coords, timestamp) else {
                     return;
@@ -119,6 +134,68 @@ impl GeoWatch {
     }
 }
 
+/// Get the current position once with the `getCurrentPosition` method of the Geolocation API.
+///
+/// This doesn't touch the `GeoWatch`, so any active watch keeps running.
+pub fn get_current_position(backend: Rc<Backend>, request: Request<GeoRequest>, opts: GeoOptions) {
+    // Exactly one of the callbacks will take the request and resolve it, unless it is taken by
+    // `cancel_current_positions` first.
+    let request: PendingPosition = Rc::new(RefCell::new(Some(request)));
+    {
+        let mut pending = backend.pending_positions.borrow_mut();
+        pending.retain(|x| x.borrow().is_some());
+        pending.push(request.clone());
+    }
+    let resolve = Rc::new(move |response: GeoResponse| {
+        if let Some(mut request) = request.borrow_mut().take() {
+            let effects = backend.core.resolve(&mut request, response);
+            backend.process_effects(effects);
+        }
+    });
+    let Some(geolocation) = web_sys::window().and_then(|w| w.navigator().geolocation().ok())
+    else {
+        resolve(GeoResponse::PositionUnavailableError);
+        return;
+    };
+    let on_success = Closure::once_into_js({
+        let resolve = resolve.clone();
+        move |pos: web_sys::Position| resolve(convert_position(pos.coords(), pos.timestamp()))
+    });
+    let on_error = Closure::once_into_js({
+        let resolve = resolve.clone();
+        move |err: web_sys::PositionError| resolve(convert_error(err))
+    });
+    let options = web_sys::PositionOptions::new();
+    options.set_enable_high_accuracy(opts.enable_high_accuracy);
+    options.set_maximum_age(opts.maximum_age.try_into().unwrap_or(u32::MAX));
+    options.set_timeout(
+        opts.timeout
+            .unwrap_or(u64::MAX)
+            .try_into()
+            .unwrap_or(u32::MAX),
+    );
+    if geolocation
+        .get_current_position_with_error_callback_and_options(
+            on_success.unchecked_ref(),
+            Some(on_error.unchecked_ref()),
+            &options,
+        )
+        .is_err()
+    {
+        resolve(GeoResponse::PositionUnavailableError);
+    }
+}
+
+/// Drop all pending requests for the current position without resolving them.
+///
+/// The Geolocation API can't cancel a call to `getCurrentPosition`, so its callbacks are still
+/// called but do nothing.
+pub fn cancel_current_positions(backend: &Backend) {
+    for request in backend.pending_positions.take() {
+        request.borrow_mut().take();
+    }
+}
+
 /// Convert a `GeoOptions` struct from `crux_geolocation` to a similar "options struct" used by
 /// `leptos_use`.
 fn convert_geo_options(opts: GeoOptions) -> UseGeolocationOptions {
@@ -134,13 +211,23 @@ fn convert_geo_options(opts: GeoOptions) -> UseGeolocationOptions {
         )
 }
 
-/// Convert a `web_sys::PositionError` to a `GeoResponse`.
+/// Convert a `web_sys::PositionError` to a `GeoResponse`, with the message from the browser if
+/// it isn't empty.
 fn convert_error(err: web_sys::PositionError) -> GeoResponse {
     use web_sys::PositionError;
+    let message = err.message();
     match err.code() {
         PositionError::PERMISSION_DENIED => GeoResponse::PermissionDeniedError,
-        PositionError::POSITION_UNAVAILABLE => GeoResponse::PositionUnavailableError,
-        PositionError::TIMEOUT => GeoResponse::TimeoutError,
+        PositionError::POSITION_UNAVAILABLE if message.is_empty() => {
+            GeoResponse::PositionUnavailableError
+        }
+        PositionError::POSITION_UNAVAILABLE => GeoResponse::PositionUnavailableErrorWithMessage {
+            message: message.into(),
+        },
+        PositionError::TIMEOUT if message.is_empty() => GeoResponse::TimeoutError,
+        PositionError::TIMEOUT => GeoResponse::TimeoutErrorWithMessage {
+            message: message.into(),
+        },
         x => panic!("Unexpected error code from geolocation: {x}"),
     }
 }
@@ -157,20 +244,9 @@ fn convert_position(coords: web_sys::Coordinates, timestamp: f64) -> GeoResponse
             altitude_accuracy: coords.altitude_accuracy(),
             heading: coords.heading(),
             volocity: coords.speed(),
+            // Browsers don't tell how the position was determined.
+            source: None,
+            satellites: None,
         },
     }
 }
-
-/// Calculate retry time for the nth retry.
-///
-/// Contains lots of hard coded numbers.
-fn retry_time(n: u64) -> Duration {
-    if n == 0 {
-        Duration::ZERO
-    } else {
-        cmp::min(
-            Duration::from_millis(250 * 2u64.pow(n.try_into().unwrap_or(u32::MAX)) - 1),
-            Duration::from_secs(10),
-        )
-    }
-}
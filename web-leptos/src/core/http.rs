@@ -0,0 +1,48 @@
+//! Perform HTTP requests from the core with the Fetch API.
+use std::rc::Rc;
+
+use crux_http::protocol::{HttpRequest, HttpResponse, HttpResult};
+use crux_http::HttpError;
+use leptos::{spawn_local, web_sys};
+use shared::Request;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use super::Backend;
+
+/// Send a request and resolve it with the response. Network errors are resolved as
+/// `HttpError::Io`.
+pub fn send(backend: Rc<Backend>, mut req: Request<HttpRequest>) {
+    let operation = req.operation.clone();
+    spawn_local(async move {
+        let result = match fetch(&operation).await {
+            Ok(response) => HttpResult::Ok(response),
+            Err(e) => HttpResult::Err(HttpError::Io(
+                e.as_string().unwrap_or_else(|| format!("{e:?}")),
+            )),
+        };
+        backend.process_effects(backend.core.resolve(&mut req, result));
+    });
+}
+
+async fn fetch(request: &HttpRequest) -> Result<HttpResponse, JsValue> {
+    let headers = web_sys::Headers::new()?;
+    for header in &request.headers {
+        headers.append(&header.name, &header.value)?;
+    }
+    let init = web_sys::RequestInit::new();
+    init.set_method(&request.method);
+    init.set_headers(&headers);
+    if !request.body.is_empty() {
+        init.set_body(&js_sys::Uint8Array::from(request.body.as_slice()));
+    }
+    let window = web_sys::window().expect("There should be a window.");
+    let response: web_sys::Response =
+        JsFuture::from(window.fetch_with_str_and_init(&request.url, &init))
+            .await?
+            .unchecked_into();
+    let body = JsFuture::from(response.array_buffer()?).await?;
+    Ok(HttpResponse::status(response.status())
+        .body(js_sys::Uint8Array::new(&body).to_vec())
+        .build())
+}
@@ -1,15 +1,23 @@
 #![allow(unused_variables, dead_code)]
+mod battery;
+mod file_upload;
 mod geolocation;
+mod http;
+mod notify;
+mod reverse_geocode;
+mod screen_wake;
 mod storage;
-use std::cell::RefCell;
+mod timers;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use chrono::Utc;
 use crux_geolocation::{GeoOptions, GeoRequest};
 use crux_kv::{value::Value, KeyValueOperation, KeyValueResponse, KeyValueResult};
 use crux_time::{TimeRequest, TimeResponse};
+use leptos::leptos_dom::helpers::TimeoutHandle;
 use leptos::signal_prelude::*;
-use leptos::watch;
+use leptos::{set_timeout_with_handle, watch};
 use shared::{view_types::ViewModel, Effect, Event, FileDownloadRequest, GeoApp, Request};
 
 /// Signals to send events to and get the last view model from the app.
@@ -35,6 +43,14 @@ struct Backend {
     set_file_download: WriteSignal<Option<FileDownloadRequest>>,
     /// A possible current watch on the geolocation API.
     geo_watch: WriteSignal<geolocation::Event>,
+    /// Requests for the current position which may be cancelled.
+    pending_positions: RefCell<Vec<geolocation::PendingPosition>>,
+    /// Pending timers by id, so that they can be cleared with `TimeRequest::Clear`.
+    timers: RefCell<timers::Timers<TimeoutHandle, Request<TimeRequest>>>,
+    /// Whether the core wants the screen to be kept awake.
+    screen_wake_wanted: Cell<bool>,
+    /// The `WakeLockSentinel` of the current screen wake lock.
+    screen_wake_lock: RefCell<Option<wasm_bindgen::JsValue>>,
 }
 
 impl App {
@@ -49,6 +65,10 @@ impl App {
             event,
             set_file_download: file_download.write_only(),
             geo_watch: geolocation::create_geo_watch(),
+            pending_positions: RefCell::new(Vec::new()),
+            timers: RefCell::default(),
+            screen_wake_wanted: Cell::new(false),
+            screen_wake_lock: RefCell::new(None),
         });
         let _ = watch(
             move || event.get(),
@@ -59,6 +79,7 @@ impl App {
             true,
         );
         set_event.set(Event::LoadPersistantData);
+        battery::watch_battery(set_event);
         Self {
             view,
             set_event,
@@ -72,6 +93,7 @@ impl Backend {
     pub fn process_effects(self: &Rc<Self>, effects: impl IntoIterator<Item = Effect>) {
         for effect in effects {
             match effect {
+                // The core only requests a render when the view model has changed.
                 Effect::Render(_) => {
                     self.render.set(Rc::new(self.core.view()));
                 }
@@ -79,42 +101,69 @@ impl Backend {
                 Effect::KeyValue(req) => self.process_storage(req),
                 Effect::Geolocation(req) => self.process_geolocation(req),
                 Effect::FileDownload(req) => self.set_file_download.set(Some(req.operation)),
+                Effect::FileUpload(req) => file_upload::upload_file(self.clone(), req),
+                Effect::Http(req) => http::send(self.clone(), req),
+                Effect::ReverseGeocode(req) => reverse_geocode::reverse_geocode(self.clone(), req),
+                Effect::Notify(req) => notify::notify(req.operation),
+                Effect::ScreenWake(req) => {
+                    screen_wake::set_screen_wake(self.clone(), req.operation.enable)
+                }
             }
         }
     }
 
     /// Process a time request from the core.
     fn process_time(self: Rc<Self>, mut request: Request<TimeRequest>) {
-        match request.operation {
+        let (id, delay) = match request.operation.clone() {
             TimeRequest::Now => {
                 let response = TimeResponse::Now(Utc::now().try_into().unwrap());
                 self.process_effects(self.core.resolve(&mut request, response));
+                return;
             }
-            TimeRequest::NotifyAfter { duration, id } => leptos::set_timeout(
-                move || {
-                    self.process_effects(
-                        self.core
-                            .resolve(&mut request, TimeResponse::DurationElapsed { id }),
-                    )
-                },
+            TimeRequest::NotifyAfter { duration, id } => (
+                id,
                 TryInto::<chrono::TimeDelta>::try_into(duration)
                     .unwrap()
                     .to_std()
                     .unwrap(),
             ),
-            TimeRequest::NotifyAt { instant, id } => leptos::set_timeout(
-                move || {
-                    self.process_effects(
-                        self.core
-                            .resolve(&mut request, TimeResponse::InstantArrived { id }),
-                    )
-                },
+            TimeRequest::NotifyAt { instant, id } => (
+                id,
                 (TryInto::<chrono::DateTime<Utc>>::try_into(instant).unwrap() - Utc::now())
                     .to_std()
                     .unwrap_or(std::time::Duration::ZERO),
             ),
-            TimeRequest::Clear { .. } => panic!("Operation not supported: TimeRequest::Clear"),
-        }
+            TimeRequest::Clear { id } => {
+                // The timer may already have fired.
+                let timer = self.timers.borrow_mut().clear(id.0);
+                if let Some((handle, mut request)) = timer {
+                    handle.clear();
+                    self.process_effects(
+                        self.core
+                            .resolve(&mut request, TimeResponse::Cleared { id }),
+                    );
+                }
+                return;
+            }
+        };
+        let key = id.0;
+        let backend = self.clone();
+        let handle = set_timeout_with_handle(
+            move || {
+                let Some(mut request) = backend.timers.borrow_mut().fire(key) else {
+                    // The timer has been cleared.
+                    return;
+                };
+                let response = match request.operation {
+                    TimeRequest::NotifyAt { .. } => TimeResponse::InstantArrived { id },
+                    _ => TimeResponse::DurationElapsed { id },
+                };
+                backend.process_effects(backend.core.resolve(&mut request, response));
+            },
+            delay,
+        )
+        .unwrap();
+        self.timers.borrow_mut().insert(key, handle, request);
     }
 
     /// Handle persistant storage operations.
@@ -129,10 +178,11 @@ impl Backend {
                 self.process_effects(self.core.resolve(&mut request, response));
             }
             KeyValueOperation::Set { key, value } => {
+                let previous = storage::get(&key);
                 storage::set(key, value);
                 let response = KeyValueResult::Ok {
                     response: KeyValueResponse::Set {
-                        previous: Value::None,
+                        previous: previous.map(Value::Bytes).unwrap_or(Value::None),
                     },
                 };
                 self.process_effects(self.core.resolve(&mut request, response));
@@ -153,7 +203,15 @@ impl Backend {
                 };
                 self.process_effects(self.core.resolve(&mut request, response));
             }
-            KeyValueOperation::ListKeys { .. } => unimplemented!(),
+            KeyValueOperation::ListKeys { prefix, .. } => {
+                let response = KeyValueResult::Ok {
+                    response: KeyValueResponse::ListKeys {
+                        keys: storage::list_keys(&prefix),
+                        next_cursor: 0,
+                    },
+                };
+                self.process_effects(self.core.resolve(&mut request, response));
+            }
         }
     }
 
@@ -166,6 +224,10 @@ impl Backend {
                 opts,
             }),
             GeoRequest::ClearWatch => self.geo_watch.set(geolocation::Event::Stop),
+            GeoRequest::GetCurrentPosition(opts) => {
+                geolocation::get_current_position(self.clone(), req, opts)
+            }
+            GeoRequest::CancelCurrentPosition => geolocation::cancel_current_positions(self),
         }
     }
 }
@@ -0,0 +1,30 @@
+//! Notify the user with the
+//! [Notifications API](https://developer.mozilla.org/en-US/docs/Web/API/Notifications_API) and
+//! the [Vibration API](https://developer.mozilla.org/en-US/docs/Web/API/Vibration_API).
+use leptos::web_sys::{self, Notification, NotificationPermission};
+use shared::{NotifyKind, NotifyUserOperation};
+
+/// Show a notification and vibrate, if the browser supports it and the user allows it.
+///
+/// Permission to show notifications is asked for the first time, so that notification is only
+/// vibrated.
+pub fn notify(op: NotifyUserOperation) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let duration = match op.kind {
+        NotifyKind::Info => 100,
+        NotifyKind::Arrival => 400,
+        NotifyKind::Warning => 200,
+    };
+    window.navigator().vibrate_with_duration(duration);
+    match Notification::permission() {
+        NotificationPermission::Granted => {
+            let _ = Notification::new(&op.message);
+        }
+        NotificationPermission::Default => {
+            let _ = Notification::request_permission();
+        }
+        _ => (),
+    }
+}
@@ -0,0 +1,50 @@
+//! Find names of places with the reverse geocoding API of
+//! [Nominatim](https://nominatim.org/release-docs/latest/api/Reverse/).
+use std::rc::Rc;
+
+use leptos::{spawn_local, web_sys};
+use shared::{Request, ReverseGeocodeOperation, ReverseGeocodeResponse};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use super::Backend;
+
+/// The URL of the reverse geocoding API.
+const NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org/reverse";
+
+/// Look up the name of a place and resolve the request with it. The name is `None` if the lookup
+/// fails for any reason.
+pub fn reverse_geocode(backend: Rc<Backend>, mut req: Request<ReverseGeocodeOperation>) {
+    let coords = req.operation.coords;
+    let url = format!(
+        "{NOMINATIM_URL}?format=jsonv2&lat={}&lon={}",
+        coords.latitude().as_degrees(),
+        coords.longitude().as_degrees()
+    );
+    spawn_local(async move {
+        let name = fetch_name(&url).await.ok().flatten();
+        let response = ReverseGeocodeResponse {
+            name: name.map(Into::into),
+        };
+        backend.process_effects(backend.core.resolve(&mut req, response));
+    });
+}
+
+/// Fetch a place from Nominatim and get its name, or its full address if it doesn't have a name.
+async fn fetch_name(url: &str) -> Result<Option<String>, JsValue> {
+    let window = web_sys::window().expect("There should be a window.");
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(url))
+        .await?
+        .unchecked_into();
+    if !response.ok() {
+        return Ok(None);
+    }
+    let json = JsFuture::from(response.json()?).await?;
+    let get = |key: &str| {
+        js_sys::Reflect::get(&json, &key.into())
+            .ok()
+            .and_then(|x| x.as_string())
+            .filter(|x| !x.is_empty())
+    };
+    Ok(get("name").or_else(|| get("display_name")))
+}
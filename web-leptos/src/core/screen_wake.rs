@@ -0,0 +1,68 @@
+//! Keep the screen awake with the
+//! [Screen Wake Lock API](https://developer.mozilla.org/en-US/docs/Web/API/Screen_Wake_Lock_API).
+//!
+//! The API is missing in some browsers, so it is feature detected and nothing happens if it is
+//! absent. The browser releases the lock when the page is hidden, so the core enables it again
+//! when the app is resumed.
+use std::rc::Rc;
+
+use js_sys::{Function, Promise, Reflect};
+use leptos::{spawn_local, web_sys};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+
+use super::Backend;
+
+/// Call a method with at most one argument on a JavaScript object, if it exists.
+fn call_method(obj: &JsValue, name: &str, arg: Option<&JsValue>) -> Option<JsValue> {
+    let method = Reflect::get(obj, &JsValue::from_str(name))
+        .ok()?
+        .dyn_into::<Function>()
+        .ok()?;
+    match arg {
+        Some(arg) => method.call1(obj, arg),
+        None => method.call0(obj),
+    }
+    .ok()
+}
+
+/// Request or release a screen wake lock.
+pub fn set_screen_wake(backend: Rc<Backend>, enable: bool) {
+    backend.screen_wake_wanted.set(enable);
+    if !enable {
+        if let Some(sentinel) = backend.screen_wake_lock.take() {
+            call_method(&sentinel, "release", None);
+        }
+        return;
+    }
+    if backend.screen_wake_lock.borrow().is_some() {
+        return;
+    }
+    let navigator = web_sys::window()
+        .expect("There should be a window.")
+        .navigator();
+    let Some(wake_lock) = Reflect::get(&navigator, &JsValue::from_str("wakeLock"))
+        .ok()
+        .filter(|x| !x.is_undefined())
+    else {
+        return;
+    };
+    let Some(promise) = call_method(&wake_lock, "request", Some(&JsValue::from_str("screen")))
+    else {
+        return;
+    };
+    spawn_local(async move {
+        // The request fails if the page isn't visible.
+        let Ok(sentinel) = JsFuture::from(promise.unchecked_into::<Promise>()).await else {
+            return;
+        };
+        // The lock may have been disabled while it was requested.
+        if backend.screen_wake_wanted.get() {
+            if let Some(previous) = backend.screen_wake_lock.replace(Some(sentinel)) {
+                call_method(&previous, "release", None);
+            }
+        } else {
+            call_method(&sentinel, "release", None);
+        }
+    });
+}
@@ -8,6 +8,7 @@
 use base64::prelude::*;
 use codee::{Decoder, Encoder};
 use leptos::signal_prelude::*;
+use leptos::web_sys;
 use leptos_use::storage::use_local_storage;
 
 /// A base64 encoder/decoder.
@@ -52,3 +53,15 @@ pub fn delete(key: impl AsRef<str>) {
     let (_, _, delete_fn) = use_local_storage::<_, Base64Codee>(key);
     delete_fn();
 }
+
+/// List all keys in persistant storage which start with a prefix.
+pub fn list_keys(prefix: &str) -> Vec<String> {
+    let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) else {
+        return Vec::new();
+    };
+    let len = storage.length().unwrap_or(0);
+    (0..len)
+        .filter_map(|i| storage.key(i).ok().flatten())
+        .filter(|key| key.starts_with(prefix))
+        .collect()
+}
@@ -0,0 +1,74 @@
+//! Bookkeeping of pending timers, so that they can be cleared by id.
+
+use std::collections::HashMap;
+
+/// Pending timers by id, each with a handle to cancel its timeout with and the request to resolve
+/// when it fires or is cleared.
+#[derive(Debug)]
+pub struct Timers<H, R> {
+    pending: HashMap<usize, (H, R)>,
+}
+
+impl<H, R> Default for Timers<H, R> {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl<H, R> Timers<H, R> {
+    /// Add a pending timer. A pending timer with the same id is replaced and returned.
+    pub fn insert(&mut self, id: usize, handle: H, request: R) -> Option<(H, R)> {
+        self.pending.insert(id, (handle, request))
+    }
+
+    /// Take the request of a timer whose timeout has fired, or `None` if it has been cleared.
+    pub fn fire(&mut self, id: usize) -> Option<R> {
+        self.pending.remove(&id).map(|(_, request)| request)
+    }
+
+    /// Take the handle and request of a timer to clear it, or `None` if it has already fired or
+    /// been cleared.
+    pub fn clear(&mut self, id: usize) -> Option<(H, R)> {
+        self.pending.remove(&id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fire() {
+        let mut timers = Timers::default();
+        assert_eq!(timers.insert(1, "handle 1", "request 1"), None);
+        assert_eq!(timers.insert(2, "handle 2", "request 2"), None);
+        assert_eq!(timers.fire(1), Some("request 1"));
+        // A timer only fires once, and can't be cleared after it has fired.
+        assert_eq!(timers.fire(1), None);
+        assert_eq!(timers.clear(1), None);
+        assert_eq!(timers.fire(2), Some("request 2"));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut timers = Timers::default();
+        timers.insert(1, "handle 1", "request 1");
+        assert_eq!(timers.clear(1), Some(("handle 1", "request 1")));
+        // A cleared timer does nothing when its timeout fires.
+        assert_eq!(timers.fire(1), None);
+        assert_eq!(timers.clear(1), None);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut timers = Timers::default();
+        timers.insert(1, "old handle", "old request");
+        assert_eq!(
+            timers.insert(1, "new handle", "new request"),
+            Some(("old handle", "old request"))
+        );
+        assert_eq!(timers.fire(1), Some("new request"));
+    }
+}
@@ -15,7 +15,7 @@ use leptos::{
 };
 use shared::{
     view_types::{ViewModel, ViewObject},
-    Event,
+    Event, UnitSystem,
 };
 
 #[component]
@@ -34,6 +34,11 @@ fn RootComponent() -> impl IntoView {
             &v.recorded_ways
         }),
         save_way_component(app),
+        clear_way_component(app),
+        named_recording_component(app),
+        keep_high_accuracy_component(app),
+        live_autosave_component(app),
+        unit_system_component(app),
         show_msg_component(app),
         file_download_component(app),
         footer_component(),
@@ -43,14 +48,50 @@ fn RootComponent() -> impl IntoView {
 fn curr_pos_component(app: App) -> impl IntoView {
     let body = move || {
         let view = app.view.get();
-        ("Status: ", view.gps_status.to_string(), move || {
+        let status = view.gps_status.to_string();
+        // Make a stale position stand out, since it may no longer be correct.
+        let status = if view.gps_stale {
+            html::strong().child(status).into_view()
+        } else {
+            status.into_view()
+        };
+        ("Status: ", status, move || {
             view.curr_pos_properties
                 .iter()
                 .map(|x| (html::br(), x.to_string()))
                 .collect::<Vec<_>>()
         })
     };
-    html::section().child((html::h3().child("Current Position"), html::p().child(body)))
+    let odometer = move || ("Odometer: ", app.view.get().odometer.to_string());
+    let target = move || {
+        let view = app.view.get();
+        let direction = view
+            .target_direction
+            .map(|x| format!(" (heading {x})"))
+            .unwrap_or_default();
+        view.target
+            .as_ref()
+            .map(|x| html::p().child(format!("{x}{direction}")))
+    };
+    let recording_duration = move || {
+        app.view
+            .get()
+            .recording_duration
+            .as_ref()
+            .map(|x| html::p().child(("Recording for: ", x.to_string())))
+    };
+    html::section().child((
+        html::h3().child("Current Position"),
+        html::p().child(body),
+        html::p().child((
+            odometer,
+            html::button()
+                .on(ev::click, move |_| app.set_event.set(Event::ResetOdometer))
+                .child("Reset"),
+        )),
+        recording_duration,
+        target,
+    ))
 }
 
 fn list_items<T: ViewObject>(
@@ -80,6 +121,23 @@ fn list_items<T: ViewObject>(
                                 .iter()
                                 .map(|x| (x.to_string(), html::br()))
                                 .collect::<Vec<_>>(),
+                            (!item.more_properties().is_empty()).then(|| {
+                                html::details().child((
+                                    html::summary().child("More"),
+                                    item.more_properties()
+                                        .iter()
+                                        .map(|x| (x.to_string(), html::br()))
+                                        .collect::<Vec<_>>(),
+                                ))
+                            }),
+                            item.actions()
+                                .into_iter()
+                                .map(|(label, event)| {
+                                    html::button()
+                                        .on(ev::click, move |_| app.set_event.set(event.clone()))
+                                        .child(label)
+                                })
+                                .collect::<Vec<_>>(),
                             item.delete().map(move |del_event| {
                                 html::button()
                                     .on(ev::click, {
@@ -111,6 +169,13 @@ fn save_pos_component(app: App) -> impl IntoView {
                         .attr("type", "text")
                         .attr("name", "name")
                         .attr("autofocus", true)
+                        .attr("placeholder", move || {
+                            app.view
+                                .get()
+                                .suggested_name
+                                .as_ref()
+                                .map(|x| x.to_string())
+                        })
                         .node_ref(input_node),
                 )
                 .child(html::input().attr("type", "submit").attr("value", "Submit"))
@@ -126,7 +191,10 @@ fn save_pos_component(app: App) -> impl IntoView {
                 .into_any()
         } else {
             html::button()
-                .on(ev::click, move |_| set_save_pos_dialog.set(true))
+                .on(ev::click, move |_| {
+                    app.set_event.set(Event::SuggestPosName);
+                    set_save_pos_dialog.set(true);
+                })
                 .child("Save the Current Position ")
                 .into_any()
         }
@@ -167,6 +235,123 @@ fn save_way_component(app: App) -> impl IntoView {
     }
 }
 
+fn clear_way_component(app: App) -> impl IntoView {
+    html::button()
+        .on(ev::click, move |_| {
+            let confirmed = web_sys::window()
+                .and_then(|w| {
+                    w.confirm_with_message("Discard all positions in the current way?")
+                        .ok()
+                })
+                .unwrap_or(false);
+            if confirmed {
+                app.set_event.set(Event::ClearCurrentRecording);
+            }
+        })
+        .child("Clear the Current Way ")
+}
+
+fn named_recording_component(app: App) -> impl IntoView {
+    let (recording_dialog, set_recording_dialog) = create_signal(false);
+    let input_node = create_node_ref::<html::Input>();
+    let name = move || -> CompactString {
+        input_node
+            .get()
+            .expect("Input element should be initialized.")
+            .value()
+            .into()
+    };
+    move || {
+        if recording_dialog.get() {
+            html::p()
+                .child(
+                    html::label()
+                        .attr("for", "name")
+                        .child("Name of the recording"),
+                )
+                .child(
+                    html::input()
+                        .attr("type", "text")
+                        .attr("name", "name")
+                        .attr("autofocus", true)
+                        .node_ref(input_node),
+                )
+                .child(
+                    html::button()
+                        .on(ev::click, move |_| {
+                            app.set_event.set(Event::StartNamedRecording(name()));
+                            set_recording_dialog.set(false);
+                        })
+                        .child("Start"),
+                )
+                .child(
+                    html::button()
+                        .on(ev::click, move |_| {
+                            app.set_event.set(Event::StopNamedRecording(name()));
+                            set_recording_dialog.set(false);
+                        })
+                        .child("Stop"),
+                )
+                .into_any()
+        } else {
+            html::button()
+                .on(ev::click, move |_| set_recording_dialog.set(true))
+                .child("Start or Stop a Named Recording ")
+                .into_any()
+        }
+    }
+}
+
+fn keep_high_accuracy_component(app: App) -> impl IntoView {
+    html::p().child(
+        html::label().child((
+            html::input().attr("type", "checkbox").on(ev::change, move |ev| {
+                let checked = event_target::<web_sys::HtmlInputElement>(&ev).checked();
+                app.set_event.set(Event::SetKeepHighAccuracy(checked));
+            }),
+            " Keep high accuracy on low battery",
+        )),
+    )
+}
+
+/// How often the current way is saved for recovery after a crash, when autosaving is enabled.
+const LIVE_AUTOSAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+fn live_autosave_component(app: App) -> impl IntoView {
+    html::p().child(
+        html::label().child((
+            html::input()
+                .attr("type", "checkbox")
+                .prop("checked", move || app.view.get().live_autosave)
+                .on(ev::change, move |ev| {
+                    let checked = event_target::<web_sys::HtmlInputElement>(&ev).checked();
+                    app.set_event.set(Event::SetLiveAutosave(
+                        checked.then_some(LIVE_AUTOSAVE_INTERVAL),
+                    ));
+                }),
+            " Save the current way every minute for recovery after a crash",
+        )),
+    )
+}
+
+fn unit_system_component(app: App) -> impl IntoView {
+    html::p().child(
+        html::label().child((
+            html::input()
+                .attr("type", "checkbox")
+                .on(ev::change, move |ev| {
+                    let checked = event_target::<web_sys::HtmlInputElement>(&ev).checked();
+                    app.set_event.set(Event::SetUnitSystem(if checked {
+                        UnitSystem::Imperial
+                    } else {
+                        UnitSystem::Metric
+                    }));
+                }),
+            " Use imperial units",
+        )),
+    )
+}
+
 fn show_msg_component(app: App) -> impl IntoView {
     html::div().child((
         html::hr(),
@@ -207,9 +392,26 @@ fn file_download_component(app: App) -> impl IntoView {
                 .child("Cancel");
             html::p().child((download_link, cancel_button)).into_any()
         } else {
-            html::button()
-                .on(ev::click, move |_| app.set_event.set(Event::DownloadData))
-                .child("Download all Saved Data as JSON")
+            html::p()
+                .child((
+                    html::button()
+                        .on(ev::click, move |_| app.set_event.set(Event::DownloadData))
+                        .child("Download all Saved Data as JSON"),
+                    html::button()
+                        .on(ev::click, move |_| app.set_event.set(Event::ExportKml))
+                        .child("Download as KML"),
+                    html::button()
+                        .on(ev::click, move |_| app.set_event.set(Event::UploadGpx))
+                        .child("Import GPX File"),
+                    html::button()
+                        .on(ev::click, move |_| app.set_event.set(Event::UploadData))
+                        .child("Import JSON File"),
+                    html::button()
+                        .on(ev::click, move |_| {
+                            app.set_event.set(Event::ReportStorageUsage)
+                        })
+                        .child("Show Storage Usage"),
+                ))
                 .into_any()
         }
     }